@@ -1,4 +1,5 @@
 extern crate chrono;
+extern crate chrono_tz;
 extern crate env_logger;
 #[macro_use]
 extern crate log;
@@ -8,21 +9,22 @@ extern crate serde_json;
 extern crate uuid;
 extern crate warp;
 
-use chrono::{Duration, Utc};
+use chrono::{Duration, Timelike, Utc};
+use chrono_tz::Tz;
 use robohome_crypto::{
     bufify_string, gen_pair as gen_auth_key_pair, gen_shared_secret,
 };
 use robohome_shared::{
+    config::{Config, Requirement},
     data::{
-        get_all_switches, get_auth_age, get_private_shared, new_token,
-        update_flip as db_update_flip, update_switch as db_update_switch, Flip, ScheduledFlip,
-        Switch,
+        self, Direction, Flip, FlipUpdate, Group, GroupFlip, Hold, NewFlip, Pause, Scene,
+        SceneActivation, ScheduledFlip, Season, Store, Switch, TimedFlip, Vacation,
     },
-    ipc::send,
-    Error,
+    ipc::Ipc,
+    time, Error,
 };
 use serde_json::to_string;
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc};
 use uuid::Uuid;
 
 use warp::{
@@ -35,50 +37,200 @@ use warp::{
 fn main() {
     ::std::env::set_var("RUST_LOG", "info");
     env_logger::init();
+    let config = match Config::load(&[Requirement::Database, Requirement::Amqp]) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to load config: {}", e);
+            ::std::process::exit(1);
+        }
+    };
+    let db = match data::connect(&config.database) {
+        Ok(db) => db,
+        Err(e) => {
+            error!("Failed to connect to database: {}", e);
+            ::std::process::exit(1);
+        }
+    };
+    match db.migrate() {
+        Ok(applied) => {
+            for version in applied {
+                info!("Applied schema migration {}", version);
+            }
+        }
+        Err(e) => {
+            error!("Failed to migrate database: {}", e);
+            ::std::process::exit(1);
+        }
+    }
+    let db = warp::any().map(move || db.clone());
+    let ipc = Ipc::new(&config.amqp.url);
+    let ipc = warp::any().map(move || ipc.clone());
+    let tz = config.location.time_zone;
+    let tz = warp::any().map(move || tz);
     let auth_head = header("Authorization");
     let flipping = post2()
         .and(path("flip"))
+        .and(db.clone())
+        .and(ipc.clone())
         .and(auth_head)
         .and(json())
         .map(flip_switch);
+    let flip_for = post2()
+        .and(path("flip_for"))
+        .and(db.clone())
+        .and(ipc.clone())
+        .and(tz.clone())
+        .and(auth_head)
+        .and(json())
+        .map(flip_for);
     let all_switches = get2()
         .and(path("switches"))
+        .and(db.clone())
         .and(auth_head)
         .map(get_switches);
     let switch_flips = put2()
         .and(path("switch"))
+        .and(db.clone())
         .and(auth_head)
         .and(json())
         .map(get_switch_flips);
+    let flip_group = post2()
+        .and(path("flip_group"))
+        .and(db.clone())
+        .and(ipc.clone())
+        .and(tz.clone())
+        .and(auth_head)
+        .and(json())
+        .map(flip_group);
+    let all_groups = get2()
+        .and(path("groups"))
+        .and(db.clone())
+        .and(auth_head)
+        .map(get_groups);
+    let group_flips = put2()
+        .and(path("group"))
+        .and(db.clone())
+        .and(auth_head)
+        .and(json())
+        .map(get_group_flips);
+    let activate_scene = post2()
+        .and(path("activate_scene"))
+        .and(db.clone())
+        .and(ipc.clone())
+        .and(tz.clone())
+        .and(auth_head)
+        .and(json())
+        .map(activate_scene);
+    let all_scenes = get2()
+        .and(path("scenes"))
+        .and(db.clone())
+        .and(auth_head)
+        .map(get_scenes);
+    let scene_flips = put2()
+        .and(path("scene"))
+        .and(db.clone())
+        .and(auth_head)
+        .and(json())
+        .map(get_scene_flips);
     let key_exchange = post2()
         .and(path("key-exchange"))
+        .and(db.clone())
         .and(auth_head)
         .and(content_length_limit(64))
         .and(concat())
         .map(check_auth_token);
     let update_switch = post2()
         .and(path("update_switch"))
+        .and(db.clone())
+        .and(ipc.clone())
         .and(auth_head)
         .and(json())
         .map(update_switch);
+    let new_flip = post2()
+        .and(path("new_flip"))
+        .and(db.clone())
+        .and(ipc.clone())
+        .and(auth_head)
+        .and(json())
+        .map(new_flip);
     let update_flip = post2()
         .and(path("update_flip"))
+        .and(db.clone())
+        .and(ipc.clone())
         .and(auth_head)
         .and(json())
         .map(update_flip);
+    let vacation = get2()
+        .and(path("vacation"))
+        .and(db.clone())
+        .and(auth_head)
+        .map(get_vacation);
+    let update_vacation = post2()
+        .and(path("vacation"))
+        .and(db.clone())
+        .and(ipc.clone())
+        .and(auth_head)
+        .and(json())
+        .map(update_vacation);
+    let pause = get2()
+        .and(path("pause"))
+        .and(db.clone())
+        .and(auth_head)
+        .map(get_pause);
+    let update_pause = post2()
+        .and(path("pause"))
+        .and(db.clone())
+        .and(ipc.clone())
+        .and(auth_head)
+        .and(json())
+        .map(update_pause);
+    let holds = get2()
+        .and(path("holds"))
+        .and(db.clone())
+        .and(auth_head)
+        .map(get_holds);
+    let hold = post2()
+        .and(path("hold"))
+        .and(db.clone())
+        .and(ipc.clone())
+        .and(auth_head)
+        .and(json())
+        .map(hold_switch);
+    let release = post2()
+        .and(path("release"))
+        .and(db.clone())
+        .and(ipc.clone())
+        .and(auth_head)
+        .and(json())
+        .map(release_switch);
     let routes = flipping
+        .or(flip_for)
+        .or(flip_group)
         .or(switch_flips)
         .or(all_switches)
+        .or(group_flips)
+        .or(all_groups)
+        .or(activate_scene)
+        .or(scene_flips)
+        .or(all_scenes)
         .or(update_switch)
+        .or(new_flip)
         .or(update_flip)
+        .or(vacation)
+        .or(update_vacation)
+        .or(pause)
+        .or(update_pause)
+        .or(holds)
+        .or(hold)
+        .or(release)
         .or(key_exchange)
         .or(warp::filters::fs::dir("public"));
-    warp::serve(routes.with(warp::log("robohome_flipper"))).run(([0, 0, 0, 0], 3434));
+    warp::serve(routes.with(warp::log("robohome_flipper"))).run(config.flipper.bind);
 }
 
-fn check_auth_header(header: String) -> Result<bool, Error> {
+fn check_auth_header(db: &dyn Store, header: String) -> Result<bool, Error> {
     let (id, public) = break_auth_header(&header)?;
-    let (private, shared) = get_private_shared(&id)?;
+    let (private, shared) = db.get_private_shared(&id)?;
     let computed_shared =
         gen_shared_secret(&private, &public).ok_or(Error::new("Invalid public key provided"))?;
     Ok(&*computed_shared == shared.as_slice())
@@ -102,9 +254,9 @@ fn break_auth_header(header: &str) -> Result<(Uuid, Vec<u8>), Error> {
     Ok((id, public))
 }
 
-fn flip_switch(header: String, flip: Flip) -> impl Reply {
+fn flip_switch(db: Arc<dyn Store>, ipc: Ipc, header: String, flip: Flip) -> impl Reply {
     info!("POST /flip: {:?}", flip);
-    match check_auth_header(header) {
+    match check_auth_header(&*db, header) {
         Ok(success) => {
             if !success {
                 return Response::builder()
@@ -117,7 +269,7 @@ fn flip_switch(header: String, flip: Flip) -> impl Reply {
             return Response::builder().status(status).body(body);
         }
     }
-    match send("switches", &flip) {
+    match ipc.send("switches", &flip) {
         Ok(_) => Response::builder().body(format!(r#"{{"flipped": {}}}"#, flip.code)),
         Err(e) => {
             let (status, body) = error_response(&e);
@@ -126,9 +278,158 @@ fn flip_switch(header: String, flip: Flip) -> impl Reply {
     }
 }
 
-fn get_switch_flips(header: String, switch: Switch) -> impl Reply {
+fn flip_for(db: Arc<dyn Store>, ipc: Ipc, tz: Tz, header: String, timed: TimedFlip) -> impl Reply {
+    info!("POST /flip_for: {:?}", timed);
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    if timed.minutes < 1 {
+        return Response::builder()
+            .status(400)
+            .body(format!(r#"{{ "message": "Invalid duration" }}"#));
+    }
+    let flip = match get_timed_flip(&*db, &tz, &timed) {
+        Ok(flip) => flip,
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    };
+    if let Err(e) = ipc.send("switches", &flip) {
+        let (status, body) = error_response(&e);
+        return Response::builder().status(status).body(body);
+    }
+    notify_database(&ipc);
+    Response::builder().body(format!(r#"{{"flipped": {}}}"#, flip.code))
+}
+
+/// The `Flip` turning the switch on, the off
+/// is recorded first so the scheduler sends
+/// it even if it restarts in the meantime
+fn get_timed_flip(db: &dyn Store, tz: &Tz, timed: &TimedFlip) -> Result<Flip, Error> {
+    let switch = db
+        .get_all_switches()?
+        .into_iter()
+        .find(|s| s.id == timed.switch_id)
+        .ok_or(Error::Other(format!(
+            "switch {} does not exist",
+            timed.switch_id
+        )))?;
+    let due = Utc::now() + Duration::minutes(i64::from(timed.minutes));
+    db.new_pending_off(switch.off_code, due)?;
+    Ok(flips_now(vec![switch.on_code], tz).remove(0))
+}
+
+fn flip_group(db: Arc<dyn Store>, ipc: Ipc, tz: Tz, header: String, flip: GroupFlip) -> impl Reply {
+    info!("POST /flip_group: {:?}", flip);
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let flips = match get_group_flip_codes(&*db, &tz, &flip) {
+        Ok(flips) => flips,
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    };
+    for flip in flips.iter() {
+        if let Err(e) = ipc.send("switches", flip) {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    Response::builder().body(format!(r#"{{"flipped": {}}}"#, flips.len()))
+}
+
+/// One `Flip` for each switch in
+/// the requested group
+fn get_group_flip_codes(db: &dyn Store, tz: &Tz, flip: &GroupFlip) -> Result<Vec<Flip>, Error> {
+    let group = db.get_group(flip.group_id)?;
+    Ok(flips_now(group.codes(flip.direction), tz))
+}
+
+fn activate_scene(
+    db: Arc<dyn Store>,
+    ipc: Ipc,
+    tz: Tz,
+    header: String,
+    activation: SceneActivation,
+) -> impl Reply {
+    info!("POST /activate_scene: {:?}", activation);
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let flips = match get_scene_flip_codes(&*db, &tz, activation.scene_id) {
+        Ok(flips) => flips,
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    };
+    for flip in flips.iter() {
+        if let Err(e) = ipc.send("switches", flip) {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    Response::builder().body(format!(r#"{{"flipped": {}}}"#, flips.len()))
+}
+
+/// One `Flip` for each member of the
+/// scene as it is currently stored
+fn get_scene_flip_codes(db: &dyn Store, tz: &Tz, scene_id: i32) -> Result<Vec<Flip>, Error> {
+    let scene = db.get_scene(scene_id)?;
+    Ok(flips_now(scene.codes(Direction::On), tz))
+}
+
+/// Stamped with the wall-clock time in `tz`,
+/// the same as the scheduler's flips
+fn flips_now(codes: Vec<i32>, tz: &Tz) -> Vec<Flip> {
+    let now = time::local_now(Utc::now(), tz);
+    codes
+        .into_iter()
+        .map(|code| Flip {
+            hour: now.hour() as i32,
+            minute: now.minute() as i32,
+            code,
+            duration: None,
+        })
+        .collect()
+}
+
+fn get_switch_flips(db: Arc<dyn Store>, header: String, switch: Switch) -> impl Reply {
     info!("PUT /switch");
-    match check_auth_header(header) {
+    match check_auth_header(&*db, header) {
         Ok(success) => {
             if !success {
                 return Response::builder()
@@ -141,13 +442,89 @@ fn get_switch_flips(header: String, switch: Switch) -> impl Reply {
             return Response::builder().status(status).body(body);
         }
     }
-    let (status, body) = get_switch_flips_response(switch);
+    let (status, body) = get_switch_flips_response(&*db, switch);
     Response::builder().status(status).body(body)
 }
 
-fn get_switches(header: String) -> impl Reply {
+fn get_switches(db: Arc<dyn Store>, header: String) -> impl Reply {
     info!("GET /switches");
-    match check_auth_header(header) {
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let (status, body) = get_switches_response(&*db);
+    Response::builder().status(status).body(body)
+}
+
+fn get_groups(db: Arc<dyn Store>, header: String) -> impl Reply {
+    info!("GET /groups");
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let (status, body) = get_groups_response(&*db);
+    Response::builder().status(status).body(body)
+}
+
+fn get_group_flips(db: Arc<dyn Store>, header: String, group: Group) -> impl Reply {
+    info!("PUT /group");
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let (status, body) = get_group_flips_response(&*db, group);
+    Response::builder().status(status).body(body)
+}
+
+fn get_scenes(db: Arc<dyn Store>, header: String) -> impl Reply {
+    info!("GET /scenes");
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let (status, body) = get_scenes_response(&*db);
+    Response::builder().status(status).body(body)
+}
+
+fn get_scene_flips(db: Arc<dyn Store>, header: String, scene: Scene) -> impl Reply {
+    info!("PUT /scene");
+    match check_auth_header(&*db, header) {
         Ok(success) => {
             if !success {
                 return Response::builder()
@@ -160,13 +537,13 @@ fn get_switches(header: String) -> impl Reply {
             return Response::builder().status(status).body(body);
         }
     }
-    let (status, body) = get_switches_response();
+    let (status, body) = get_scene_flips_response(&*db, scene);
     Response::builder().status(status).body(body)
 }
 
-fn update_switch(header: String, switch: Switch) -> impl Reply {
+fn update_switch(db: Arc<dyn Store>, ipc: Ipc, header: String, switch: Switch) -> impl Reply {
     info!("POST /switch {:?}", switch);
-    match check_auth_header(header) {
+    match check_auth_header(&*db, header) {
         Ok(success) => {
             if !success {
                 return Response::builder()
@@ -179,13 +556,13 @@ fn update_switch(header: String, switch: Switch) -> impl Reply {
             return Response::builder().status(status).body(body);
         }
     }
-    let (status, body) = get_update_switch_response(switch);
+    let (status, body) = get_update_switch_response(&*db, &ipc, switch);
     Response::builder().status(status).body(body)
 }
 
-fn update_flip(header: String, flip: ScheduledFlip) -> impl Reply {
-    info!("POST /flip {:?}", flip);
-    match check_auth_header(header) {
+fn new_flip(db: Arc<dyn Store>, ipc: Ipc, header: String, flip: NewFlip) -> impl Reply {
+    info!("POST /new_flip {:?}", flip);
+    match check_auth_header(&*db, header) {
         Ok(success) => {
             if !success {
                 return Response::builder()
@@ -198,30 +575,331 @@ fn update_flip(header: String, flip: ScheduledFlip) -> impl Reply {
             return Response::builder().status(status).body(body);
         }
     }
-    let (status, body) = get_update_flip_response(flip);
+    let (status, body) = get_new_flip_response(&*db, &ipc, flip);
+    Response::builder().status(status).body(body)
+}
+
+fn update_flip(db: Arc<dyn Store>, ipc: Ipc, header: String, update: FlipUpdate) -> impl Reply {
+    info!("POST /flip {:?}", update);
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(401)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let (status, body) = get_update_flip_response(&*db, &ipc, update);
+    Response::builder().status(status).body(body)
+}
+
+fn get_vacation(db: Arc<dyn Store>, header: String) -> impl Reply {
+    info!("GET /vacation");
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let (status, body) = get_vacation_response(&*db);
+    Response::builder().status(status).body(body)
+}
+
+fn update_vacation(db: Arc<dyn Store>, ipc: Ipc, header: String, vacation: Vacation) -> impl Reply {
+    info!("POST /vacation {:?}", vacation);
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let (status, body) = get_update_vacation_response(&*db, &ipc, vacation);
     Response::builder().status(status).body(body)
 }
 
-fn get_update_flip_response(flip: ScheduledFlip) -> (u16, String) {
-    match db_update_flip(
+fn get_pause(db: Arc<dyn Store>, header: String) -> impl Reply {
+    info!("GET /pause");
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let (status, body) = get_pause_response(&*db);
+    Response::builder().status(status).body(body)
+}
+
+fn update_pause(db: Arc<dyn Store>, ipc: Ipc, header: String, pause: Pause) -> impl Reply {
+    info!("POST /pause {:?}", pause);
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let (status, body) = get_update_pause_response(&*db, &ipc, pause);
+    Response::builder().status(status).body(body)
+}
+
+fn get_holds(db: Arc<dyn Store>, header: String) -> impl Reply {
+    info!("GET /holds");
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let (status, body) = get_holds_response(&*db);
+    Response::builder().status(status).body(body)
+}
+
+fn hold_switch(db: Arc<dyn Store>, ipc: Ipc, header: String, hold: Hold) -> impl Reply {
+    info!("POST /hold {:?}", hold);
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let (status, body) = get_hold_response(&*db, &ipc, hold);
+    Response::builder().status(status).body(body)
+}
+
+fn release_switch(db: Arc<dyn Store>, ipc: Ipc, header: String, hold: Hold) -> impl Reply {
+    info!("POST /release {:?}", hold);
+    match check_auth_header(&*db, header) {
+        Ok(success) => {
+            if !success {
+                return Response::builder()
+                    .status(403)
+                    .body(format!(r#"{{"message": "Unauthorized"}}"#));
+            }
+        }
+        Err(e) => {
+            let (status, body) = error_response(&e);
+            return Response::builder().status(status).body(body);
+        }
+    }
+    let (status, body) = get_release_response(&*db, &ipc, hold);
+    Response::builder().status(status).body(body)
+}
+
+fn get_update_flip_response(
+    db: &dyn Store,
+    notifier: &dyn Notifier,
+    update: FlipUpdate,
+) -> (u16, String) {
+    let flip = match db.get_flip(update.id) {
+        Ok(flip) => update.apply(flip),
+        Err(e) => return error_response(&e),
+    };
+    if let Some(invalid) = invalid_flip_response(flip.season, flip.duration) {
+        return invalid;
+    }
+    match db.update_flip(
         flip.id,
         flip.hour,
         flip.minute,
         flip.dow,
         flip.direction,
         flip.kind,
+        flip.offset,
+        flip.duration,
+        flip.season,
     ) {
         Ok(flip) => match to_string(&flip) {
-            Ok(body) => (200, body),
+            Ok(body) => {
+                notify_database(notifier);
+                (200, body)
+            }
+            Err(e) => error_response(&Error::from(e)),
+        },
+        Err(e) => error_response(&e),
+    }
+}
+
+fn get_new_flip_response(db: &dyn Store, notifier: &dyn Notifier, flip: NewFlip) -> (u16, String) {
+    if let Some(invalid) = invalid_flip_response(flip.season, flip.duration) {
+        return invalid;
+    }
+    match db.new_scheduled_flip(
+        flip.switch_id,
+        flip.hour,
+        flip.minute,
+        flip.dow,
+        flip.direction,
+        flip.kind,
+        flip.offset,
+        flip.duration,
+        flip.season,
+    ) {
+        Ok(flip) => match to_string(&flip) {
+            Ok(body) => {
+                notify_database(notifier);
+                (200, body)
+            }
             Err(e) => error_response(&Error::from(e)),
         },
         Err(e) => error_response(&e),
     }
 }
 
-fn get_update_switch_response(switch: Switch) -> (u16, String) {
-    match db_update_switch(switch.id, &switch.name, switch.on_code, switch.off_code) {
+/// The 400 for a season or duration
+/// that can't be stored
+fn invalid_flip_response(season: Option<Season>, duration: Option<i32>) -> Option<(u16, String)> {
+    if let Some(season) = season {
+        if !season.is_valid() {
+            return Some((400, format!(r#"{{ "message": "Invalid season" }}"#)));
+        }
+    }
+    if duration.map(|d| d < 1).unwrap_or(false) {
+        return Some((400, format!(r#"{{ "message": "Invalid duration" }}"#)));
+    }
+    None
+}
+
+fn get_update_switch_response(
+    db: &dyn Store,
+    notifier: &dyn Notifier,
+    switch: Switch,
+) -> (u16, String) {
+    match db.update_switch(switch.id, &switch.name, switch.on_code, switch.off_code) {
         Ok(sw) => match to_string(&sw) {
+            Ok(body) => {
+                notify_database(notifier);
+                (200, body)
+            }
+            Err(e) => error_response(&Error::from(e)),
+        },
+        Err(e) => error_response(&e),
+    }
+}
+
+/// The scheduler picks the change up
+/// when it reloads today's flips
+fn get_update_vacation_response(
+    db: &dyn Store,
+    notifier: &dyn Notifier,
+    vacation: Vacation,
+) -> (u16, String) {
+    if let Err(e) = db.update_vacation(vacation) {
+        return error_response(&e);
+    }
+    notify_database(notifier);
+    get_vacation_response(db)
+}
+
+fn get_vacation_response(db: &dyn Store) -> (u16, String) {
+    match db.get_vacation() {
+        Ok(vacation) => match to_string(&vacation) {
+            Ok(body) => (200, body),
+            Err(e) => error_response(&Error::from(e)),
+        },
+        Err(e) => error_response(&e),
+    }
+}
+
+fn get_update_pause_response(
+    db: &dyn Store,
+    notifier: &dyn Notifier,
+    pause: Pause,
+) -> (u16, String) {
+    if pause.paused && !pause.is_paused(Utc::now()) {
+        return (400, format!(r#"{{ "message": "Invalid resume time" }}"#));
+    }
+    if let Err(e) = db.update_pause(pause) {
+        return error_response(&e);
+    }
+    notify_database(notifier);
+    get_pause_response(db)
+}
+
+fn get_pause_response(db: &dyn Store) -> (u16, String) {
+    match db.get_pause() {
+        Ok(pause) => match to_string(&pause) {
+            Ok(body) => (200, body),
+            Err(e) => error_response(&Error::from(e)),
+        },
+        Err(e) => error_response(&e),
+    }
+}
+
+fn get_hold_response(db: &dyn Store, notifier: &dyn Notifier, hold: Hold) -> (u16, String) {
+    if !hold.is_held(Utc::now()) {
+        return (400, format!(r#"{{ "message": "Invalid hold time" }}"#));
+    }
+    match db.new_hold(hold.switch_id, hold.until) {
+        Ok(hold) => match to_string(&hold) {
+            Ok(body) => {
+                notify_database(notifier);
+                (200, body)
+            }
+            Err(e) => error_response(&Error::from(e)),
+        },
+        Err(e) => error_response(&e),
+    }
+}
+
+fn get_release_response(db: &dyn Store, notifier: &dyn Notifier, hold: Hold) -> (u16, String) {
+    match db.remove_hold(hold.switch_id) {
+        Ok(count) => {
+            notify_database(notifier);
+            (200, format!(r#"{{"released": {}}}"#, count))
+        }
+        Err(e) => error_response(&e),
+    }
+}
+
+fn get_holds_response(db: &dyn Store) -> (u16, String) {
+    match db.get_holds() {
+        Ok(holds) => match to_string(&holds) {
             Ok(body) => (200, body),
             Err(e) => error_response(&Error::from(e)),
         },
@@ -229,8 +907,8 @@ fn get_update_switch_response(switch: Switch) -> (u16, String) {
     }
 }
 
-fn get_switches_response() -> (u16, String) {
-    match get_all_switches() {
+fn get_switches_response(db: &dyn Store) -> (u16, String) {
+    match db.get_all_switches() {
         Ok(switches) => match to_string(&switches) {
             Ok(body) => (200, body),
             Err(e) => error_response(&Error::from(e)),
@@ -239,8 +917,48 @@ fn get_switches_response() -> (u16, String) {
     }
 }
 
-fn get_switch_flips_response(switch: Switch) -> (u16, String) {
-    match robohome_shared::data::get_flips_for_switch(switch.id) {
+fn get_switch_flips_response(db: &dyn Store, switch: Switch) -> (u16, String) {
+    match db.get_flips_for_switch(switch.id) {
+        Ok(flips) => match to_string(&flips) {
+            Ok(body) => (200, body),
+            Err(e) => error_response(&Error::from(e)),
+        },
+        Err(e) => error_response(&e),
+    }
+}
+
+fn get_groups_response(db: &dyn Store) -> (u16, String) {
+    match db.get_all_groups() {
+        Ok(groups) => match to_string(&groups) {
+            Ok(body) => (200, body),
+            Err(e) => error_response(&Error::from(e)),
+        },
+        Err(e) => error_response(&e),
+    }
+}
+
+fn get_group_flips_response(db: &dyn Store, group: Group) -> (u16, String) {
+    match db.get_flips_for_group(group.id) {
+        Ok(flips) => match to_string(&flips) {
+            Ok(body) => (200, body),
+            Err(e) => error_response(&Error::from(e)),
+        },
+        Err(e) => error_response(&e),
+    }
+}
+
+fn get_scenes_response(db: &dyn Store) -> (u16, String) {
+    match db.get_all_scenes() {
+        Ok(scenes) => match to_string(&scenes) {
+            Ok(body) => (200, body),
+            Err(e) => error_response(&Error::from(e)),
+        },
+        Err(e) => error_response(&e),
+    }
+}
+
+fn get_scene_flips_response(db: &dyn Store, scene: Scene) -> (u16, String) {
+    match db.get_flips_for_scene(scene.id) {
         Ok(flips) => match to_string(&flips) {
             Ok(body) => (200, body),
             Err(e) => error_response(&Error::from(e)),
@@ -249,9 +967,9 @@ fn get_switch_flips_response(switch: Switch) -> (u16, String) {
     }
 }
 
-fn check_auth_token(header: String, body: warp::body::FullBody) -> impl Reply {
+fn check_auth_token(db: Arc<dyn Store>, header: String, body: warp::body::FullBody) -> impl Reply {
     info!("POST /key-exchange");
-    let (status, body) = match check_auth_token_response(&header, body) {
+    let (status, body) = match check_auth_token_response(&*db, &header, body.bytes()) {
         Ok((status, body)) => (status, body),
         Err(e) => error_response(&e),
     };
@@ -259,21 +977,20 @@ fn check_auth_token(header: String, body: warp::body::FullBody) -> impl Reply {
 }
 
 fn check_auth_token_response(
+    db: &dyn Store,
     header: &String,
-    body: warp::body::FullBody,
+    buf: &[u8],
 ) -> Result<(u16, String), Error> {
     info!("check_auth_token_response {}", header);
     let start = "Bearer ".len();
     let token = &header[start..];
     let token = Uuid::from_str(token)?;
     info!("parsed token");
-    let age = get_auth_age(&token)?;
+    let age = db.get_auth_age(&token)?;
     info!("got auth age");
     if Utc::now().signed_duration_since(age) > Duration::days(1) {
         return Ok((401, format!(r#"{{"status": "expired"}}"#)));
     }
-    info!("Extracting body key");
-    let buf = body.bytes();
     info!("Generating server pair");
     let key_pair = gen_auth_key_pair();
     info!("getting server private key");
@@ -282,10 +999,413 @@ fn check_auth_token_response(
     let shared = gen_shared_secret(&my_private, buf)
         .ok_or(Error::new("Failed to generate shared secret"))?;
     info!("storing key info");
-    new_token(&my_private, &shared, token)?;
+    db.new_token(&my_private, &shared, token)?;
     Ok((200, format!(r#"{{"status": "success"}}"#)))
 }
 
+/// Something that can pass an empty
+/// message on to one of the queues
+trait Notifier {
+    fn notify(&self, queue: &str) -> Result<(), Error>;
+}
+
+impl Notifier for Ipc {
+    fn notify(&self, queue: &str) -> Result<(), Error> {
+        self.send(queue, &())
+    }
+}
+
+/// Let the scheduler know that it
+/// needs to reload today's flips
+fn notify_database(notifier: &dyn Notifier) {
+    if let Err(e) = notifier.notify("database") {
+        error!("Failed to send db update message {}", e);
+    }
+}
+
 fn error_response(e: &Error) -> (u16, String) {
     (500, format!(r#"{{ "message": "{}" }}"#, e))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use robohome_crypto::stringify_buf;
+    use robohome_shared::data::{DayOfTheWeek, FlipKind, MemoryStore, MonthDay};
+    use std::cell::RefCell;
+
+    /// Keeps the queues it was asked to
+    /// notify instead of sending anything
+    #[derive(Default)]
+    struct Recorder {
+        sent: RefCell<Vec<String>>,
+    }
+
+    impl Notifier for Recorder {
+        fn notify(&self, queue: &str) -> Result<(), Error> {
+            self.sent.borrow_mut().push(queue.to_string());
+            Ok(())
+        }
+    }
+
+    impl Recorder {
+        fn take(&self) -> Vec<String> {
+            self.sent.borrow_mut().drain(..).collect()
+        }
+    }
+
+    #[test]
+    fn key_exchange() {
+        let store = MemoryStore::new();
+        let auth = store.new_auth().unwrap();
+        let client = gen_auth_key_pair();
+        let (status, _) =
+            check_auth_token_response(&store, &format!("Bearer {}", auth), &client.public())
+                .unwrap();
+        assert_eq!(status, 200);
+        let header = format!("Bearer {}${}", auth, stringify_buf(&client.public()));
+        assert!(check_auth_header(&store, header).unwrap());
+        let other = gen_auth_key_pair();
+        let header = format!("Bearer {}${}", auth, stringify_buf(&other.public()));
+        assert!(!check_auth_header(&store, header).unwrap());
+        // a token can only be exchanged once
+        assert!(
+            check_auth_token_response(&store, &format!("Bearer {}", auth), &client.public())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn switch_listing() {
+        let store = MemoryStore::new();
+        let sw = store.new_switch("Living Room", 4543795, 4543804).unwrap();
+        store
+            .new_scheduled_flip(sw.id, 7, 0, 31.into(), Direction::On, FlipKind::Custom, 0, None, None)
+            .unwrap();
+        let (status, body) = get_switches_response(&store);
+        assert_eq!(status, 200);
+        let switches: Vec<Switch> = serde_json::from_str(&body).unwrap();
+        assert_eq!(switches, vec![sw.clone()]);
+        let (status, body) = get_switch_flips_response(&store, sw);
+        assert_eq!(status, 200);
+        let flips: Vec<ScheduledFlip> = serde_json::from_str(&body).unwrap();
+        assert_eq!(flips.len(), 1);
+        assert_eq!(flips[0].direction, Direction::On);
+    }
+
+    #[test]
+    fn group_flipping() {
+        let store = MemoryStore::new();
+        let lamp = store.new_switch("Lamp", 4543795, 4543804).unwrap();
+        let tree = store.new_switch("Tree", 4543939, 4543948).unwrap();
+        let group = store.new_group("Living Room").unwrap();
+        store.add_group_member(group.id, lamp.id).unwrap();
+        store.add_group_member(group.id, tree.id).unwrap();
+        let request = GroupFlip {
+            group_id: group.id,
+            direction: Direction::Off,
+        };
+        let codes: Vec<i32> = get_group_flip_codes(&store, &Tz::UTC, &request)
+            .unwrap()
+            .into_iter()
+            .map(|f| f.code)
+            .collect();
+        assert_eq!(codes, vec![4543804, 4543948]);
+        let (status, body) = get_groups_response(&store);
+        assert_eq!(status, 200);
+        let groups: Vec<Group> = serde_json::from_str(&body).unwrap();
+        assert_eq!(groups[0].switches, vec![lamp, tree]);
+        let missing = GroupFlip {
+            group_id: -1,
+            direction: Direction::On,
+        };
+        assert!(get_group_flip_codes(&store, &Tz::UTC, &missing).is_err());
+    }
+
+    #[test]
+    fn manual_flips_use_local_time() {
+        let tz = Tz::Asia__Kolkata;
+        let before = time::local_now(Utc::now(), &tz);
+        let flip = flips_now(vec![4543795], &tz).remove(0);
+        let after = time::local_now(Utc::now(), &tz);
+        let stamped = (flip.hour as u32, flip.minute as u32);
+        assert!(
+            stamped == (before.hour(), before.minute())
+                || stamped == (after.hour(), after.minute())
+        );
+    }
+
+    #[test]
+    fn timed_flipping() {
+        let store = MemoryStore::new();
+        let fan = store.new_switch("Fan", 4543795, 4543804).unwrap();
+        let request: TimedFlip =
+            serde_json::from_str(&format!(r#"{{"switchId": {}, "minutes": 45}}"#, fan.id)).unwrap();
+        let before = Utc::now();
+        let flip = get_timed_flip(&store, &Tz::UTC, &request).unwrap();
+        assert_eq!(flip.code, 4543795);
+        let offs = store.get_pending_offs().unwrap();
+        assert_eq!(offs.len(), 1);
+        assert_eq!(offs[0].code, 4543804);
+        assert!(offs[0].due >= before + Duration::minutes(45));
+        let missing = TimedFlip {
+            switch_id: -1,
+            minutes: 45,
+        };
+        assert!(get_timed_flip(&store, &Tz::UTC, &missing).is_err());
+    }
+
+    #[test]
+    fn pausing() {
+        let store = MemoryStore::new();
+        let notifier = Recorder::default();
+        let (status, body) = get_pause_response(&store);
+        assert_eq!(status, 200);
+        assert_eq!(body, r#"{"paused":false,"resumeAt":null}"#);
+        let request: Pause = serde_json::from_str(r#"{"paused": true}"#).unwrap();
+        let (status, _) = get_update_pause_response(&store, &notifier, request);
+        assert_eq!(status, 200);
+        assert_eq!(notifier.take(), vec!["database"]);
+        assert!(store.get_pause().unwrap().is_paused(Utc::now()));
+        let past = Pause {
+            paused: true,
+            resume_at: Some(Utc::now() - Duration::minutes(5)),
+        };
+        let (status, _) = get_update_pause_response(&store, &notifier, past);
+        assert_eq!(status, 400);
+        assert!(notifier.take().is_empty());
+        assert_eq!(store.get_pause().unwrap(), request);
+    }
+
+    #[test]
+    fn holding() {
+        let store = MemoryStore::new();
+        let notifier = Recorder::default();
+        let lamp = store.new_switch("Living Room", 4543795, 4543804).unwrap();
+        let request: Hold =
+            serde_json::from_str(&format!(r#"{{"switchId": {}}}"#, lamp.id)).unwrap();
+        let (status, _) = get_hold_response(&store, &notifier, request);
+        assert_eq!(status, 200);
+        assert_eq!(notifier.take(), vec!["database"]);
+        let (status, body) = get_holds_response(&store);
+        assert_eq!(status, 200);
+        let holds: Vec<Hold> = serde_json::from_str(&body).unwrap();
+        assert_eq!(holds, vec![request]);
+        let expired = Hold {
+            until: Some(Utc::now() - Duration::minutes(5)),
+            ..request
+        };
+        let (status, _) = get_hold_response(&store, &notifier, expired);
+        assert_eq!(status, 400);
+        assert!(notifier.take().is_empty());
+        let (status, body) = get_release_response(&store, &notifier, request);
+        assert_eq!(status, 200);
+        assert_eq!(body, r#"{"released": 1}"#);
+        assert_eq!(notifier.take(), vec!["database"]);
+        assert!(store.get_holds().unwrap().is_empty());
+    }
+
+    #[test]
+    fn scene_activation() {
+        let store = MemoryStore::new();
+        let lamp = store.new_switch("Lamp", 4543795, 4543804).unwrap();
+        let tree = store.new_switch("Tree", 4543939, 4543948).unwrap();
+        let scene = store.new_scene("Movie Night").unwrap();
+        store.set_scene_member(scene.id, lamp.id, Direction::Off).unwrap();
+        store.set_scene_member(scene.id, tree.id, Direction::On).unwrap();
+        let activation: SceneActivation =
+            serde_json::from_str(&format!(r#"{{"sceneId": {}}}"#, scene.id)).unwrap();
+        let codes: Vec<i32> = get_scene_flip_codes(&store, &Tz::UTC, activation.scene_id)
+            .unwrap()
+            .into_iter()
+            .map(|f| f.code)
+            .collect();
+        assert_eq!(codes, vec![4543804, 4543939]);
+        let (status, body) = get_scenes_response(&store);
+        assert_eq!(status, 200);
+        let scenes: Vec<Scene> = serde_json::from_str(&body).unwrap();
+        assert_eq!(scenes[0].members.len(), 2);
+        assert!(get_scene_flip_codes(&store, &Tz::UTC, -1).is_err());
+    }
+
+    #[test]
+    fn vacation_toggle() {
+        let store = MemoryStore::new();
+        let notifier = Recorder::default();
+        let (status, body) = get_vacation_response(&store);
+        assert_eq!(status, 200);
+        assert_eq!(body, r#"{"enabled":false}"#);
+        let request: Vacation = serde_json::from_str(r#"{"enabled": true}"#).unwrap();
+        let (status, body) = get_update_vacation_response(&store, &notifier, request);
+        assert_eq!(status, 200);
+        assert_eq!(body, r#"{"enabled":true}"#);
+        assert_eq!(notifier.take(), vec!["database"]);
+        assert!(store.get_vacation().unwrap().enabled);
+    }
+
+    #[test]
+    fn timed_flip_creation() {
+        let store = MemoryStore::new();
+        let notifier = Recorder::default();
+        let fan = store.new_switch("Fan", 4543795, 4543804).unwrap();
+        let mut request: NewFlip = serde_json::from_str(&format!(
+            r#"{{"switchId": {}, "hour": 7, "minute": 0, "dow": {}, "direction": "On",
+                "kind": "Custom", "duration": 45}}"#,
+            fan.id,
+            serde_json::to_string(&DayOfTheWeek::from(127)).unwrap()
+        ))
+        .unwrap();
+        let (status, _) = get_new_flip_response(&store, &notifier, request.clone());
+        assert_eq!(status, 200);
+        assert_eq!(notifier.take(), vec!["database"]);
+        let saved = store.get_flips_for_switch(fan.id).unwrap();
+        assert_eq!(saved[0].duration, Some(45));
+        request.duration = Some(0);
+        let (status, _) = get_new_flip_response(&store, &notifier, request);
+        assert_eq!(status, 400);
+        assert!(notifier.take().is_empty());
+    }
+
+    #[test]
+    fn seasonal_update() {
+        let store = MemoryStore::new();
+        let notifier = Recorder::default();
+        let tree = store.new_switch("X-mas Tree", 4543939, 4543948).unwrap();
+        let flip = store
+            .new_scheduled_flip(tree.id, 18, 0, 127.into(), Direction::On, FlipKind::Custom, 0, None, None)
+            .unwrap();
+        let mut request: FlipUpdate = serde_json::from_str(&format!(
+            r#"{{"id": {}, "hour": 18, "minute": 0, "dow": {}, "direction": "On", "kind": "Custom",
+                "season": {{"start": {{"month": 11, "day": 25}}, "end": {{"month": 1, "day": 6}}}}}}"#,
+            flip.id,
+            serde_json::to_string(&flip.dow).unwrap()
+        ))
+        .unwrap();
+        let (status, _) = get_update_flip_response(&store, &notifier, request.clone());
+        assert_eq!(status, 200);
+        assert_eq!(notifier.take(), vec!["database"]);
+        let saved = store.get_flips_for_switch(tree.id).unwrap();
+        assert_eq!(Some(saved[0].season), request.season);
+        request.season.as_mut().unwrap().as_mut().unwrap().end.month = 13;
+        let (status, _) = get_update_flip_response(&store, &notifier, request);
+        assert_eq!(status, 400);
+        assert!(notifier.take().is_empty());
+    }
+
+    #[test]
+    fn update_keeps_omitted_season() {
+        let store = MemoryStore::new();
+        let notifier = Recorder::default();
+        let tree = store.new_switch("X-mas Tree", 4543939, 4543948).unwrap();
+        let season = Season {
+            start: MonthDay { month: 11, day: 25 },
+            end: MonthDay { month: 1, day: 6 },
+        };
+        let flip = store
+            .new_scheduled_flip(
+                tree.id,
+                18,
+                0,
+                127.into(),
+                Direction::On,
+                FlipKind::Custom,
+                0,
+                None,
+                Some(season),
+            )
+            .unwrap();
+        let request: FlipUpdate = serde_json::from_str(&format!(
+            r#"{{"id": {}, "hour": 19, "minute": 30, "dow": {}, "direction": "On", "kind": "Custom"}}"#,
+            flip.id,
+            serde_json::to_string(&flip.dow).unwrap()
+        ))
+        .unwrap();
+        let (status, _) = get_update_flip_response(&store, &notifier, request);
+        assert_eq!(status, 200);
+        let saved = store.get_flips_for_switch(tree.id).unwrap();
+        assert_eq!(saved[0].hour, 19);
+        assert_eq!(saved[0].season, Some(season));
+        let cleared: FlipUpdate = serde_json::from_str(&format!(
+            r#"{{"id": {}, "hour": 19, "minute": 30, "dow": {}, "direction": "On", "kind": "Custom",
+                "season": null}}"#,
+            flip.id,
+            serde_json::to_string(&flip.dow).unwrap()
+        ))
+        .unwrap();
+        let (status, _) = get_update_flip_response(&store, &notifier, cleared);
+        assert_eq!(status, 200);
+        let saved = store.get_flips_for_switch(tree.id).unwrap();
+        assert_eq!(saved[0].season, None);
+    }
+
+    #[test]
+    fn update_keeps_omitted_offset() {
+        let store = MemoryStore::new();
+        let notifier = Recorder::default();
+        let porch = store.new_switch("Porch", 4543795, 4543804).unwrap();
+        let flip = store
+            .new_scheduled_flip(
+                porch.id,
+                18,
+                0,
+                127.into(),
+                Direction::On,
+                FlipKind::Sunset,
+                -20,
+                None,
+                None,
+            )
+            .unwrap();
+        let request: FlipUpdate = serde_json::from_str(&format!(
+            r#"{{"id": {}, "hour": 18, "minute": 0, "dow": {}, "direction": "On", "kind": "Dusk"}}"#,
+            flip.id,
+            serde_json::to_string(&flip.dow).unwrap()
+        ))
+        .unwrap();
+        let (status, _) = get_update_flip_response(&store, &notifier, request);
+        assert_eq!(status, 200);
+        let saved = store.get_flips_for_switch(porch.id).unwrap();
+        assert_eq!(saved[0].kind, FlipKind::Dusk);
+        assert_eq!(saved[0].offset, -20);
+    }
+
+    #[test]
+    fn update_keeps_omitted_duration() {
+        let store = MemoryStore::new();
+        let notifier = Recorder::default();
+        let fan = store.new_switch("Fan", 4543795, 4543804).unwrap();
+        let flip = store
+            .new_scheduled_flip(
+                fan.id,
+                7,
+                0,
+                127.into(),
+                Direction::On,
+                FlipKind::Custom,
+                0,
+                Some(45),
+                None,
+            )
+            .unwrap();
+        let body = |extra: &str| {
+            format!(
+                r#"{{"id": {}, "hour": 7, "minute": 30, "dow": {}, "direction": "On", "kind": "Custom"{}}}"#,
+                flip.id,
+                serde_json::to_string(&flip.dow).unwrap(),
+                extra
+            )
+        };
+        let request: FlipUpdate = serde_json::from_str(&body("")).unwrap();
+        let (status, _) = get_update_flip_response(&store, &notifier, request);
+        assert_eq!(status, 200);
+        let saved = store.get_flips_for_switch(fan.id).unwrap();
+        assert_eq!(saved[0].minute, 30);
+        assert_eq!(saved[0].duration, Some(45));
+        // sent as null it is cleared
+        let request: FlipUpdate = serde_json::from_str(&body(r#", "duration": null"#)).unwrap();
+        let (status, _) = get_update_flip_response(&store, &notifier, request);
+        assert_eq!(status, 200);
+        let saved = store.get_flips_for_switch(fan.id).unwrap();
+        assert_eq!(saved[0].duration, None);
+    }
+}
@@ -1,12 +1,13 @@
 import * as React from 'react';
 import * as ReactDom from 'react-dom';
-import * as moment from 'moment';
 
 interface IAppState {
     switches: Switch[];
     view: View;
     selectedSwitchFlips?: ScheduledFlip[];
     selectedSwitchName?: string;
+    vacation: boolean;
+    paused: boolean;
 }
 
 enum View {
@@ -155,6 +156,8 @@ export class App extends React.Component<{}, IAppState> {
         this.state = {
             switches: [],
             view: View.Loading,
+            vacation: false,
+            paused: false,
         };
     }
     componentDidMount() {
@@ -167,7 +170,16 @@ export class App extends React.Component<{}, IAppState> {
                     switches,
                     view: View.Switches,
                 });
-            })
+            });
+        // a bare `false` can't be wrapped in a Result
+        Http.get('/vacation', (j) => ({enabled: !!j.enabled}))
+            .then((res: Result<{enabled: boolean}>) => {
+                this.setState({vacation: res.unwrapOr({enabled: false}).enabled});
+            });
+        Http.get('/pause', (j) => ({paused: !!j.paused}))
+            .then((res: Result<{paused: boolean}>) => {
+                this.setState({paused: res.unwrapOr({paused: false}).paused});
+            });
     }
 
     render() {
@@ -175,6 +187,22 @@ export class App extends React.Component<{}, IAppState> {
             <div>
                 <header>
                     <h1>Robohome Light Switch</h1>
+                    <label className="vacation-toggle">
+                        <input
+                            type="checkbox"
+                            checked={this.state.vacation}
+                            onChange={() => this.toggleVacation()}
+                        />
+                        Vacation mode
+                    </label>
+                    <label className="pause-toggle">
+                        <input
+                            type="checkbox"
+                            checked={this.state.paused}
+                            onChange={() => this.togglePause()}
+                        />
+                        Pause schedule
+                    </label>
                 </header>
                 <main>
                 {
@@ -213,6 +241,28 @@ export class App extends React.Component<{}, IAppState> {
         });
     }
 
+    toggleVacation() {
+        let enabled = !this.state.vacation;
+        Http.post('/vacation', {enabled}, (j) => ({enabled: !!j.enabled}))
+            .then(r => {
+                if (r.is_err()) {
+                    return this.httpFail(r.errorMessage());
+                }
+                this.setState({vacation: r.unwrap().enabled});
+            });
+    }
+
+    togglePause() {
+        let paused = !this.state.paused;
+        Http.post('/pause', {paused, resumeAt: null}, (j) => ({paused: !!j.paused}))
+            .then(r => {
+                if (r.is_err()) {
+                    return this.httpFail(r.errorMessage());
+                }
+                this.setState({paused: r.unwrap().paused});
+            });
+    }
+
     httpFail(msg) {
         console.error('httpFail', msg);
         this.setState({
@@ -412,6 +462,20 @@ class SwitchInfo extends React.Component<ISwitchInfoProps, ISwitchInfoState> {
                                         </tr>
                                     </tbody>
                                 </table>
+                                <div className="flip-season">
+                                    <label>Season</label>
+                                    <input
+                                        placeholder="MM-DD"
+                                        defaultValue={f.season ? f.season.start.toString() : ''}
+                                        onChange={ev => this.updateSeason(i, 'start', ev.currentTarget.value)}
+                                    />
+                                    <span>to</span>
+                                    <input
+                                        placeholder="MM-DD"
+                                        defaultValue={f.season ? f.season.end.toString() : ''}
+                                        onChange={ev => this.updateSeason(i, 'end', ev.currentTarget.value)}
+                                    />
+                                </div>
                             </div>
                         )
                     })
@@ -449,6 +513,20 @@ class SwitchInfo extends React.Component<ISwitchInfoProps, ISwitchInfoState> {
                 return '🌝';
             case FlipKind.Sunset:
                 return '🌚';
+            case FlipKind.AstronomicalDawn:
+                return '🌌';
+            case FlipKind.NauticalDawn:
+                return '⚓';
+            case FlipKind.CivilDawn:
+                return '🌄';
+            case FlipKind.SolarNoon:
+                return '☀️';
+            case FlipKind.CivilDusk:
+                return '🌇';
+            case FlipKind.NauticalDusk:
+                return '🌊';
+            case FlipKind.AstronomicalDusk:
+                return '🌃';
         }
     }
 
@@ -464,6 +542,20 @@ class SwitchInfo extends React.Component<ISwitchInfoProps, ISwitchInfoState> {
                 return 'Dusk';
             case FlipKind.Sunset:
                 return 'Sunset';
+            case FlipKind.AstronomicalDawn:
+                return 'Astronomical Dawn';
+            case FlipKind.NauticalDawn:
+                return 'Nautical Dawn';
+            case FlipKind.CivilDawn:
+                return 'Civil Dawn';
+            case FlipKind.SolarNoon:
+                return 'Solar Noon';
+            case FlipKind.CivilDusk:
+                return 'Civil Dusk';
+            case FlipKind.NauticalDusk:
+                return 'Nautical Dusk';
+            case FlipKind.AstronomicalDusk:
+                return 'Astronomical Dusk';
         }
     }
 
@@ -471,18 +563,39 @@ class SwitchInfo extends React.Component<ISwitchInfoProps, ISwitchInfoState> {
         let newFlip = this.state.flips[idx].clone();
         switch (newFlip.kind) {
             case FlipKind.Custom:
+                newFlip.kind = FlipKind.AstronomicalDawn;
+            break;
+            case FlipKind.AstronomicalDawn:
+                newFlip.kind = FlipKind.NauticalDawn;
+            break;
+            case FlipKind.NauticalDawn:
                 newFlip.kind = FlipKind.PreDawn;
             break;
             case FlipKind.PreDawn:
+                newFlip.kind = FlipKind.CivilDawn;
+            break;
+            case FlipKind.CivilDawn:
                 newFlip.kind = FlipKind.Sunrise;
             break;
             case FlipKind.Sunrise:
+                newFlip.kind = FlipKind.SolarNoon;
+            break;
+            case FlipKind.SolarNoon:
                 newFlip.kind = FlipKind.Dusk;
             break;
             case FlipKind.Dusk:
                 newFlip.kind = FlipKind.Sunset;
             break;
             case FlipKind.Sunset:
+                newFlip.kind = FlipKind.CivilDusk;
+            break;
+            case FlipKind.CivilDusk:
+                newFlip.kind = FlipKind.NauticalDusk;
+            break;
+            case FlipKind.NauticalDusk:
+                newFlip.kind = FlipKind.AstronomicalDusk;
+            break;
+            case FlipKind.AstronomicalDusk:
                 newFlip.kind = FlipKind.Custom;
             break;
         }
@@ -534,6 +647,31 @@ class SwitchInfo extends React.Component<ISwitchInfoProps, ISwitchInfoState> {
         });
     }
 
+    updateSeason(idx: number, end: 'start' | 'end', text: string) {
+        let flip = this.state.flips[idx].clone();
+        if (text.trim() === '') {
+            // clearing either end makes the flip year-round
+            flip.season = null;
+        } else {
+            let day = MonthDay.parse(text);
+            if (!day) {
+                return;
+            }
+            let season = flip.season ? flip.season.clone() : new Season(day, day);
+            season[end] = day;
+            flip.season = season;
+        }
+        let flips = this.state.flips.map((f, i) => i === idx ? flip : f);
+        let dirtyFlips = this.state.dirtyFlips.map(f => f);
+        if (dirtyFlips.indexOf(idx) < 0) {
+            dirtyFlips.push(idx);
+        }
+        this.setState({
+            flips,
+            dirtyFlips,
+        });
+    }
+
     async save() {
         let newState = {};
         if (this.state.switchDirty) {
@@ -604,16 +742,7 @@ class Switch {
     }
 }
 
-function getLocalOffset() {
-    let offsetMinutes = moment().utcOffset();
-    return {
-        hours: Math.floor(offsetMinutes / 60),
-        minutes: Math.floor(offsetMinutes % 60),
-    }
-}
 class ScheduledFlip {
-    private static offsetHours;
-    private static offsetMinute;
     private _hour: number;
     public minute: number;
     constructor(
@@ -623,14 +752,13 @@ class ScheduledFlip {
         public dow: DayOfTheWeek,
         public flip: Direction,
         public kind: FlipKind,
+        public offset: number = 0,
+        public date: string | null = null,
+        public season: Season | null = null,
+        public duration: number | null = null,
     ) {
-        if (!ScheduledFlip.offsetHours || !ScheduledFlip.offsetMinute) {
-            let {hours, minutes} = getLocalOffset();
-            ScheduledFlip.offsetHours = hours;
-            ScheduledFlip.offsetMinute = minutes;
-        }
-        this._hour = hour;// + ScheduledFlip.offsetHours;
-        this.minute = minute;// + ScheduledFlip.offsetMinute;
+        this._hour = hour;
+        this.minute = minute;
     }
 
     get hour() {
@@ -652,6 +780,10 @@ class ScheduledFlip {
             DayOfTheWeek.fromJson(json.dow),
             json.direction,
             json.kind,
+            json.offset || 0,
+            json.date || null,
+            json.season ? Season.fromJson(json.season) : null,
+            json.duration || null,
         );
     }
 
@@ -663,6 +795,10 @@ class ScheduledFlip {
             dow: this.dow,
             direction: this.flip,
             kind: this.kind,
+            offset: this.offset,
+            date: this.date,
+            season: this.season,
+            duration: this.duration,
         }
     }
 
@@ -674,10 +810,74 @@ class ScheduledFlip {
             this.dow.clone(),
             this.flip,
             this.kind,
+            this.offset,
+            this.date,
+            this.season ? this.season.clone() : null,
+            this.duration,
         )
     }
 }
 
+class Season {
+    constructor(
+        public start: MonthDay,
+        public end: MonthDay,
+    ) { }
+
+    static fromJson(json) {
+        return new Season(
+            MonthDay.fromJson(json.start),
+            MonthDay.fromJson(json.end),
+        );
+    }
+
+    clone(): Season {
+        return new Season(
+            this.start.clone(),
+            this.end.clone(),
+        );
+    }
+}
+
+class MonthDay {
+    constructor(
+        public month: number,
+        public day: number,
+    ) { }
+
+    static fromJson(json) {
+        return new MonthDay(
+            json.month,
+            json.day,
+        );
+    }
+
+    // null when `text` isn't an MM-DD day of the year
+    static parse(text: string): MonthDay | null {
+        let match = /^\s*(\d{1,2})-(\d{1,2})\s*$/.exec(text);
+        if (!match) {
+            return null;
+        }
+        let month = parseInt(match[1]);
+        let day = parseInt(match[2]);
+        if (month < 1 || month > 12 || day < 1 || day > 31) {
+            return null;
+        }
+        return new MonthDay(month, day);
+    }
+
+    toString() {
+        return `0${this.month}`.substr(-2) + '-' + `0${this.day}`.substr(-2);
+    }
+
+    clone(): MonthDay {
+        return new MonthDay(
+            this.month,
+            this.day,
+        );
+    }
+}
+
 class Flip {
     constructor(
         public hour: number,
@@ -768,6 +968,13 @@ enum FlipKind {
     Sunrise = 'Sunrise',
     Dusk = 'Dusk',
     Sunset = 'Sunset',
+    CivilDawn = 'CivilDawn',
+    CivilDusk = 'CivilDusk',
+    NauticalDawn = 'NauticalDawn',
+    NauticalDusk = 'NauticalDusk',
+    AstronomicalDawn = 'AstronomicalDawn',
+    AstronomicalDusk = 'AstronomicalDusk',
+    SolarNoon = 'SolarNoon',
 }
 const mockSwitches = [
     new Switch(1, 'Living Room', 44444, 22222),
@@ -1,3 +1,5 @@
+extern crate chrono;
+extern crate chrono_tz;
 extern crate robohome_shared;
 extern crate reqwest;
 extern crate serde;
@@ -8,87 +10,451 @@ extern crate serde_json;
 extern crate log;
 extern crate pretty_env_logger;
 
+mod provider;
+mod solar;
+mod weather;
+
+use chrono::{
+    DateTime,
+    Duration,
+    NaiveDate,
+    NaiveTime,
+    Timelike,
+    Utc,
+};
+use chrono_tz::Tz;
 use robohome_shared::{
-    data,
-    ipc::send,
+    config::{
+        Config,
+        Requirement,
+        SunProvider,
+    },
+    data::{
+        self,
+        offset_time,
+        FlipKind,
+        SpecialTime,
+        Store,
+    },
+    ipc::{
+        Alert,
+        Ipc,
+    },
+    time,
+};
+use crate::{
+    provider::{
+        Calculator,
+        SunTimesProvider,
+    },
+    solar::SunTimes,
+    weather::CloudCoverProvider,
+};
+
+use std::{
+    thread::sleep,
+    time::Duration as StdDuration,
 };
 
-static WEATHER_URI: &str = include_str!("../api_url");
+/// PreDawn and Dusk are this many
+/// minutes before sunrise and sunset
+const TWILIGHT_OFFSET: i32 = -60;
+/// The source recorded when no provider could
+/// be reached and the flips keep resolving to
+/// the times already in the database
+const STORED: &str = "stored";
 
 fn main() {
     if ::std::env::var("RUST_LOG").is_err() {
         ::std::env::set_var("RUST_LOG", "info");
     }
     pretty_env_logger::init();
-    info!("Getting new sunrise and sunset times");
-    let mut res = match reqwest::get(WEATHER_URI) {
-        Ok(res) => res,
+    let config = match Config::load(&[Requirement::Database, Requirement::Amqp]) {
+        Ok(config) => config,
         Err(e) => {
-            error!("Request Failed {}", e);
-            send_and_exit(1);
+            error!("Failed to load config: {}", e);
+            ::std::process::exit(1);
         }
     };
-    let res: WunderGroundResponse = match res.json() {
-        Ok(j) => j,
+    let ipc = Ipc::new(&config.amqp.url);
+    let db = match data::connect(&config.database) {
+        Ok(db) => db,
         Err(e) => {
-            error!("Deserialization failed: {}", e);
-            send_and_exit(2);
+            error!("Failed to connect to database: {}", e);
+            ::std::process::exit(3);
         }
     };
-    let SunPhase { sunrise, sunset } = res.sun_phase;
-    info!("new sunrise: {}:{}", sunrise.hour, sunrise.minute);
-    info!("new sunset: {}:{}", sunset.hour, sunset.minute);
-    match data::update_special_times(sunrise.hour - 1, sunrise.minute,
-                                    sunrise.hour, sunrise.minute,
-                                    sunset.hour - 1, sunset.minute,
-                                    sunset.hour, sunset.minute) {
-        Ok(ct) => info!("update {} special times", ct),
+    if !config.refresher.daemon {
+        if let Err(code) = refresh(&config, &ipc, &*db) {
+            ::std::process::exit(code);
+        }
+        return;
+    }
+    info!("Refreshing every day at {}", config.refresher.refresh_at);
+    loop {
+        let _ = refresh(&config, &ipc, &*db);
+        let wait = until_next(Utc::now(), config.location.time_zone, config.refresher.refresh_at);
+        info!("Next refresh in {}h{:02}m", wait.num_hours(), wait.num_minutes() % 60);
+        sleep(wait.to_std().unwrap_or_else(|_| StdDuration::from_secs(60)));
+    }
+}
+
+/// Refresh today and the configured days after it, the
+/// scheduler is only told to reload when a time changed.
+/// Fails with the exit code for the first day that failed
+fn refresh(config: &Config, ipc: &Ipc, db: &dyn Store) -> Result<(), i32> {
+    let today = time::today(Utc::now(), &config.location.time_zone);
+    let weather = match weather::from_config(config) {
+        Ok(weather) => weather,
         Err(e) => {
-            error!("Failed to update special times: {}", e);
-            send_and_exit(3);
+            warn!("Not adjusting for cloud cover, {}", e);
+            None
+        }
+    };
+    let mut changed = 0;
+    let mut ret = Ok(());
+    for offset in 0..=config.refresher.days_ahead {
+        let date = today + Duration::days(i64::from(offset));
+        match refresh_date(config, db, weather.as_ref().map(|w| &**w), date) {
+            Ok(ct) => changed += ct,
+            Err(code) => if ret.is_ok() {
+                ret = Err(code);
+            },
+        }
+    }
+    if changed > 0 {
+        info!("{} special times changed", changed);
+        if let Err(e) = ipc.send("database", &()) {
+            error!("Failed to send db update message {}", e);
+        }
+    } else {
+        info!("No special times changed");
+    }
+    check_staleness(ipc, db, config.refresher.stale_days, today);
+    ret
+}
+
+/// Store any of `date`'s special times that changed
+/// and record the refresh, returning how many changed
+fn refresh_date(config: &Config, db: &dyn Store, weather: Option<&dyn CloudCoverProvider>,
+                date: NaiveDate) -> Result<i32, i32> {
+    let outcome = fetch(config, date);
+    let mut ret = Err(3);
+    if let Some(ref sun) = outcome.sun {
+        let mut times = special_times(date, sun);
+        if let Some(weather) = weather {
+            times = weather::adjust(times, cloud_adjustment(config, weather, date));
         }
+        ret = db.get_special_times(date)
+            .and_then(|existing| db.update_special_times(&changed_times(&existing, times)))
+            .map_err(|e| {
+                error!("Failed to update special times for {}: {}", date, e);
+                3
+            });
+    }
+    let message = outcome.error.as_ref().map(|e| e.to_string());
+    if let Err(e) = db.new_refresh(date, outcome.source, ret.is_ok(),
+                                   outcome.attempts as i32, message.as_ref().map(String::as_str)) {
+        error!("Failed to record refresh: {}", e);
+    }
+    match (ret, outcome.error) {
+        (Err(_), Some(e)) => Err(e.exit_code()),
+        (ret, _) => ret,
     }
 }
 
-fn send_and_exit(code: i32) -> ! {
-    if let Err(e) = send("database", &()) {
-        error!("Failed to send db update message {}", e);
+/// Minutes to move `date`'s evening times by for
+/// cloud cover, none when the forecast isn't available
+fn cloud_adjustment(config: &Config, weather: &dyn CloudCoverProvider, date: NaiveDate) -> i32 {
+    let (cover, _) = provider::with_retries(config.refresher.retries,
+                                            StdDuration::from_secs(config.refresher.retry_delay),
+                                            || weather.cloud_cover(date));
+    match cover {
+        Ok(cover) => {
+            let ret = weather::adjustment(cover, config.refresher.cloud_adjustment);
+            info!("{:.0}% cloud cover on {}, moving dusk and sunset {} minutes", cover * 100.0, date, ret);
+            ret
+        },
+        Err(e) => {
+            warn!("Failed to get cloud cover for {}: {}", date, e);
+            0
+        },
     }
-    ::std::process::exit(code);
 }
 
-#[derive(Deserialize, Debug)]
-struct WunderGroundResponse {
-    pub sun_phase: SunPhase,
+/// The times in `times` that aren't already stored
+fn changed_times(existing: &[SpecialTime], times: Vec<SpecialTime>) -> Vec<SpecialTime> {
+    times.into_iter()
+        .filter(|st| !existing.contains(st))
+        .collect()
 }
 
-#[derive(Deserialize, Debug)]
-struct SunPhase {
-    #[serde(deserialize_with = "deserialize_time")]
-    pub sunrise: ApiTime,
-    #[serde(deserialize_with = "deserialize_time")]
-    pub sunset: ApiTime,
+/// How long from `now` until the next `at` in `tz`, see
+/// `time::instant` for days the clocks change on
+fn until_next(now: DateTime<Utc>, tz: Tz, at: NaiveTime) -> Duration {
+    let mut date = time::today(now, &tz);
+    loop {
+        let next = time::instant(date.and_time(at), &tz);
+        if next > now {
+            return next - now;
+        }
+        date = date.succ();
+    }
 }
 
-#[derive(Debug)]
-struct ApiTime {
-    pub hour: i32,
-    pub minute: i32,
+/// Where a day's sun times came from
+struct Outcome {
+    /// `None` when the stored times are kept
+    sun: Option<SunTimes>,
+    source: &'static str,
+    attempts: u32,
+    /// Why the configured provider wasn't used
+    error: Option<provider::Error>,
 }
 
-use serde::{Deserializer, Deserialize, de::Error};
-use std::collections::HashMap;
-fn deserialize_time<'de, D>(d: D) -> Result<ApiTime, D::Error> where D: Deserializer<'de> {
-    type Json = HashMap<String, String>;
-    let map = Json::deserialize(d)?;
-    let hour = map.get("hour").ok_or(serde::de::Error::missing_field("hour"))?;
-    let hour: i32 = hour.parse()
-                .map_err(|e| Error::custom(format!("Failed to parse i32 {}", e)))?;
-    let minute = map.get("minute").ok_or(serde::de::Error::missing_field("minute"))?;
-    let minute: i32 = minute.parse()
-                .map_err(|e| Error::custom(format!("Failed to parse i32 {}", e)))?;
-    Ok(ApiTime {
+/// Ask the configured provider for `date`, retrying while it
+/// can't be reached, then fall back to working the times out
+/// locally and finally to keeping the stored times
+fn fetch(config: &Config, date: NaiveDate) -> Outcome {
+    let provider = config.refresher.provider();
+    info!("Getting sun times for {} from {}", date, provider.as_str());
+    let (result, attempts) = match provider::from_config(config) {
+        Ok(ref p) if !p.has_date(date) => {
+            info!("{} doesn't have {}, calculating it", provider.as_str(), date);
+            return Outcome {
+                sun: Calculator::new(config).and_then(|calc| calc.sun_times(date)).ok(),
+                source: SunProvider::Calculate.as_str(),
+                attempts: 0,
+                error: None,
+            };
+        },
+        Ok(p) => provider::with_retries(config.refresher.retries,
+                                        StdDuration::from_secs(config.refresher.retry_delay),
+                                        || p.sun_times(date)),
+        Err(e) => (Err(e), 0),
+    };
+    let error = match result {
+        Ok(sun) => return Outcome {
+            sun: Some(fill_in(config, date, provider, sun)),
+            source: provider.as_str(),
+            attempts,
+            error: None,
+        },
+        Err(e) => e,
+    };
+    error!("Failed to get sun times from {}: {}", provider.as_str(), error);
+    if provider != SunProvider::Calculate {
+        if let Ok(calc) = Calculator::new(config) {
+            warn!("Falling back to calculating sun times");
+            return Outcome {
+                sun: calc.sun_times(date).ok(),
+                source: SunProvider::Calculate.as_str(),
+                attempts,
+                error: Some(error),
+            };
+        }
+    }
+    warn!("Keeping the stored sun times");
+    Outcome {
+        sun: None,
+        source: STORED,
+        attempts,
+        error: Some(error),
+    }
+}
+
+/// Anything the provider doesn't report is worked
+/// out locally when the location is configured
+fn fill_in(config: &Config, date: NaiveDate, provider: SunProvider, sun: SunTimes) -> SunTimes {
+    if provider == SunProvider::Calculate {
+        return sun;
+    }
+    match Calculator::new(config).and_then(|calc| calc.sun_times(date)) {
+        Ok(calculated) => sun.or(calculated),
+        Err(e) => {
+            warn!("Not filling in missing sun times, {}", e);
+            sun
+        }
+    }
+}
+
+/// Send an alert when the newest sun times
+/// are more than `stale_days` before `date`
+fn check_staleness(ipc: &Ipc, db: &dyn Store, stale_days: i64, date: NaiveDate) {
+    let latest = match db.get_latest_special_date() {
+        Ok(latest) => latest,
+        Err(e) => {
+            error!("Failed to get the latest special time: {}", e);
+            return;
+        }
+    };
+    if !is_stale(latest, date, stale_days) {
+        return;
+    }
+    match latest {
+        Some(latest) => warn!("The newest sun times are for {}", latest),
+        None => warn!("No sun times have been recorded"),
+    }
+    if let Err(e) = ipc.send("alerts", &Alert::StaleSunTimes { date, latest }) {
+        error!("Failed to send stale alert {}", e);
+    }
+}
+
+fn is_stale(latest: Option<NaiveDate>, date: NaiveDate, stale_days: i64) -> bool {
+    match latest {
+        Some(latest) => date.signed_duration_since(latest).num_days() > stale_days,
+        None => true,
+    }
+}
+
+/// Every kind the sun reaches on `date`, flips
+/// of a kind it doesn't reach fall back to the
+/// nearest date that has one
+fn special_times(date: NaiveDate, sun: &SunTimes) -> Vec<SpecialTime> {
+    let sunrise = sun.sunrise.map(rounded);
+    let sunset = sun.sunset.map(rounded);
+    let events = vec![
+        (FlipKind::AstronomicalDawn, sun.astronomical_dawn.map(rounded)),
+        (FlipKind::NauticalDawn, sun.nautical_dawn.map(rounded)),
+        (FlipKind::PreDawn, sunrise.map(|(h, m)| offset_time(h, m, TWILIGHT_OFFSET))),
+        (FlipKind::CivilDawn, sun.civil_dawn.map(rounded)),
+        (FlipKind::Sunrise, sunrise),
+        (FlipKind::SolarNoon, sun.solar_noon.map(rounded)),
+        (FlipKind::Dusk, sunset.map(|(h, m)| offset_time(h, m, TWILIGHT_OFFSET))),
+        (FlipKind::Sunset, sunset),
+        (FlipKind::CivilDusk, sun.civil_dusk.map(rounded)),
+        (FlipKind::NauticalDusk, sun.nautical_dusk.map(rounded)),
+        (FlipKind::AstronomicalDusk, sun.astronomical_dusk.map(rounded)),
+    ];
+    events.into_iter()
+        .filter_map(|(kind, time)| match time {
+            Some(time) => {
+                info!("new {}: {}:{:02}", kind.as_str(), time.0, time.1);
+                Some(special_time(date, kind, time))
+            },
+            None => {
+                info!("No {} on {}", kind.as_str(), date);
+                None
+            },
+        })
+        .collect()
+}
+
+fn special_time(date: NaiveDate, kind: FlipKind, (hour, minute): (i32, i32)) -> SpecialTime {
+    SpecialTime {
+        date,
+        kind,
         hour,
         minute,
-    })
-}
\ No newline at end of file
+        adjustment: 0,
+    }
+}
+
+/// Rounded to the nearest minute
+fn rounded(t: NaiveTime) -> (i32, i32) {
+    let t = t + Duration::seconds(30);
+    (t.hour() as i32, t.minute() as i32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn monday() -> NaiveDate {
+        NaiveDate::from_ymd(2018, 10, 1)
+    }
+
+    #[test]
+    fn staleness() {
+        assert!(is_stale(None, monday(), 2));
+        assert!(!is_stale(Some(monday()), monday(), 2));
+        assert!(!is_stale(Some(NaiveDate::from_ymd(2018, 9, 29)), monday(), 2));
+        assert!(is_stale(Some(NaiveDate::from_ymd(2018, 9, 28)), monday(), 2));
+        // times recorded ahead aren't stale
+        assert!(!is_stale(Some(NaiveDate::from_ymd(2018, 10, 5)), monday(), 0));
+    }
+
+    #[test]
+    fn polar_night_skips_kinds() {
+        let sun = SunTimes {
+            astronomical_dawn: Some(NaiveTime::from_hms(9, 2, 0)),
+            nautical_dawn: Some(NaiveTime::from_hms(10, 14, 0)),
+            civil_dawn: None,
+            sunrise: None,
+            solar_noon: Some(NaiveTime::from_hms(11, 44, 31)),
+            sunset: None,
+            civil_dusk: None,
+            nautical_dusk: Some(NaiveTime::from_hms(13, 15, 0)),
+            astronomical_dusk: Some(NaiveTime::from_hms(14, 27, 0)),
+        };
+        let times = special_times(monday(), &sun);
+        let kinds: Vec<FlipKind> = times.iter().map(|st| st.kind.clone()).collect();
+        assert_eq!(kinds, vec![FlipKind::AstronomicalDawn, FlipKind::NauticalDawn, FlipKind::SolarNoon,
+                               FlipKind::NauticalDusk, FlipKind::AstronomicalDusk]);
+        assert_eq!((times[2].hour, times[2].minute), (11, 45));
+    }
+
+    #[test]
+    fn dawn_and_dusk_follow_the_sun() {
+        let sun = SunTimes {
+            astronomical_dawn: None,
+            nautical_dawn: None,
+            civil_dawn: None,
+            sunrise: Some(NaiveTime::from_hms(0, 20, 0)),
+            solar_noon: None,
+            sunset: Some(NaiveTime::from_hms(18, 45, 10)),
+            civil_dusk: None,
+            nautical_dusk: None,
+            astronomical_dusk: None,
+        };
+        let times: Vec<(FlipKind, i32, i32)> = special_times(monday(), &sun)
+            .into_iter()
+            .map(|st| (st.kind, st.hour, st.minute))
+            .collect();
+        assert_eq!(times, vec![
+            (FlipKind::PreDawn, 23, 20),
+            (FlipKind::Sunrise, 0, 20),
+            (FlipKind::Dusk, 17, 45),
+            (FlipKind::Sunset, 18, 45),
+        ]);
+    }
+
+    #[test]
+    fn only_changed_times() {
+        let existing = vec![
+            special_time(monday(), FlipKind::Sunrise, (7, 12)),
+            special_time(monday(), FlipKind::Sunset, (18, 50)),
+            special_time(monday() - Duration::days(1), FlipKind::Dusk, (17, 51)),
+        ];
+        let times = vec![
+            special_time(monday(), FlipKind::Sunrise, (7, 12)),
+            special_time(monday(), FlipKind::Sunset, (18, 49)),
+            special_time(monday(), FlipKind::Dusk, (17, 51)),
+        ];
+        assert_eq!(changed_times(&existing, times), vec![
+            special_time(monday(), FlipKind::Sunset, (18, 49)),
+            special_time(monday(), FlipKind::Dusk, (17, 51)),
+        ]);
+        assert!(changed_times(&existing, existing.clone()).is_empty());
+    }
+
+    #[test]
+    fn next_refresh() {
+        let tz = chrono_tz::America::Chicago;
+        let at = NaiveTime::from_hms(2, 30, 0);
+        // 01:00 CDT
+        let now = Utc.ymd(2018, 10, 1).and_hms(6, 0, 0);
+        assert_eq!(until_next(now, tz, at), Duration::minutes(90));
+        // 03:00 CDT, tomorrow
+        let now = Utc.ymd(2018, 10, 1).and_hms(8, 0, 0);
+        assert_eq!(until_next(now, tz, at), Duration::minutes(23 * 60 + 30));
+        // 02:30 doesn't happen on the 10th of March 2019, it
+        // runs when the clocks change at 02:00 CST
+        let now = Utc.ymd(2019, 3, 10).and_hms(7, 0, 0);
+        assert_eq!(until_next(now, tz, at), Duration::minutes(60));
+        // midnight CDT, the clocks go back an hour at 02:00
+        let now = Utc.ymd(2019, 11, 3).and_hms(5, 0, 0);
+        assert_eq!(until_next(now, tz, at), Duration::minutes(210));
+    }
+}
@@ -0,0 +1,463 @@
+//! The places refresher can get a day's sun
+//! times from, picked by `refresher.provider`
+use chrono::{
+    DateTime,
+    NaiveDate,
+    NaiveTime,
+    Utc,
+};
+use chrono_tz::Tz;
+use robohome_shared::config::{
+    Config,
+    SunProvider,
+};
+use serde::{
+    de::{
+        DeserializeOwned,
+        Error as DeError,
+    },
+    Deserializer,
+};
+use serde_json::Value;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    thread,
+    time::Duration,
+};
+
+use crate::solar::{
+    self,
+    SunTimes,
+};
+
+const SUNRISE_SUNSET_URL: &str = "https://api.sunrise-sunset.org/json";
+
+/// Something that knows when the sun does
+/// what on a given day
+pub trait SunTimesProvider {
+    /// The sun times for `date` in local time
+    fn sun_times(&self, date: NaiveDate) -> Result<SunTimes, Error>;
+    /// If `date` can be asked for at all
+    fn has_date(&self, _date: NaiveDate) -> bool {
+        true
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The provider is missing a setting it needs
+    Config(String),
+    /// The provider couldn't be reached
+    Request(String),
+    /// The provider answered with something unusable
+    Response(String),
+}
+
+impl Error {
+    /// refresher exits with this when it can't
+    /// get the day's sun times
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Config(_) | Error::Request(_) => 1,
+            Error::Response(_) => 2,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Config(msg) => write!(f, "{}", msg),
+            Error::Request(msg) => write!(f, "Request failed: {}", msg),
+            Error::Response(msg) => write!(f, "Invalid response: {}", msg),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(other: reqwest::Error) -> Self {
+        Error::Request(other.to_string())
+    }
+}
+
+/// The provider `config` asks for
+pub fn from_config(config: &Config) -> Result<Box<dyn SunTimesProvider>, Error> {
+    Ok(match config.refresher.provider() {
+        SunProvider::Calculate => Box::new(Calculator::new(config)?),
+        SunProvider::SunriseSunset => Box::new(SunriseSunset::new(config)?),
+        SunProvider::Wunderground => Box::new(Wunderground::new(config)?),
+    })
+}
+
+/// Call `f` up to `retries` more times while the provider
+/// can't be reached, waiting `delay` before the first retry
+/// and twice as long before each one after that, returns
+/// the last result and the number of attempts made
+pub fn with_retries<T, F>(retries: u32, delay: Duration, mut f: F) -> (Result<T, Error>, u32)
+where F: FnMut() -> Result<T, Error> {
+    let mut attempts = 0;
+    let mut delay = delay;
+    loop {
+        attempts += 1;
+        match f() {
+            Err(Error::Request(ref msg)) if attempts <= retries => {
+                warn!("Attempt {} failed, retrying in {:?}: {}", attempts, delay, msg);
+                thread::sleep(delay);
+                delay *= 2;
+            },
+            result => return (result, attempts),
+        }
+    }
+}
+
+/// Works the sun times out locally, no
+/// network access required
+pub struct Calculator {
+    latitude: f64,
+    longitude: f64,
+    time_zone: Tz,
+}
+
+impl Calculator {
+    pub fn new(config: &Config) -> Result<Calculator, Error> {
+        let (latitude, longitude) = config.coordinates()
+            .map_err(|e| Error::Config(e.to_string()))?;
+        Ok(Calculator {
+            latitude,
+            longitude,
+            time_zone: config.location.time_zone,
+        })
+    }
+}
+
+impl SunTimesProvider for Calculator {
+    fn sun_times(&self, date: NaiveDate) -> Result<SunTimes, Error> {
+        Ok(solar::sun_times(date, self.latitude, self.longitude, &self.time_zone))
+    }
+}
+
+/// A sunrise-sunset.org style API, asked for the
+/// configured location with `formatted=0` so the
+/// times come back as UTC timestamps
+pub struct SunriseSunset {
+    url: String,
+    latitude: f64,
+    longitude: f64,
+    time_zone: Tz,
+}
+
+impl SunriseSunset {
+    pub fn new(config: &Config) -> Result<SunriseSunset, Error> {
+        let (latitude, longitude) = config.coordinates()
+            .map_err(|e| Error::Config(e.to_string()))?;
+        Ok(SunriseSunset {
+            url: config.refresher.api_url.clone()
+                .unwrap_or_else(|| SUNRISE_SUNSET_URL.to_string()),
+            latitude,
+            longitude,
+            time_zone: config.location.time_zone,
+        })
+    }
+}
+
+impl SunTimesProvider for SunriseSunset {
+    fn sun_times(&self, date: NaiveDate) -> Result<SunTimes, Error> {
+        let url = format!("{}?lat={}&lng={}&date={}&formatted=0",
+                          self.url, self.latitude, self.longitude, date);
+        let res: SunriseSunsetResponse = get_json(&url)?;
+        if res.status != "OK" {
+            return Err(Error::Response(format!("status {}", res.status)));
+        }
+        let r: SunriseSunsetResults = serde_json::from_value(res.results)
+            .map_err(|e| Error::Response(e.to_string()))?;
+        let tz = &self.time_zone;
+        Ok(SunTimes {
+            astronomical_dawn: utc_event(&r.astronomical_twilight_begin, tz)?,
+            nautical_dawn: utc_event(&r.nautical_twilight_begin, tz)?,
+            civil_dawn: utc_event(&r.civil_twilight_begin, tz)?,
+            sunrise: utc_event(&r.sunrise, tz)?,
+            solar_noon: utc_event(&r.solar_noon, tz)?,
+            sunset: utc_event(&r.sunset, tz)?,
+            civil_dusk: utc_event(&r.civil_twilight_end, tz)?,
+            nautical_dusk: utc_event(&r.nautical_twilight_end, tz)?,
+            astronomical_dusk: utc_event(&r.astronomical_twilight_end, tz)?,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct SunriseSunsetResponse {
+    /// An empty string when `status` isn't `OK`
+    results: Value,
+    status: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SunriseSunsetResults {
+    sunrise: String,
+    sunset: String,
+    solar_noon: String,
+    civil_twilight_begin: String,
+    civil_twilight_end: String,
+    nautical_twilight_begin: String,
+    nautical_twilight_end: String,
+    astronomical_twilight_begin: String,
+    astronomical_twilight_end: String,
+}
+
+/// Events that don't happen come back
+/// as the first second of 1970
+fn utc_event(value: &str, tz: &Tz) -> Result<Option<NaiveTime>, Error> {
+    let dt = DateTime::parse_from_rfc3339(value)
+        .map_err(|e| Error::Response(format!("{} {}", value, e)))?;
+    if dt.timestamp() <= 1 {
+        return Ok(None);
+    }
+    Ok(Some(dt.with_timezone(tz).time()))
+}
+
+/// A Wunderground style astronomy endpoint, the
+/// location is part of `api_url` and the answer
+/// is always for today
+pub struct Wunderground {
+    url: String,
+    time_zone: Tz,
+}
+
+impl Wunderground {
+    pub fn new(config: &Config) -> Result<Wunderground, Error> {
+        let url = config.refresher.api_url.clone()
+            .ok_or(Error::Config("refresher.api_url is required for the wunderground provider".to_string()))?;
+        Ok(Wunderground {
+            url,
+            time_zone: config.location.time_zone,
+        })
+    }
+}
+
+impl SunTimesProvider for Wunderground {
+    fn sun_times(&self, _date: NaiveDate) -> Result<SunTimes, Error> {
+        let res: WunderGroundResponse = get_json(&self.url)?;
+        Ok(SunTimes {
+            astronomical_dawn: None,
+            nautical_dawn: None,
+            civil_dawn: None,
+            sunrise: Some(res.sun_phase.sunrise),
+            solar_noon: None,
+            sunset: Some(res.sun_phase.sunset),
+            civil_dusk: None,
+            nautical_dusk: None,
+            astronomical_dusk: None,
+        })
+    }
+
+    fn has_date(&self, date: NaiveDate) -> bool {
+        date == Utc::now().with_timezone(&self.time_zone).date().naive_local()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct WunderGroundResponse {
+    pub sun_phase: SunPhase,
+}
+
+#[derive(Deserialize, Debug)]
+struct SunPhase {
+    #[serde(deserialize_with = "deserialize_time")]
+    pub sunrise: NaiveTime,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub sunset: NaiveTime,
+}
+
+fn deserialize_time<'de, D>(d: D) -> Result<NaiveTime, D::Error> where D: Deserializer<'de> {
+    let map: HashMap<String, String> = serde::Deserialize::deserialize(d)?;
+    let hour = map.get("hour").ok_or(DeError::missing_field("hour"))?;
+    let hour: u32 = hour.parse()
+                .map_err(|e| DeError::custom(format!("Failed to parse u32 {}", e)))?;
+    let minute = map.get("minute").ok_or(DeError::missing_field("minute"))?;
+    let minute: u32 = minute.parse()
+                .map_err(|e| DeError::custom(format!("Failed to parse u32 {}", e)))?;
+    NaiveTime::from_hms_opt(hour, minute, 0)
+        .ok_or(DeError::custom(format!("Invalid time {}:{}", hour, minute)))
+}
+
+/// The url isn't included in errors since
+/// it can hold an api key
+pub(crate) fn get_json<T: DeserializeOwned>(url: &str) -> Result<T, Error> {
+    let mut res = reqwest::get(url)?;
+    if !res.status().is_success() {
+        return Err(Error::Request(format!("provider returned {}", res.status())));
+    }
+    let body = res.text()?;
+    serde_json::from_str(&body).map_err(|e| Error::Response(e.to_string()))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use std::{
+        io::{
+            Read,
+            Write,
+        },
+        net::TcpListener,
+        sync::mpsc::{
+            channel,
+            Receiver,
+        },
+        thread,
+    };
+
+    const SUNRISE_SUNSET_OK: &str = r#"{"results":{"sunrise":"2019-06-21T10:26:19+00:00","sunset":"2019-06-22T02:03:09+00:00","solar_noon":"2019-06-21T18:14:44+00:00","day_length":56210,"civil_twilight_begin":"2019-06-21T09:48:23+00:00","civil_twilight_end":"2019-06-22T02:41:05+00:00","nautical_twilight_begin":"2019-06-21T08:57:37+00:00","nautical_twilight_end":"2019-06-22T03:31:51+00:00","astronomical_twilight_begin":"2019-06-21T07:52:46+00:00","astronomical_twilight_end":"2019-06-22T04:36:42+00:00"},"status":"OK"}"#;
+    const SUNRISE_SUNSET_POLAR: &str = r#"{"results":{"sunrise":"1970-01-01T00:00:01+00:00","sunset":"1970-01-01T00:00:01+00:00","solar_noon":"2019-06-21T10:44:12+00:00","day_length":0,"civil_twilight_begin":"1970-01-01T00:00:01+00:00","civil_twilight_end":"1970-01-01T00:00:01+00:00","nautical_twilight_begin":"1970-01-01T00:00:01+00:00","nautical_twilight_end":"1970-01-01T00:00:01+00:00","astronomical_twilight_begin":"1970-01-01T00:00:01+00:00","astronomical_twilight_end":"1970-01-01T00:00:01+00:00"},"status":"OK"}"#;
+    const SUNRISE_SUNSET_INVALID: &str = r#"{"results":"","status":"INVALID_DATE"}"#;
+    const WUNDERGROUND_OK: &str = r#"{"response":{"version":"0.1"},"sun_phase":{"sunrise":{"hour":"5","minute":"26"},"sunset":{"hour":"21","minute":"3"}}}"#;
+    const WUNDERGROUND_BAD_TIME: &str = r#"{"sun_phase":{"sunrise":{"hour":"5","minute":"61"},"sunset":{"hour":"21","minute":"3"}}}"#;
+
+    /// Serve canned responses in order on a free local port,
+    /// the url to ask and a receiver for each request line
+    pub(crate) fn stub(responses: &[(&'static str, &'static str)]) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/json", listener.local_addr().unwrap());
+        let responses = responses.to_vec();
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0; 4096];
+                let len = stream.read(&mut buf).unwrap();
+                let request = String::from_utf8_lossy(&buf[..len]);
+                tx.send(request.lines().next().unwrap_or("").to_string()).unwrap();
+                write!(stream, "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                       status, body.len(), body).unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn config(provider: &str, url: &str) -> Config {
+        format!("[location]\nlatitude = 44.98\nlongitude = -93.27\ntime_zone = \"America/Chicago\"\n\
+                 [refresher]\nprovider = \"{}\"\napi_url = \"{}\"", provider, url)
+            .parse()
+            .unwrap()
+    }
+
+    fn midsummer() -> NaiveDate {
+        NaiveDate::from_ymd(2019, 6, 21)
+    }
+
+    fn hm(hour: u32, minute: u32, second: u32) -> Option<NaiveTime> {
+        Some(NaiveTime::from_hms(hour, minute, second))
+    }
+
+    #[test]
+    fn sunrise_sunset() {
+        let (url, request) = stub(&[("200 OK", SUNRISE_SUNSET_OK)]);
+        let provider = from_config(&config("sunrise_sunset", &url)).unwrap();
+        let times = provider.sun_times(midsummer()).unwrap();
+        let request = request.recv().unwrap();
+        assert!(request.contains("lat=44.98&lng=-93.27&date=2019-06-21&formatted=0"), "{}", request);
+        // CDT
+        assert_eq!(times.sunrise, hm(5, 26, 19));
+        assert_eq!(times.solar_noon, hm(13, 14, 44));
+        assert_eq!(times.sunset, hm(21, 3, 9));
+        assert_eq!(times.civil_dawn, hm(4, 48, 23));
+        assert_eq!(times.astronomical_dusk, hm(23, 36, 42));
+    }
+
+    #[test]
+    fn sunrise_sunset_polar() {
+        let (url, _request) = stub(&[("200 OK", SUNRISE_SUNSET_POLAR)]);
+        let provider = from_config(&config("sunrise_sunset", &url)).unwrap();
+        let times = provider.sun_times(midsummer()).unwrap();
+        assert_eq!(times.sunrise, None);
+        assert_eq!(times.astronomical_dusk, None);
+        assert!(times.solar_noon.is_some());
+    }
+
+    #[test]
+    fn sunrise_sunset_errors() {
+        let (url, _request) = stub(&[("200 OK", SUNRISE_SUNSET_INVALID)]);
+        let provider = from_config(&config("sunrise_sunset", &url)).unwrap();
+        match provider.sun_times(midsummer()) {
+            Err(e @ Error::Response(_)) => assert_eq!(e.exit_code(), 2),
+            other => panic!("expected a response error {:?}", other.map(|_| ())),
+        }
+        let (url, _request) = stub(&[("500 Internal Server Error", "")]);
+        let provider = from_config(&config("sunrise_sunset", &url)).unwrap();
+        match provider.sun_times(midsummer()) {
+            Err(e @ Error::Request(_)) => assert_eq!(e.exit_code(), 1),
+            other => panic!("expected a request error {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn wunderground() {
+        let (url, request) = stub(&[("200 OK", WUNDERGROUND_OK)]);
+        let provider = from_config(&config("wunderground", &url)).unwrap();
+        let times = provider.sun_times(midsummer()).unwrap();
+        assert!(request.recv().unwrap().starts_with("GET /json "));
+        assert_eq!(times.sunrise, hm(5, 26, 0));
+        assert_eq!(times.sunset, hm(21, 3, 0));
+        assert_eq!(times.civil_dusk, None);
+        let today = Utc::now().with_timezone(&Tz::America__Chicago).date().naive_local();
+        assert!(provider.has_date(today));
+        assert!(!provider.has_date(today.succ()));
+    }
+
+    #[test]
+    fn wunderground_errors() {
+        for body in &["not json", WUNDERGROUND_BAD_TIME, SUNRISE_SUNSET_OK] {
+            let (url, _request) = stub(&[("200 OK", *body)]);
+            let provider = from_config(&config("wunderground", &url)).unwrap();
+            match provider.sun_times(midsummer()) {
+                Err(Error::Response(_)) => (),
+                other => panic!("expected a response error for {} {:?}", body, other.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn retries() {
+        let (url, requests) = stub(&[("503 Service Unavailable", ""), ("502 Bad Gateway", ""), ("200 OK", WUNDERGROUND_OK)]);
+        let provider = from_config(&config("wunderground", &url)).unwrap();
+        let (times, attempts) = with_retries(3, Duration::from_millis(1), || provider.sun_times(midsummer()));
+        assert_eq!(times.unwrap().sunrise, hm(5, 26, 0));
+        assert_eq!(attempts, 3);
+        assert_eq!(requests.iter().take(3).count(), 3);
+        // bad responses aren't retried
+        let (url, _requests) = stub(&[("200 OK", "not json"), ("200 OK", WUNDERGROUND_OK)]);
+        let provider = from_config(&config("wunderground", &url)).unwrap();
+        let (times, attempts) = with_retries(3, Duration::from_millis(1), || provider.sun_times(midsummer()));
+        assert!(times.is_err());
+        assert_eq!(attempts, 1);
+        let (url, _requests) = stub(&[("500 Internal Server Error", ""), ("500 Internal Server Error", "")]);
+        let provider = from_config(&config("wunderground", &url)).unwrap();
+        let (times, attempts) = with_retries(1, Duration::from_millis(1), || provider.sun_times(midsummer()));
+        assert!(times.is_err());
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn unreachable() {
+        let url = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}/json", listener.local_addr().unwrap())
+        };
+        let provider = from_config(&config("wunderground", &url)).unwrap();
+        match provider.sun_times(midsummer()) {
+            Err(Error::Request(_)) => (),
+            other => panic!("expected a request error {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn calculator_needs_a_location() {
+        let config: Config = "[refresher]\nprovider = \"calculate\"".parse().unwrap();
+        assert!(from_config(&config).is_err());
+        let calc = from_config(&self::config("calculate", "")).unwrap();
+        assert!(calc.sun_times(midsummer()).unwrap().sunrise.is_some());
+    }
+}
@@ -0,0 +1,248 @@
+//! Sunrise, sunset, twilight and solar noon worked out
+//! locally with the NOAA solar calculator
+//! equations, good to about a minute away
+//! from the poles
+use chrono::{
+    Datelike,
+    Duration,
+    NaiveDate,
+    NaiveTime,
+    TimeZone,
+    Utc,
+};
+
+/// The sun's center is this far below the horizon
+/// at sunrise and sunset, once refraction and the
+/// size of the disc are accounted for
+const SUNRISE_ZENITH: f64 = 90.833;
+/// Twilight ends when the sun's center is
+/// 6, 12 and 18 degrees below the horizon
+const CIVIL_ZENITH: f64 = 96.0;
+const NAUTICAL_ZENITH: f64 = 102.0;
+const ASTRONOMICAL_ZENITH: f64 = 108.0;
+
+/// One day's solar events in local time, an event is
+/// `None` when the sun never reaches its angle that
+/// day, like sunrise during a polar night or
+/// astronomical dusk in a northern summer, or when
+/// a provider doesn't report it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SunTimes {
+    pub astronomical_dawn: Option<NaiveTime>,
+    pub nautical_dawn: Option<NaiveTime>,
+    pub civil_dawn: Option<NaiveTime>,
+    pub sunrise: Option<NaiveTime>,
+    pub solar_noon: Option<NaiveTime>,
+    pub sunset: Option<NaiveTime>,
+    pub civil_dusk: Option<NaiveTime>,
+    pub nautical_dusk: Option<NaiveTime>,
+    pub astronomical_dusk: Option<NaiveTime>,
+}
+
+impl SunTimes {
+    /// Fill in any event that's missing with
+    /// the one from `other`
+    pub fn or(self, other: SunTimes) -> SunTimes {
+        SunTimes {
+            astronomical_dawn: self.astronomical_dawn.or(other.astronomical_dawn),
+            nautical_dawn: self.nautical_dawn.or(other.nautical_dawn),
+            civil_dawn: self.civil_dawn.or(other.civil_dawn),
+            sunrise: self.sunrise.or(other.sunrise),
+            solar_noon: self.solar_noon.or(other.solar_noon),
+            sunset: self.sunset.or(other.sunset),
+            civil_dusk: self.civil_dusk.or(other.civil_dusk),
+            nautical_dusk: self.nautical_dusk.or(other.nautical_dusk),
+            astronomical_dusk: self.astronomical_dusk.or(other.astronomical_dusk),
+        }
+    }
+}
+
+/// Latitude is positive to the north and
+/// longitude is positive to the east
+pub fn sun_times<T: TimeZone>(date: NaiveDate, latitude: f64, longitude: f64, tz: &T) -> SunTimes {
+    let jd = julian_day(date);
+    let event = |zenith, rising| {
+        event_utc(jd, latitude, longitude, zenith, rising)
+            .map(|m| local_time(date, m, tz))
+    };
+    SunTimes {
+        astronomical_dawn: event(ASTRONOMICAL_ZENITH, true),
+        nautical_dawn: event(NAUTICAL_ZENITH, true),
+        civil_dawn: event(CIVIL_ZENITH, true),
+        sunrise: event(SUNRISE_ZENITH, true),
+        solar_noon: Some(local_time(date, solar_noon_utc(jd, longitude), tz)),
+        sunset: event(SUNRISE_ZENITH, false),
+        civil_dusk: event(CIVIL_ZENITH, false),
+        nautical_dusk: event(NAUTICAL_ZENITH, false),
+        astronomical_dusk: event(ASTRONOMICAL_ZENITH, false),
+    }
+}
+
+struct SunPosition {
+    /// Degrees
+    declination: f64,
+    /// Minutes
+    equation_of_time: f64,
+}
+
+/// The julian day at midnight UTC
+fn julian_day(date: NaiveDate) -> f64 {
+    date.num_days_from_ce() as f64 + 1_721_424.5
+}
+
+fn julian_century(jd: f64) -> f64 {
+    (jd - 2_451_545.0) / 36_525.0
+}
+
+fn sun_position(t: f64) -> SunPosition {
+    let mean_long = (280.46646 + t * (36_000.76983 + t * 0.0003032)) % 360.0;
+    let mean_anomaly = 357.52911 + t * (35_999.05029 - 0.0001537 * t);
+    let eccentricity = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+    let center = mean_anomaly.to_radians().sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+        + (2.0 * mean_anomaly).to_radians().sin() * (0.019993 - 0.000101 * t)
+        + (3.0 * mean_anomaly).to_radians().sin() * 0.000289;
+    let omega = 125.04 - 1934.136 * t;
+    let apparent_long = mean_long + center - 0.00569 - 0.00478 * omega.to_radians().sin();
+    let mean_obliquity = 23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+    let obliquity = mean_obliquity + 0.00256 * omega.to_radians().cos();
+    let declination = (obliquity.to_radians().sin() * apparent_long.to_radians().sin())
+        .asin()
+        .to_degrees();
+    let y = (obliquity / 2.0).to_radians().tan().powi(2);
+    let l0 = mean_long.to_radians();
+    let m = mean_anomaly.to_radians();
+    let equation_of_time = 4.0 * (y * (2.0 * l0).sin()
+        - 2.0 * eccentricity * m.sin()
+        + 4.0 * eccentricity * y * m.sin() * (2.0 * l0).cos()
+        - 0.5 * y * y * (4.0 * l0).sin()
+        - 1.25 * eccentricity * eccentricity * (2.0 * m).sin())
+        .to_degrees();
+    SunPosition {
+        declination,
+        equation_of_time,
+    }
+}
+
+/// Degrees either side of solar noon that the
+/// sun crosses `zenith`, `None` if it never does
+fn hour_angle(latitude: f64, declination: f64, zenith: f64) -> Option<f64> {
+    let lat = latitude.to_radians();
+    let decl = declination.to_radians();
+    let cos_ha = zenith.to_radians().cos() / (lat.cos() * decl.cos()) - lat.tan() * decl.tan();
+    if cos_ha.abs() > 1.0 {
+        None
+    } else {
+        Some(cos_ha.acos().to_degrees())
+    }
+}
+
+/// Minutes after midnight UTC
+fn solar_noon_utc(jd: f64, longitude: f64) -> f64 {
+    let estimate = 720.0 - 4.0 * longitude;
+    let pos = sun_position(julian_century(jd + estimate / 1440.0));
+    720.0 - 4.0 * longitude - pos.equation_of_time
+}
+
+/// Minutes after midnight UTC, the sun's position
+/// is worked out again at the first estimate to
+/// tighten up the result
+fn event_utc(jd: f64, latitude: f64, longitude: f64, zenith: f64, rising: bool) -> Option<f64> {
+    let mut minutes = 720.0 - 4.0 * longitude;
+    for _ in 0..2 {
+        let pos = sun_position(julian_century(jd + minutes / 1440.0));
+        let ha = hour_angle(latitude, pos.declination, zenith)?;
+        let ha = if rising { ha } else { -ha };
+        minutes = 720.0 - 4.0 * (longitude + ha) - pos.equation_of_time;
+    }
+    Some(minutes)
+}
+
+fn local_time<T: TimeZone>(date: NaiveDate, minutes: f64, tz: &T) -> NaiveTime {
+    let utc = Utc.from_utc_datetime(&date.and_hms(0, 0, 0))
+        + Duration::seconds((minutes * 60.0).round() as i64);
+    utc.with_timezone(tz).time()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{
+        FixedOffset,
+        Timelike,
+    };
+
+    const LONDON: (f64, f64) = (51.5074, -0.1278);
+    const NEW_YORK: (f64, f64) = (40.7128, -74.0060);
+    const TROMSO: (f64, f64) = (69.6492, 18.9553);
+
+    fn assert_near(actual: NaiveTime, hour: u32, minute: u32) {
+        let expected = hour as i64 * 60 + minute as i64;
+        let actual_min = actual.hour() as i64 * 60 + actual.minute() as i64;
+        assert!((actual_min - expected).abs() <= 2,
+                "{} is not within 2 minutes of {:02}:{:02}", actual, hour, minute);
+    }
+
+    fn times(date: NaiveDate, (lat, lon): (f64, f64), offset_hours: i32) -> SunTimes {
+        sun_times(date, lat, lon, &FixedOffset::east(offset_hours * 3600))
+    }
+
+    #[test]
+    fn london_solstices() {
+        // BST
+        let summer = times(NaiveDate::from_ymd(2019, 6, 21), LONDON, 1);
+        assert_near(summer.sunrise.unwrap(), 4, 43);
+        assert_near(summer.sunset.unwrap(), 21, 21);
+        // GMT
+        let winter = times(NaiveDate::from_ymd(2019, 12, 21), LONDON, 0);
+        assert_near(winter.sunrise.unwrap(), 8, 4);
+        assert_near(winter.sunset.unwrap(), 15, 54);
+    }
+
+    #[test]
+    fn new_york_summer() {
+        // EDT, sunset is after midnight UTC
+        let summer = times(NaiveDate::from_ymd(2019, 6, 21), NEW_YORK, -4);
+        assert_near(summer.sunrise.unwrap(), 5, 25);
+        assert_near(summer.sunset.unwrap(), 20, 31);
+        assert_near(summer.solar_noon.unwrap(), 12, 58);
+    }
+
+    #[test]
+    fn equation_of_time_extremes() {
+        // the sun is 16 minutes early in early
+        // November and 14 minutes late in February
+        let november = times(NaiveDate::from_ymd(2019, 11, 3), (0.0, 0.0), 0);
+        assert_near(november.solar_noon.unwrap(), 11, 44);
+        let february = times(NaiveDate::from_ymd(2019, 2, 11), (0.0, 0.0), 0);
+        assert_near(february.solar_noon.unwrap(), 12, 14);
+    }
+
+    #[test]
+    fn london_twilight() {
+        // GMT
+        let winter = times(NaiveDate::from_ymd(2019, 12, 21), LONDON, 0);
+        assert_near(winter.astronomical_dawn.unwrap(), 5, 59);
+        assert_near(winter.nautical_dawn.unwrap(), 6, 40);
+        assert_near(winter.civil_dawn.unwrap(), 7, 24);
+        assert_near(winter.civil_dusk.unwrap(), 16, 34);
+        assert_near(winter.nautical_dusk.unwrap(), 17, 17);
+        assert_near(winter.astronomical_dusk.unwrap(), 17, 58);
+        // BST, it never gets darker than nautical
+        // twilight in a London summer
+        let summer = times(NaiveDate::from_ymd(2019, 6, 21), LONDON, 1);
+        assert_near(summer.civil_dusk.unwrap(), 22, 9);
+        assert_near(summer.nautical_dusk.unwrap(), 23, 23);
+        assert_eq!(summer.astronomical_dawn, None);
+        assert_eq!(summer.astronomical_dusk, None);
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        let midsummer = times(NaiveDate::from_ymd(2019, 6, 21), TROMSO, 2);
+        assert_eq!(midsummer.sunrise, None);
+        assert_eq!(midsummer.sunset, None);
+        let midwinter = times(NaiveDate::from_ymd(2019, 12, 21), TROMSO, 1);
+        assert_eq!(midwinter.sunrise, None);
+        assert_eq!(midwinter.sunset, None);
+    }
+}
@@ -0,0 +1,182 @@
+//! Cloud cover, used to move the evening special
+//! times earlier on days the house gets dark early
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use robohome_shared::{
+    config::Config,
+    data::{
+        offset_time,
+        FlipKind,
+        SpecialTime,
+    },
+};
+
+use crate::provider::{
+    get_json,
+    Error,
+};
+
+/// Something that knows how cloudy a day will be
+pub trait CloudCoverProvider {
+    /// The fraction of the sky covered on `date`, 0 to 1
+    fn cloud_cover(&self, date: NaiveDate) -> Result<f64, Error>;
+}
+
+/// The provider `config` asks for, `None`
+/// when `refresher.weather_url` isn't set
+pub fn from_config(config: &Config) -> Result<Option<Box<dyn CloudCoverProvider>>, Error> {
+    if config.refresher.weather_url.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(Box::new(OpenMeteo::new(config)?)))
+}
+
+/// Minutes to move the evening times by, none
+/// for a clear sky and `max_minutes` earlier
+/// for a completely covered one
+pub fn adjustment(cover: f64, max_minutes: i32) -> i32 {
+    let cover = cover.max(0.0).min(1.0);
+    -(cover * f64::from(max_minutes)).round() as i32
+}
+
+/// Move the dusk and sunset kinds in `times` by
+/// `adjustment` minutes, recording it on each
+pub fn adjust(times: Vec<SpecialTime>, adjustment: i32) -> Vec<SpecialTime> {
+    times.into_iter()
+        .map(|st| match st.kind {
+            FlipKind::Dusk | FlipKind::Sunset => {
+                let (hour, minute) = offset_time(st.hour, st.minute, adjustment);
+                SpecialTime {
+                    hour,
+                    minute,
+                    adjustment,
+                    ..st
+                }
+            },
+            _ => st,
+        })
+        .collect()
+}
+
+/// An Open-Meteo style forecast API, asked for
+/// the mean cloud cover of a day at the
+/// configured location
+pub struct OpenMeteo {
+    url: String,
+    latitude: f64,
+    longitude: f64,
+    time_zone: Tz,
+}
+
+impl OpenMeteo {
+    pub fn new(config: &Config) -> Result<OpenMeteo, Error> {
+        let url = config.refresher.weather_url.clone()
+            .ok_or(Error::Config("refresher.weather_url is required for cloud cover".to_string()))?;
+        let (latitude, longitude) = config.coordinates()
+            .map_err(|e| Error::Config(e.to_string()))?;
+        Ok(OpenMeteo {
+            url,
+            latitude,
+            longitude,
+            time_zone: config.location.time_zone,
+        })
+    }
+}
+
+impl CloudCoverProvider for OpenMeteo {
+    fn cloud_cover(&self, date: NaiveDate) -> Result<f64, Error> {
+        let url = format!("{}?latitude={}&longitude={}&daily=cloud_cover_mean&timezone={}&start_date={}&end_date={}",
+                          self.url, self.latitude, self.longitude, self.time_zone.name(), date, date);
+        let res: OpenMeteoResponse = get_json(&url)?;
+        let day = date.to_string();
+        res.daily.time.iter()
+            .position(|d| d == &day)
+            .and_then(|i| res.daily.cloud_cover_mean.get(i).cloned())
+            .and_then(|percent| percent)
+            .map(|percent| percent / 100.0)
+            .ok_or(Error::Response(format!("no cloud cover for {}", date)))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoResponse {
+    daily: OpenMeteoDaily,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoDaily {
+    time: Vec<String>,
+    /// Percent, `null` past the end of the forecast
+    cloud_cover_mean: Vec<Option<f64>>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::provider::test::stub;
+
+    const OPEN_METEO_OK: &str = r#"{"latitude":44.98,"longitude":-93.27,"timezone":"America/Chicago","daily_units":{"time":"iso8601","cloud_cover_mean":"%"},"daily":{"time":["2018-10-01"],"cloud_cover_mean":[85]}}"#;
+    const OPEN_METEO_NULL: &str = r#"{"daily":{"time":["2018-10-01"],"cloud_cover_mean":[null]}}"#;
+
+    fn config(url: &str) -> Config {
+        format!("[location]\nlatitude = 44.98\nlongitude = -93.27\ntime_zone = \"America/Chicago\"\n\
+                 [refresher]\nweather_url = \"{}\"", url)
+            .parse()
+            .unwrap()
+    }
+
+    fn monday() -> NaiveDate {
+        NaiveDate::from_ymd(2018, 10, 1)
+    }
+
+    fn special_time(kind: FlipKind, hour: i32, minute: i32) -> SpecialTime {
+        SpecialTime {
+            date: monday(),
+            kind,
+            hour,
+            minute,
+            adjustment: 0,
+        }
+    }
+
+    #[test]
+    fn open_meteo() {
+        let (url, request) = stub(&[("200 OK", OPEN_METEO_OK)]);
+        let provider = from_config(&config(&url)).unwrap().unwrap();
+        assert_eq!(provider.cloud_cover(monday()).unwrap(), 0.85);
+        let request = request.recv().unwrap();
+        assert!(request.contains("latitude=44.98&longitude=-93.27&daily=cloud_cover_mean&timezone=America/Chicago&start_date=2018-10-01&end_date=2018-10-01"), "{}", request);
+        let (url, _request) = stub(&[("200 OK", OPEN_METEO_NULL)]);
+        let provider = from_config(&config(&url)).unwrap().unwrap();
+        match provider.cloud_cover(monday()) {
+            Err(Error::Response(_)) => (),
+            other => panic!("expected a response error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn off_without_a_url() {
+        let config: Config = "".parse().unwrap();
+        assert!(from_config(&config).unwrap().is_none());
+    }
+
+    #[test]
+    fn adjustments() {
+        assert_eq!(adjustment(0.0, 30), 0);
+        assert_eq!(adjustment(0.85, 30), -26);
+        assert_eq!(adjustment(1.0, 30), -30);
+        assert_eq!(adjustment(1.5, 30), -30);
+        let times = adjust(vec![
+            special_time(FlipKind::Sunrise, 7, 12),
+            special_time(FlipKind::Dusk, 17, 50),
+            special_time(FlipKind::Sunset, 18, 10),
+            special_time(FlipKind::CivilDusk, 18, 38),
+        ], -26);
+        assert_eq!(times, vec![
+            special_time(FlipKind::Sunrise, 7, 12),
+            SpecialTime { adjustment: -26, ..special_time(FlipKind::Dusk, 17, 24) },
+            SpecialTime { adjustment: -26, ..special_time(FlipKind::Sunset, 17, 44) },
+            special_time(FlipKind::CivilDusk, 18, 38),
+        ]);
+    }
+}
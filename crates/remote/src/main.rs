@@ -2,7 +2,11 @@ extern crate robohome_shared;
 extern crate codesender;
 
 use robohome_shared::{
-    ipc::listen,
+    config::{
+        Config,
+        Requirement,
+    },
+    ipc::Ipc,
     data::Flip,
     Error,
 };
@@ -10,26 +14,28 @@ use robohome_shared::{
 use codesender::send;
 
 fn main() {
-    let rx = listen::<Flip>("switches")
+    let config = Config::load(&[Requirement::Amqp])
+                .expect("Failed to load config");
+    let rx = Ipc::new(&config.amqp.url).listen::<Flip>("switches")
                 .expect("Failed to establish listener");
     loop {
         match rx.recv() {
-            Ok(r) => handle_message(r),
+            Ok(r) => handle_message(r, config.remote.rf_pin),
             Err(e) => panic!("Error recv: {}", e),
         };
     }
 }
 
 
-fn handle_message(r: Result<Flip, Error>) {
+fn handle_message(r: Result<Flip, Error>, pin: u8) {
     match r {
-        Ok(f) => send_code(f),
+        Ok(f) => send_code(f, pin),
         Err(e) => eprintln!("{}", e),
     }
 }
 #[inline(always)]
-fn send_code(f: Flip) {
+fn send_code(f: Flip, pin: u8) {
     for _ in 0..10 {
-        let _  = send(f.code as usize, 17, 178);
+        let _  = send(f.code as usize, pin, 178);
     }
 }
\ No newline at end of file
@@ -0,0 +1,1134 @@
+//! Works out which flips to send when. The time
+//! comes from a `Clock` and the flips from a
+//! `FlipSource` so both can be swapped out in tests
+extern crate chrono;
+extern crate chrono_tz;
+#[macro_use]
+extern crate log;
+extern crate robohome_shared;
+
+mod vacation;
+
+use std::{
+    cell::Cell,
+    cmp::{
+        Ordering,
+        Reverse,
+    },
+    collections::BinaryHeap,
+    iter::FromIterator,
+    sync::Arc,
+    time::Duration as StdDuration,
+};
+
+use chrono::{
+    DateTime,
+    Duration,
+    NaiveDate,
+    Timelike,
+    Utc,
+};
+use chrono_tz::Tz;
+
+use robohome_shared::{
+    config::{
+        CatchUp,
+        ScheduleConfig,
+    },
+    data::{
+        Flip,
+        Group,
+        Hold,
+        Pause,
+        PendingOff,
+        Store,
+        Switch,
+    },
+    Error,
+    time,
+};
+
+/// Where the scheduler gets the current time from
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system's clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when it is told to
+pub struct ManualClock {
+    now: Cell<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> ManualClock {
+        ManualClock {
+            now: Cell::new(now),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        self.now.set(now);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.now.get()
+    }
+}
+
+impl<'a, C: Clock + ?Sized> Clock for &'a C {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
+
+/// Where the scheduler gets the flips from
+/// and records how far it has got
+pub trait FlipSource {
+    /// Everything that fires on `date`, in wall-clock time
+    fn flips_for_day(&self, date: NaiveDate) -> Result<Vec<Flip>, Error>;
+    /// Clear out any one-off flips from before `date`,
+    /// returns the number removed
+    fn remove_expired(&self, date: NaiveDate) -> Result<i32, Error>;
+    fn switches(&self) -> Result<Vec<Switch>, Error>;
+    /// The instant everything due was last sent
+    fn last_processed(&self) -> Result<Option<DateTime<Utc>>, Error>;
+    fn mark_processed(&self, at: DateTime<Utc>) -> Result<(), Error>;
+    /// If vacation mode is turned on
+    fn vacation(&self) -> Result<bool, Error>;
+    fn groups(&self) -> Result<Vec<Group>, Error>;
+    /// The offs from timed flips still to send
+    fn pending_offs(&self) -> Result<Vec<PendingOff>, Error>;
+    fn add_pending_off(&self, code: i32, due: DateTime<Utc>) -> Result<PendingOff, Error>;
+    /// Clear out an off once it has been sent,
+    /// returns the number removed
+    fn remove_off(&self, id: i32) -> Result<i32, Error>;
+    fn pause(&self) -> Result<Pause, Error>;
+    /// Carry on after a pause
+    fn resume(&self) -> Result<(), Error>;
+    fn holds(&self) -> Result<Vec<Hold>, Error>;
+    /// Clear out the holds that ran out by `through`,
+    /// returns the number removed
+    fn expire_holds(&self, through: DateTime<Utc>) -> Result<i32, Error>;
+}
+
+impl FlipSource for Arc<dyn Store> {
+    fn flips_for_day(&self, date: NaiveDate) -> Result<Vec<Flip>, Error> {
+        self.get_flips_for_day(date)
+    }
+
+    fn remove_expired(&self, date: NaiveDate) -> Result<i32, Error> {
+        self.remove_expired_flips(date)
+    }
+
+    fn switches(&self) -> Result<Vec<Switch>, Error> {
+        self.get_all_switches()
+    }
+
+    fn last_processed(&self) -> Result<Option<DateTime<Utc>>, Error> {
+        self.get_last_processed()
+    }
+
+    fn mark_processed(&self, at: DateTime<Utc>) -> Result<(), Error> {
+        self.update_last_processed(at)
+    }
+
+    fn vacation(&self) -> Result<bool, Error> {
+        Ok(self.get_vacation()?.enabled)
+    }
+
+    fn groups(&self) -> Result<Vec<Group>, Error> {
+        self.get_all_groups()
+    }
+
+    fn pending_offs(&self) -> Result<Vec<PendingOff>, Error> {
+        self.get_pending_offs()
+    }
+
+    fn add_pending_off(&self, code: i32, due: DateTime<Utc>) -> Result<PendingOff, Error> {
+        self.new_pending_off(code, due)
+    }
+
+    fn remove_off(&self, id: i32) -> Result<i32, Error> {
+        self.remove_pending_off(id)
+    }
+
+    fn pause(&self) -> Result<Pause, Error> {
+        self.get_pause()
+    }
+
+    fn resume(&self) -> Result<(), Error> {
+        self.update_pause(Pause::default())
+    }
+
+    fn holds(&self) -> Result<Vec<Hold>, Error> {
+        self.get_holds()
+    }
+
+    fn expire_holds(&self, through: DateTime<Utc>) -> Result<i32, Error> {
+        self.remove_expired_holds(through)
+    }
+}
+
+/// The flips left to send today, moving on to
+/// the next day's when the date changes in `tz`
+pub struct Scheduler<C, S> {
+    clock: C,
+    source: S,
+    tz: Tz,
+    config: ScheduleConfig,
+    date: NaiveDate,
+    pending: Queue,
+    /// The instant everything due has been handed
+    /// out up to, whether or not it was sent yet
+    taken: Option<DateTime<Utc>>,
+    /// The offs for timed flips, these aren't
+    /// tied to a day so they are kept apart
+    offs: Queue<PendingOff>,
+    /// The offs handed out but not yet
+    /// cleared by `mark_processed`
+    taken_offs: Vec<PendingOff>,
+    /// When a pause or hold that is keeping flips
+    /// back runs out, the offs it kept are loaded
+    /// again from the source then
+    wake_at: Option<DateTime<Utc>>,
+}
+
+impl<C: Clock, S: FlipSource> Scheduler<C, S> {
+    pub fn new(clock: C, source: S, tz: Tz, config: ScheduleConfig) -> Scheduler<C, S> {
+        let date = time::today(clock.now(), &tz);
+        let mut ret = Scheduler {
+            clock,
+            source,
+            tz,
+            config,
+            date,
+            pending: Queue::default(),
+            taken: None,
+            offs: Queue::default(),
+            taken_offs: Vec::new(),
+            wake_at: None,
+        };
+        ret.reload();
+        ret
+    }
+
+    /// The flips still to send today, along with
+    /// any offs waiting on a timed flip, and when
+    pub fn pending(&self) -> Vec<(DateTime<Utc>, Flip)> {
+        let mut ret = self.pending.to_vec();
+        ret.extend(self.offs.to_vec().into_iter()
+            .map(|(at, off)| (at, self.off_flip(off.due, off.code))));
+        ret.sort_by_key(|(at, _)| *at);
+        ret
+    }
+
+    /// When `tick` next has something to do, either
+    /// the next flip, a pause or hold running out or
+    /// moving on to the next day
+    pub fn next_wakeup(&self) -> DateTime<Utc> {
+        let midnight = time::instant(self.date.succ().and_hms(0, 0, 0), &self.tz);
+        self.pending.next().into_iter()
+            .chain(self.offs.next())
+            .chain(self.wake_at)
+            .filter(|at| *at < midnight)
+            .min()
+            .unwrap_or(midnight)
+    }
+
+    /// How long to wait before calling `tick`,
+    /// nothing if it is already overdue
+    pub fn until_next(&self) -> StdDuration {
+        (self.next_wakeup() - self.clock.now())
+            .to_std()
+            .unwrap_or(StdDuration::from_secs(0))
+    }
+
+    /// The flips that were due before the scheduler
+    /// started that `config.catch_up` says to send, with
+    /// the instant to `mark_processed` once they are. Offs
+    /// for timed flips are always sent, so nothing is
+    /// left on, once the schedule isn't paused and the
+    /// switch isn't held
+    pub fn start(&mut self) -> (DateTime<Utc>, Vec<Flip>) {
+        let now = self.clock.now();
+        self.taken = Some(now);
+        let missed = self.pending.overdue(now);
+        let offs = self.offs.due(now);
+        if missed.is_empty() && offs.is_empty() {
+            return (now, Vec::new());
+        }
+        let switches = self.switches();
+        let count = missed.len();
+        let caught_up = catch_up(missed, now, &self.config, &switches);
+        info!("Catching up {} of {} missed flips ({})", caught_up.len(), count, self.config.catch_up.as_str());
+        let ret = self.send(caught_up, offs, now, &switches);
+        (now, ret)
+    }
+
+    /// The flips due now, the rest of the day that
+    /// just ended first if it is a new day, with the
+    /// instant to `mark_processed` once they are sent.
+    /// Nothing is sent while the schedule is paused or
+    /// for a switch that is held, the offs for timed
+    /// flips wait until the pause or hold is over
+    pub fn tick(&mut self) -> (DateTime<Utc>, Vec<Flip>) {
+        let now = self.clock.now();
+        if self.wake_at.map(|at| at <= now).unwrap_or(false) {
+            self.wake_at = None;
+            self.offs = self.load_offs();
+        }
+        let mut due = self.pending.due(now);
+        let today = time::today(now, &self.tz);
+        if today != self.date {
+            info!("Moving on to the flips for {}", today);
+            self.date = today;
+            self.reload();
+            due.extend(self.pending.due(now));
+        }
+        let offs = self.offs.due(now);
+        let mut ret = Vec::new();
+        if !due.is_empty() || !offs.is_empty() {
+            let switches = self.switches();
+            ret = self.send(due, offs, now, &switches);
+        }
+        self.taken = Some(now);
+        (now, ret)
+    }
+
+    /// Record that everything due by `at` has been
+    /// sent so a restart doesn't send it again, the
+    /// offs sent with it are cleared out of the source
+    pub fn mark_processed(&mut self, at: DateTime<Utc>) {
+        if let Err(e) = self.source.mark_processed(at) {
+            error!("Failed to record last processed time: {}", e);
+        }
+        let (sent, taken) = self.taken_offs.drain(..).partition(|off| off.due <= at);
+        self.taken_offs = taken;
+        for off in sent {
+            if let Err(e) = self.source.remove_off(off.id) {
+                error!("Failed to clear sent off {}: {}", off.id, e);
+            }
+        }
+    }
+
+    /// Look the flips up again after the database changed,
+    /// moving on to the next day is left to `tick` so the
+    /// rest of the day that just ended is still sent
+    pub fn refresh(&mut self) {
+        self.reload();
+    }
+
+    fn reload(&mut self) {
+        self.pending = match self.load_day() {
+            Ok(pending) => pending.into_iter().collect(),
+            Err(e) => {
+                error!("Failed to get flips for {}: {}", self.date, e);
+                Queue::default()
+            },
+        };
+        self.offs = self.load_offs();
+        self.wake_at = match self.source.pause() {
+            Ok(ref pause) if pause.paused => pause.resume_at,
+            Ok(_) => None,
+            Err(e) => {
+                error!("Failed to get pause: {}", e);
+                None
+            },
+        };
+    }
+
+    /// Any already handed out are left out
+    fn load_offs(&self) -> Queue<PendingOff> {
+        match self.source.pending_offs() {
+            Ok(offs) => offs.into_iter()
+                .filter(|off| !self.taken_offs.iter().any(|taken| taken.id == off.id))
+                .map(|off| (off.due, off))
+                .collect(),
+            Err(e) => {
+                error!("Failed to get pending offs: {}", e);
+                Queue::default()
+            },
+        }
+    }
+
+    fn switches(&self) -> Vec<Switch> {
+        self.source.switches().unwrap_or_else(|e| {
+            error!("Failed to get switches: {}", e);
+            Vec::new()
+        })
+    }
+
+    /// The offs and then the flips that aren't kept back
+    /// by a pause or a hold, recording the offs for any
+    /// timed flips among them. Each off that is sent is
+    /// held on to so `mark_processed` clears only those
+    /// out of the source, the ones kept back are left
+    /// there for later
+    fn send(&mut self, flips: Vec<Flip>, offs: Vec<PendingOff>, now: DateTime<Utc>,
+            switches: &[Switch]) -> Vec<Flip> {
+        let kept = self.kept_back(now, switches);
+        let mut ret = Vec::new();
+        for off in offs {
+            if kept.keeps(off.code) {
+                info!("Keeping back off {}", off.code);
+                if let Some(until) = kept.until(off.code) {
+                    self.wake_at = Some(self.wake_at.map_or(until, |at| at.min(until)));
+                }
+                continue;
+            }
+            ret.push(self.off_flip(off.due, off.code));
+            self.taken_offs.push(off);
+        }
+        if kept.paused && !flips.is_empty() {
+            info!("Paused, holding back {} flips", flips.len());
+        }
+        ret.extend(flips.into_iter().filter(|f| !kept.keeps(f.code)));
+        self.add_offs(&ret, now, switches);
+        ret
+    }
+
+    /// What a pause or the holds are keeping back
+    /// at `now`, a pause or hold that has run out
+    /// is cleared
+    fn kept_back<'a>(&self, now: DateTime<Utc>, switches: &'a [Switch]) -> KeptBack<'a> {
+        let pause = self.source.pause().unwrap_or_else(|e| {
+            error!("Failed to get pause: {}", e);
+            Pause::default()
+        });
+        if pause.paused && !pause.is_paused(now) {
+            info!("Resuming the schedule");
+            if let Err(e) = self.source.resume() {
+                error!("Failed to resume the schedule: {}", e);
+            }
+        }
+        if let Err(e) = self.source.expire_holds(now) {
+            error!("Failed to clear expired holds: {}", e);
+        }
+        let holds = self.source.holds().unwrap_or_else(|e| {
+            error!("Failed to get holds: {}", e);
+            Vec::new()
+        });
+        KeptBack {
+            paused: pause.is_paused(now),
+            resume_at: pause.resume_at,
+            held: switches.iter()
+                .filter_map(|s| holds.iter()
+                    .find(|h| h.switch_id == s.id && h.is_held(now))
+                    .map(|h| (s, h.until)))
+                .collect(),
+        }
+    }
+
+    /// Record and queue the off for each timed flip
+    /// in `flips`, counting from when they were sent
+    fn add_offs(&mut self, flips: &[Flip], sent: DateTime<Utc>, switches: &[Switch]) {
+        for flip in flips {
+            let minutes = match flip.duration {
+                Some(minutes) => minutes,
+                None => continue,
+            };
+            let switch = match switches.iter().find(|s| s.on_code == flip.code) {
+                Some(switch) => switch,
+                None => {
+                    warn!("No switch turned on by {} to turn back off", flip.code);
+                    continue;
+                },
+            };
+            let due = sent + Duration::minutes(i64::from(minutes));
+            match self.source.add_pending_off(switch.off_code, due) {
+                Ok(off) => self.offs.push(off.due, off),
+                Err(e) => error!("Failed to record off for {}: {}", switch.name, e),
+            }
+        }
+    }
+
+    /// The flip that sends `code` at `due`
+    fn off_flip(&self, due: DateTime<Utc>, code: i32) -> Flip {
+        let local = time::local_now(due, &self.tz);
+        Flip {
+            hour: local.hour() as i32,
+            minute: local.minute() as i32,
+            code,
+            duration: None,
+        }
+    }
+
+    /// Clear out any one-off flips that have already passed
+    /// before looking up the flips for `self.date`, each with
+    /// the instant it should happen at. Any that were already
+    /// sent, or handed out to be, are left out
+    fn load_day(&self) -> Result<Vec<(DateTime<Utc>, Flip)>, Error> {
+        let removed = self.source.remove_expired(self.date)?;
+        if removed > 0 {
+            info!("Removed {} expired flips", removed);
+        }
+        let mut flips = self.source.flips_for_day(self.date)?;
+        if self.source.vacation()? {
+            flips = self.vacation_flips(flips)?;
+        }
+        let mut ret = fire_times(self.date, flips, &self.tz);
+        if let Some(since) = self.source.last_processed()?.max(self.taken) {
+            ret.retain(|(at, _)| *at > since);
+        }
+        Ok(ret)
+    }
+
+    /// `flips` moved around a little, with the extra
+    /// cycles for the configured rooms
+    fn vacation_flips(&self, flips: Vec<Flip>) -> Result<Vec<Flip>, Error> {
+        let config = &self.config.vacation;
+        let mut ret = vacation::jitter(self.date, flips, &self.source.switches()?, config.jitter);
+        if !config.rooms.is_empty() {
+            let rooms: Vec<Group> = self.source.groups()?
+                .into_iter()
+                .filter(|g| config.rooms.contains(&g.name))
+                .collect();
+            for name in config.rooms.iter().filter(|name| !rooms.iter().any(|g| &g.name == *name)) {
+                warn!("No group named {} for vacation cycles", name);
+            }
+            ret.extend(vacation::cycles(self.date, &rooms, config));
+        }
+        info!("Vacation mode is on, {} flips for {}", ret.len(), self.date);
+        Ok(ret)
+    }
+}
+
+/// What a pause or the holds on switches
+/// are keeping from being sent
+struct KeptBack<'a> {
+    paused: bool,
+    resume_at: Option<DateTime<Utc>>,
+    /// Each held switch and when its hold runs out
+    held: Vec<(&'a Switch, Option<DateTime<Utc>>)>,
+}
+
+impl<'a> KeptBack<'a> {
+    /// The held switch `code` belongs to
+    fn held(&self, code: i32) -> Option<&(&'a Switch, Option<DateTime<Utc>>)> {
+        self.held.iter().find(|(s, _)| s.on_code == code || s.off_code == code)
+    }
+
+    fn keeps(&self, code: i32) -> bool {
+        if self.paused {
+            return true;
+        }
+        match self.held(code) {
+            Some((switch, _)) => {
+                info!("{} is held, leaving out {}", switch.name, code);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// When `code` can be sent again, `None` when
+    /// that is up to someone turning it off by hand
+    fn until(&self, code: i32) -> Option<DateTime<Utc>> {
+        if self.paused {
+            self.resume_at
+        } else {
+            self.held(code).and_then(|(_, until)| *until)
+        }
+    }
+}
+
+/// Flips are stored in wall-clock time, see
+/// `time::instant` for clock changes
+fn fire_times(date: NaiveDate, flips: Vec<Flip>, tz: &Tz) -> Vec<(DateTime<Utc>, Flip)> {
+    flips.into_iter()
+        .map(|f| (time::instant(date.and_hms(f.hour as u32, f.minute as u32, 0), tz), f))
+        .collect()
+}
+
+/// Flips waiting to be sent, earliest first. Flips
+/// due at the same instant keep the order they
+/// were added in
+struct Queue<T = Flip> {
+    heap: BinaryHeap<Reverse<Entry<T>>>,
+    added: usize,
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Queue {
+            heap: BinaryHeap::new(),
+            added: 0,
+        }
+    }
+}
+
+impl<T: Clone> Queue<T> {
+    fn push(&mut self, at: DateTime<Utc>, flip: T) {
+        self.heap.push(Reverse(Entry {
+            at,
+            order: self.added,
+            flip,
+        }));
+        self.added += 1;
+    }
+
+    /// When the earliest flip is due
+    fn next(&self) -> Option<DateTime<Utc>> {
+        self.heap.peek().map(|e| e.0.at)
+    }
+
+    /// Take the flips that should have happened by `now`
+    fn due(&mut self, now: DateTime<Utc>) -> Vec<T> {
+        self.overdue(now).into_iter().map(|(_, f)| f).collect()
+    }
+
+    /// Take the flips that should have happened
+    /// by `now` along with when they were due
+    fn overdue(&mut self, now: DateTime<Utc>) -> Vec<(DateTime<Utc>, T)> {
+        let mut ret = Vec::new();
+        while self.next().map(|at| at <= now).unwrap_or(false) {
+            if let Some(Reverse(e)) = self.heap.pop() {
+                ret.push((e.at, e.flip));
+            }
+        }
+        ret
+    }
+
+    fn to_vec(&self) -> Vec<(DateTime<Utc>, T)> {
+        let mut entries: Vec<&Entry<T>> = self.heap.iter().map(|e| &e.0).collect();
+        entries.sort();
+        entries.into_iter().map(|e| (e.at, e.flip.clone())).collect()
+    }
+}
+
+impl<T: Clone> FromIterator<(DateTime<Utc>, T)> for Queue<T> {
+    fn from_iter<I: IntoIterator<Item = (DateTime<Utc>, T)>>(iter: I) -> Queue<T> {
+        let mut ret = Queue::default();
+        for (at, flip) in iter {
+            ret.push(at, flip);
+        }
+        ret
+    }
+}
+
+struct Entry<T> {
+    at: DateTime<Utc>,
+    order: usize,
+    flip: T,
+}
+
+impl<T> Entry<T> {
+    fn key(&self) -> (DateTime<Utc>, usize) {
+        (self.at, self.order)
+    }
+}
+
+impl<T> PartialEq for Entry<T> {
+    fn eq(&self, other: &Entry<T>) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T> Eq for Entry<T> {}
+
+impl<T> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Entry<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Entry<T> {
+    fn cmp(&self, other: &Entry<T>) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Which of the flips missed while the scheduler wasn't
+/// running to send, in the order they were due
+fn catch_up(mut missed: Vec<(DateTime<Utc>, Flip)>, now: DateTime<Utc>,
+            config: &ScheduleConfig, switches: &[Switch]) -> Vec<Flip> {
+    missed.sort_by_key(|(at, _)| *at);
+    match config.catch_up {
+        CatchUp::Skip => Vec::new(),
+        CatchUp::Grace => {
+            let since = now - Duration::minutes(config.grace);
+            missed.into_iter()
+                .filter(|(at, _)| *at >= since)
+                .map(|(_, f)| f)
+                .collect()
+        },
+        CatchUp::Latest => {
+            let mut ret: Vec<Flip> = Vec::new();
+            for (_, flip) in missed {
+                ret.retain(|f| !same_switch(switches, f, &flip));
+                ret.push(flip);
+            }
+            ret
+        },
+    }
+}
+
+/// Codes that don't belong to a switch
+/// only match themselves
+fn same_switch(switches: &[Switch], lhs: &Flip, rhs: &Flip) -> bool {
+    let switch_id = |code| switches.iter()
+        .find(|s| s.on_code == code || s.off_code == code)
+        .map(|s| s.id);
+    match (switch_id(lhs.code), switch_id(rhs.code)) {
+        (Some(lhs), Some(rhs)) => lhs == rhs,
+        _ => lhs.code == rhs.code,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use chrono_tz::America::Chicago;
+    use robohome_shared::data::{
+        Direction,
+        FlipKind,
+        MemoryStore,
+        Pause,
+        Vacation,
+    };
+
+    fn flip(hour: i32, minute: i32, code: i32) -> Flip {
+        Flip {
+            hour,
+            minute,
+            code,
+            duration: None,
+        }
+    }
+
+    fn switch(id: i32, on_code: i32, off_code: i32) -> Switch {
+        Switch {
+            id,
+            name: format!("switch {}", id),
+            on_code,
+            off_code,
+        }
+    }
+
+    fn config(catch_up: CatchUp) -> ScheduleConfig {
+        ScheduleConfig {
+            catch_up,
+            ..ScheduleConfig::default()
+        }
+    }
+
+    /// A store with one-off flips for porch (1 on, 2 off)
+    /// either side of midnight on Monday the 1st
+    fn overnight() -> Arc<dyn Store> {
+        let db = MemoryStore::new();
+        let porch = db.new_switch("porch", 1, 2).unwrap();
+        let monday = NaiveDate::from_ymd(2018, 10, 1);
+        let tuesday = monday.succ();
+        db.new_dated_flip(porch.id, monday, 23, 59, Direction::On, FlipKind::Custom).unwrap();
+        db.new_dated_flip(porch.id, tuesday, 0, 0, Direction::Off, FlipKind::Custom).unwrap();
+        db.new_dated_flip(porch.id, tuesday, 7, 0, Direction::On, FlipKind::Custom).unwrap();
+        Arc::new(db)
+    }
+
+    /// `hour`:`minute` CDT on the `day`th of October 2018
+    fn cdt(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Chicago.ymd(2018, 10, day).and_hms(hour, minute, 0).with_timezone(&Utc)
+    }
+
+    /// Tick once a minute until `until`, each
+    /// flip sent with the minute it was sent in
+    fn run_until<S: FlipSource>(scheduler: &mut Scheduler<&ManualClock, S>, clock: &ManualClock,
+                               until: DateTime<Utc>) -> Vec<(DateTime<Utc>, Flip)> {
+        let mut ret = Vec::new();
+        while clock.now() < until {
+            clock.advance(Duration::minutes(1));
+            let (at, flips) = scheduler.tick();
+            for flip in flips {
+                ret.push((clock.now(), flip));
+            }
+            scheduler.mark_processed(at);
+        }
+        ret
+    }
+
+    #[test]
+    fn due_in_local_time() {
+        let monday = NaiveDate::from_ymd(2018, 10, 1);
+        let mut pending: Queue = fire_times(monday, vec![flip(23, 59, 3), flip(7, 0, 1), flip(19, 30, 2)], &Chicago)
+            .into_iter()
+            .collect();
+        // 19:30 CDT
+        let now = Utc.ymd(2018, 10, 2).and_hms(0, 30, 0);
+        assert_eq!(pending.due(now), vec![flip(7, 0, 1), flip(19, 30, 2)]);
+        assert!(pending.due(now).is_empty());
+        assert_eq!(pending.next(), Some(now + Duration::minutes(269)));
+        assert_eq!(pending.due(now + Duration::hours(5)), vec![flip(23, 59, 3)]);
+        assert_eq!(pending.next(), None);
+    }
+
+    #[test]
+    fn clock_changes() {
+        let spring = NaiveDate::from_ymd(2019, 3, 10);
+        let mut pending: Queue = fire_times(spring, vec![flip(2, 30, 1), flip(3, 15, 2)], &Chicago)
+            .into_iter()
+            .collect();
+        // 03:00 CDT, the skipped flip happens as the clocks change
+        assert_eq!(pending.due(Utc.ymd(2019, 3, 10).and_hms(8, 0, 0)), vec![flip(2, 30, 1)]);
+        let fall = NaiveDate::from_ymd(2019, 11, 3);
+        let mut pending: Queue = fire_times(fall, vec![flip(1, 30, 1)], &Chicago)
+            .into_iter()
+            .collect();
+        // 01:30 CDT, it only happens the first time round
+        assert_eq!(pending.due(Utc.ymd(2019, 11, 3).and_hms(6, 30, 0)), vec![flip(1, 30, 1)]);
+        assert!(pending.due(Utc.ymd(2019, 11, 3).and_hms(7, 30, 0)).is_empty());
+    }
+
+    #[test]
+    fn catching_up() {
+        let monday = NaiveDate::from_ymd(2018, 10, 1);
+        let switches = vec![switch(1, 11, 12), switch(2, 21, 22)];
+        let missed = fire_times(monday, vec![
+            flip(20, 0, 11),
+            flip(6, 0, 11),
+            flip(7, 0, 12),
+            flip(19, 0, 21),
+            flip(21, 50, 12),
+            flip(21, 55, 99),
+        ], &Tz::UTC);
+        let now = Utc.ymd(2018, 10, 1).and_hms(22, 0, 0);
+        assert!(catch_up(missed.clone(), now, &config(CatchUp::Skip), &switches).is_empty());
+        assert_eq!(catch_up(missed.clone(), now, &config(CatchUp::Latest), &switches),
+                   vec![flip(19, 0, 21), flip(21, 50, 12), flip(21, 55, 99)]);
+        assert_eq!(catch_up(missed.clone(), now, &config(CatchUp::Grace), &switches),
+                   vec![flip(21, 50, 12), flip(21, 55, 99)]);
+        // without the switches each code is its own
+        assert_eq!(catch_up(missed, now, &config(CatchUp::Latest), &[]),
+                   vec![flip(19, 0, 21), flip(20, 0, 11), flip(21, 50, 12), flip(21, 55, 99)]);
+    }
+
+    #[test]
+    fn overnight_run() {
+        let db = overnight();
+        let clock = ManualClock::new(cdt(1, 22, 0));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        assert!(scheduler.start().1.is_empty());
+        // midnight UTC is at 19:00 CDT, nothing changes then
+        let sent = run_until(&mut scheduler, &clock, cdt(2, 8, 0));
+        assert_eq!(sent, vec![
+            (cdt(1, 23, 59), flip(23, 59, 1)),
+            (cdt(2, 0, 0), flip(0, 0, 2)),
+            (cdt(2, 7, 0), flip(7, 0, 1)),
+        ]);
+        assert_eq!(db.get_last_processed().unwrap(), Some(cdt(2, 8, 0)));
+    }
+
+    #[test]
+    fn wakes_for_each_flip() {
+        let db = overnight();
+        let clock = ManualClock::new(cdt(1, 22, 0));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        let mut woken = Vec::new();
+        while clock.now() < cdt(3, 0, 0) {
+            clock.set(scheduler.next_wakeup());
+            woken.push((clock.now(), scheduler.tick().1));
+        }
+        assert_eq!(woken, vec![
+            (cdt(1, 23, 59), vec![flip(23, 59, 1)]),
+            (cdt(2, 0, 0), vec![flip(0, 0, 2)]),
+            (cdt(2, 7, 0), vec![flip(7, 0, 1)]),
+            (cdt(3, 0, 0), vec![]),
+        ]);
+        clock.set(cdt(4, 0, 0) - Duration::seconds(30));
+        assert_eq!(scheduler.until_next(), StdDuration::from_secs(30));
+        clock.set(cdt(4, 0, 1));
+        assert_eq!(scheduler.until_next(), StdDuration::from_secs(0));
+    }
+
+    #[test]
+    fn vacation_mode() {
+        let db = overnight();
+        let porch = db.get_all_switches().unwrap()[0].id;
+        let room = db.new_group("Porch").unwrap();
+        db.add_group_member(room.id, porch).unwrap();
+        let mut config = config(CatchUp::Skip);
+        config.vacation.rooms = vec!["Porch".to_string(), "Attic".to_string()];
+        let clock = ManualClock::new(cdt(2, 0, 30));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config.clone());
+        assert!(scheduler.start().1.is_empty());
+        assert_eq!(scheduler.pending(), vec![(cdt(2, 7, 0), flip(7, 0, 1))]);
+        db.update_vacation(Vacation { enabled: true }).unwrap();
+        scheduler.refresh();
+        let pending = scheduler.pending();
+        // the 07:00 flip moved and two extra evening cycles
+        assert_eq!(pending.len(), 5);
+        assert!(pending[0].0 >= cdt(2, 6, 45) && pending[0].0 <= cdt(2, 7, 15), "{:?}", pending);
+        assert!(pending[1..].iter().all(|(at, _)| *at >= cdt(2, 18, 0) && *at <= cdt(2, 23, 0)), "{:?}", pending);
+        assert_eq!(pending[1..].iter().map(|(_, f)| f.code).collect::<Vec<_>>(), vec![1, 2, 1, 2]);
+        // the same times after a restart
+        let mut restarted = Scheduler::new(&clock, db.clone(), Chicago, config);
+        restarted.start();
+        assert_eq!(restarted.pending(), pending);
+        db.update_vacation(Vacation { enabled: false }).unwrap();
+        scheduler.refresh();
+        assert_eq!(scheduler.pending(), vec![(cdt(2, 7, 0), flip(7, 0, 1))]);
+    }
+
+    #[test]
+    fn late_tick_over_midnight() {
+        // a tick that was held up past midnight sends the
+        // end of one day and the start of the next
+        let db = overnight();
+        let clock = ManualClock::new(cdt(1, 23, 0));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        clock.set(cdt(2, 0, 1));
+        assert_eq!(scheduler.tick().1, vec![flip(23, 59, 1), flip(0, 0, 2)]);
+        // Monday's one-off flip was cleared out
+        assert!(db.get_flips_for_day(NaiveDate::from_ymd(2018, 10, 1)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn refresh_over_midnight() {
+        // a refresh that comes in after midnight but
+        // before the tick keeps the end of the day before
+        let db = overnight();
+        let clock = ManualClock::new(cdt(1, 23, 0));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        clock.set(cdt(2, 0, 1));
+        scheduler.refresh();
+        assert_eq!(scheduler.tick().1, vec![flip(23, 59, 1), flip(0, 0, 2)]);
+    }
+
+    #[test]
+    fn restarts() {
+        let db = overnight();
+        let clock = ManualClock::new(cdt(1, 23, 0));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        run_until(&mut scheduler, &clock, cdt(2, 0, 30));
+        // nothing is sent twice after a restart
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        assert!(scheduler.start().1.is_empty());
+        assert_eq!(scheduler.pending().len(), 1);
+        // down until after 07:00, the last state
+        // for the porch is caught up
+        clock.set(cdt(2, 9, 0));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        assert_eq!(scheduler.start().1, vec![flip(7, 0, 1)]);
+        assert!(run_until(&mut scheduler, &clock, cdt(2, 23, 0)).is_empty());
+    }
+
+    #[test]
+    fn refreshed_flips() {
+        let db = overnight();
+        let clock = ManualClock::new(cdt(2, 6, 0));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Skip));
+        assert!(scheduler.start().1.is_empty());
+        let porch = db.get_all_switches().unwrap()[0].id;
+        db.new_dated_flip(porch, NaiveDate::from_ymd(2018, 10, 2), 6, 30, Direction::Off, FlipKind::Custom).unwrap();
+        scheduler.refresh();
+        assert_eq!(run_until(&mut scheduler, &clock, cdt(2, 7, 0)), vec![
+            (cdt(2, 6, 30), flip(6, 30, 2)),
+            (cdt(2, 7, 0), flip(7, 0, 1)),
+        ]);
+    }
+
+    #[test]
+    fn unsent_flips() {
+        let db = overnight();
+        let clock = ManualClock::new(cdt(1, 23, 0));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        clock.set(cdt(1, 23, 59));
+        // handed out but never sent
+        assert_eq!(scheduler.tick().1, vec![flip(23, 59, 1)]);
+        assert_eq!(db.get_last_processed().unwrap(), None);
+        // not handed out again until a restart
+        scheduler.refresh();
+        assert!(scheduler.tick().1.is_empty());
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        assert_eq!(scheduler.start().1, vec![flip(23, 59, 1)]);
+    }
+
+    /// A store with a fan (3 on, 4 off) turned
+    /// on at 23:45 for half an hour every day
+    fn timed() -> Arc<dyn Store> {
+        let db = MemoryStore::new();
+        let fan = db.new_switch("fan", 3, 4).unwrap();
+        let on = db.new_scheduled_flip(fan.id, 23, 45, 127.into(), Direction::On, FlipKind::Custom, 0, None, None).unwrap();
+        db.update_flip(on.id, 23, 45, 127.into(), Direction::On, FlipKind::Custom, 0, Some(30), None).unwrap();
+        Arc::new(db)
+    }
+
+    #[test]
+    fn timed_flips() {
+        let db = timed();
+        let clock = ManualClock::new(cdt(1, 23, 0));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Skip));
+        let on = Flip {
+            duration: Some(30),
+            ..flip(23, 45, 3)
+        };
+        // the off is sent the next day
+        assert_eq!(run_until(&mut scheduler, &clock, cdt(2, 1, 0)), vec![
+            (cdt(1, 23, 45), on),
+            (cdt(2, 0, 15), flip(0, 15, 4)),
+        ]);
+        assert!(db.get_pending_offs().unwrap().is_empty());
+    }
+
+    #[test]
+    fn timed_flip_restarts() {
+        let db = timed();
+        let clock = ManualClock::new(cdt(1, 23, 40));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Skip));
+        run_until(&mut scheduler, &clock, cdt(1, 23, 50));
+        assert_eq!(db.get_pending_offs().unwrap().len(), 1);
+        // the off is still waiting after a restart
+        let restarted = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Skip));
+        assert_eq!(restarted.pending(), vec![(cdt(2, 0, 15), flip(0, 15, 4))]);
+        assert_eq!(restarted.next_wakeup(), cdt(2, 0, 0));
+        // and is sent when missed, even when skipping
+        clock.set(cdt(2, 1, 0));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Skip));
+        let (at, flips) = scheduler.start();
+        assert_eq!(flips, vec![flip(0, 15, 4)]);
+        // cleared out once it has been sent
+        assert_eq!(db.get_pending_offs().unwrap().len(), 1);
+        scheduler.mark_processed(at);
+        assert!(db.get_pending_offs().unwrap().is_empty());
+        assert!(scheduler.pending().iter().all(|(_, f)| f.code == 3));
+    }
+
+    #[test]
+    fn offs_added_meanwhile() {
+        let db = timed();
+        let clock = ManualClock::new(cdt(1, 23, 40));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Skip));
+        run_until(&mut scheduler, &clock, cdt(2, 0, 5));
+        // a lamp turned on for a while from flipper,
+        // before the scheduler is told to reload
+        let lamp = db.new_pending_off(6, cdt(2, 0, 10)).unwrap();
+        assert_eq!(run_until(&mut scheduler, &clock, cdt(2, 0, 20)), vec![(cdt(2, 0, 15), flip(0, 15, 4))]);
+        // only the off that was sent is cleared
+        assert_eq!(db.get_pending_offs().unwrap(), vec![lamp]);
+        scheduler.refresh();
+        let (at, flips) = scheduler.tick();
+        assert_eq!(flips, vec![flip(0, 10, 6)]);
+        scheduler.mark_processed(at);
+        assert!(db.get_pending_offs().unwrap().is_empty());
+    }
+
+    #[test]
+    fn paused_schedule() {
+        let db = overnight();
+        db.update_pause(Pause {
+            paused: true,
+            resume_at: Some(cdt(2, 0, 0)),
+        }).unwrap();
+        let clock = ManualClock::new(cdt(1, 22, 0));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        // held back until it resumes by itself
+        assert_eq!(run_until(&mut scheduler, &clock, cdt(2, 8, 0)), vec![
+            (cdt(2, 0, 0), flip(0, 0, 2)),
+            (cdt(2, 7, 0), flip(7, 0, 1)),
+        ]);
+        assert_eq!(db.get_pause().unwrap(), Pause::default());
+        // paused until it is turned off, skipped flips aren't caught up
+        db.update_pause(Pause {
+            paused: true,
+            resume_at: None,
+        }).unwrap();
+        clock.set(cdt(1, 22, 0));
+        db.update_last_processed(clock.now()).unwrap();
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        assert!(run_until(&mut scheduler, &clock, cdt(2, 0, 30)).is_empty());
+        db.update_pause(Pause::default()).unwrap();
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        assert!(scheduler.start().1.is_empty());
+        assert_eq!(run_until(&mut scheduler, &clock, cdt(2, 8, 0)), vec![(cdt(2, 7, 0), flip(7, 0, 1))]);
+    }
+
+    #[test]
+    fn held_switches() {
+        let db = overnight();
+        let porch = db.get_all_switches().unwrap()[0].id;
+        let fan = db.new_switch("fan", 3, 4).unwrap();
+        db.new_dated_flip(fan.id, NaiveDate::from_ymd(2018, 10, 2), 0, 0, Direction::On, FlipKind::Custom).unwrap();
+        db.new_hold(porch, Some(cdt(2, 6, 0))).unwrap();
+        let clock = ManualClock::new(cdt(1, 22, 0));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Latest));
+        assert_eq!(run_until(&mut scheduler, &clock, cdt(2, 8, 0)), vec![
+            (cdt(2, 0, 0), flip(0, 0, 3)),
+            (cdt(2, 7, 0), flip(7, 0, 1)),
+        ]);
+        // the hold ran out and was cleared
+        assert!(db.get_holds().unwrap().is_empty());
+    }
+
+    #[test]
+    fn paused_offs() {
+        let db = timed();
+        let clock = ManualClock::new(cdt(1, 23, 40));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Skip));
+        run_until(&mut scheduler, &clock, cdt(1, 23, 50));
+        // paused across the off, which waits for the resume
+        db.update_pause(Pause {
+            paused: true,
+            resume_at: Some(cdt(2, 0, 30)),
+        }).unwrap();
+        scheduler.refresh();
+        let mut sent = Vec::new();
+        while scheduler.next_wakeup() < cdt(2, 1, 0) {
+            clock.set(scheduler.next_wakeup());
+            let (at, flips) = scheduler.tick();
+            for flip in flips {
+                sent.push((clock.now(), flip));
+            }
+            scheduler.mark_processed(at);
+        }
+        assert_eq!(sent, vec![(cdt(2, 0, 30), flip(0, 15, 4))]);
+        assert!(db.get_pending_offs().unwrap().is_empty());
+        assert_eq!(db.get_pause().unwrap(), Pause::default());
+        // paused until it is turned off
+        clock.set(cdt(2, 23, 40));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Skip));
+        run_until(&mut scheduler, &clock, cdt(2, 23, 50));
+        db.update_pause(Pause {
+            paused: true,
+            resume_at: None,
+        }).unwrap();
+        scheduler.refresh();
+        assert!(run_until(&mut scheduler, &clock, cdt(3, 1, 0)).is_empty());
+        assert_eq!(db.get_pending_offs().unwrap().len(), 1);
+        db.update_pause(Pause::default()).unwrap();
+        scheduler.refresh();
+        let (at, flips) = scheduler.tick();
+        assert_eq!(flips, vec![flip(0, 15, 4)]);
+        assert_eq!(db.get_pending_offs().unwrap().len(), 1);
+        scheduler.mark_processed(at);
+        assert!(db.get_pending_offs().unwrap().is_empty());
+    }
+
+    #[test]
+    fn held_offs() {
+        let db = timed();
+        let fan = db.get_all_switches().unwrap()[0].id;
+        let clock = ManualClock::new(cdt(1, 23, 40));
+        let mut scheduler = Scheduler::new(&clock, db.clone(), Chicago, config(CatchUp::Skip));
+        run_until(&mut scheduler, &clock, cdt(1, 23, 50));
+        db.new_hold(fan, Some(cdt(2, 0, 40))).unwrap();
+        scheduler.refresh();
+        // the off goes out when the hold runs out
+        assert_eq!(run_until(&mut scheduler, &clock, cdt(2, 1, 0)), vec![(cdt(2, 0, 40), flip(0, 15, 4))]);
+        assert!(db.get_pending_offs().unwrap().is_empty());
+        assert!(db.get_holds().unwrap().is_empty());
+    }
+}
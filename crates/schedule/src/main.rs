@@ -3,35 +3,43 @@ extern crate env_logger;
 #[macro_use]
 extern crate log;
 extern crate robohome_shared;
+extern crate schedule;
 
 use std::{
     sync::mpsc::{
         channel,
         Receiver,
+        RecvTimeoutError,
     },
-    thread::{
-        Builder,
-        sleep,
-    },
+    thread::Builder,
     time::Duration,
 };
 
 use chrono::{
+    DateTime,
     Utc,
-    Timelike,
 };
 
 use robohome_shared::{
+    config::{
+        Config,
+        Requirement,
+    },
     data::{
+        self,
         Flip,
-        get_flips_for_today,
     },
     Error,
-    ipc::{
-        listen,
-        send
-    },
+    ipc::Ipc,
 };
+use schedule::{
+    Scheduler,
+    SystemClock,
+};
+
+/// The longest to sleep between checks, so
+/// a change to the system clock is noticed
+const MAX_WAIT: Duration = Duration::from_secs(5 * 60);
 
 fn main() -> Result<(), Error> {
 
@@ -40,18 +48,16 @@ fn main() -> Result<(), Error> {
     }
     env_logger::init();
     info!("Booting scheduler");
+    let config = Config::load(&[Requirement::Database, Requirement::Amqp])?;
+    let db = data::connect(&config.database)?;
+    for version in db.migrate()? {
+        info!("Applied schema migration {}", version);
+    }
+    let ipc = Ipc::new(&config.amqp.url);
+    let tz = config.location.time_zone;
+    info!("Scheduling flips in {}", tz.name());
+    let schedule = config.schedule.clone();
     let (tx, rx) = channel();
-    let timer_tx = tx.clone();
-
-    let _ = Builder::new()
-            .name(format!("timer_thread"))
-            .spawn(move || {
-                let tx = timer_tx;
-                loop {
-                    let _ = tx.send(Message::Tick);
-                    sleep(Duration::from_secs(60));
-                }
-            });
     let (lookup_tx, lookup_rx) = channel();
     let tx2 = tx.clone();
     let _ = Builder::new()
@@ -60,49 +66,45 @@ fn main() -> Result<(), Error> {
             info!("Spawning lookup thread");
             let out = tx2;
             let rx = lookup_rx;
-            let mut all_day: Vec<Flip> = match get_flips_for_today() {
-                Ok(f) => f,
-                Err(e) => {
-                    error!("failed to get initial flips for today: {}", e);
-                    vec![]
-                },
-            };
-            debug!("initial flips {:#?}", all_day);
+            let mut scheduler = Scheduler::new(SystemClock, db, tz, schedule);
+            debug!("initial flips {:#?}", scheduler.pending());
+            let (at, missed) = scheduler.start();
+            if !missed.is_empty() {
+                let _ = out.send(Message::Flips(at, missed));
+            }
             loop {
-                match rx.recv() {
+                let wait = scheduler.until_next().min(MAX_WAIT);
+                debug!("lookup: waking in {:?}", wait);
+                match rx.recv_timeout(wait) {
                     Ok(msg) => match msg {
-                        Message::Tick => {
-                            info!("lookup: tick");
-                            let now = Utc::now();
-                            let now = now.time();
-                            let for_sending: Vec<Flip> = all_day.clone().into_iter().filter(|f| {
-                                chrono::NaiveTime::from_hms(f.hour as u32, f.minute as u32, 0) < now
-                            }).collect();
-                            all_day.retain(|f| !for_sending.contains(f));
-                            let _ = out.send(Message::Flips(for_sending));
-                        },
+                        Message::Sent(at) => scheduler.mark_processed(at),
                         Message::Refresh => {
                             info!("lookup: refresh");
-                            match get_flips_for_today() {
-                                Ok(today) => all_day = today,
-                                Err(e) => {
-                                    error!("Failed to get flips for today: {}", e);
-                                    all_day = Vec::new();
-                                }
-                            }
+                            scheduler.refresh();
                         },
                         _ => info!("Unknown message"),
                     },
-                    Err(e) => error!(target: "robohome", "lookup_thread error: {}", e),
+                    Err(RecvTimeoutError::Timeout) => {
+                        let (at, flips) = scheduler.tick();
+                        if !flips.is_empty() {
+                            info!("lookup: sending {} flips", flips.len());
+                            let _ = out.send(Message::Flips(at, flips));
+                        }
+                    },
+                    Err(e) => {
+                        error!(target: "robohome", "lookup_thread error: {}", e);
+                        return;
+                    },
                 }
             }
         });
+    let listener = ipc.clone();
     let _ = Builder::new()
         .name(format!("db_update_thread"))
         .spawn(move || {
             info!("spawning db update thread");
             let tx = tx;
-            let db_rx: Receiver<Result<(), Error>> = match listen("database") {
+            let db_rx: Receiver<Result<(), Error>> = match listener.listen("database") {
                 Ok(rx) => rx,
                 Err(e) => {
                     error!("Failed to create mq listener for database updates: {}", e);
@@ -119,20 +121,24 @@ fn main() -> Result<(), Error> {
                 }
             }
         });
+    let mut failed = false;
     loop {
         match rx.recv() {
             Ok(msg) => {
                 debug!("Main Thread: {:?}", msg);
                 match msg {
-                    Message::Tick => {
-                        if let Err(e) = lookup_tx.send(Message::Tick){
-                            error!("Failed to send tick message {}", e);
-                        }
-                    },
-                    Message::Flips(flips) => {
+                    Message::Flips(at, flips) => {
                         for ref flip in flips {
-                            if let Err(e) = send("switches", &flip){
+                            if let Err(e) = ipc.send("switches", &flip){
                                 error!("Failed to send flip message {}", e);
+                                failed = true;
+                            }
+                        }
+                        // once a flip fails to send nothing more is
+                        // marked, so a restart still catches up on it
+                        if !failed {
+                            if let Err(e) = lookup_tx.send(Message::Sent(at)) {
+                                error!("Failed to send sent message {}", e);
                             }
                         }
                     },
@@ -140,16 +146,18 @@ fn main() -> Result<(), Error> {
                         if let Err(e) = lookup_tx.send(Message::Refresh){
                             error!("Failed to send refresh message {}", e);
                         }
-                    }
+                    },
+                    _ => info!("Unknown message"),
                 }
             },
             Err(e) => return Err(e.into()),
         }
     }
 }
+
 #[derive(Debug)]
 enum Message {
-    Flips(Vec<Flip>),
+    Flips(DateTime<Utc>, Vec<Flip>),
+    Sent(DateTime<Utc>),
     Refresh,
-    Tick,
-}
\ No newline at end of file
+}
@@ -0,0 +1,242 @@
+//! Makes the house look lived-in while we are away.
+//! Everything here is random but worked out from the
+//! date, so a restart or refresh on the same day
+//! gives the same times
+use std::collections::HashMap;
+
+use chrono::{
+    Datelike,
+    NaiveDate,
+    NaiveTime,
+    Timelike,
+};
+
+use robohome_shared::{
+    config::VacationConfig,
+    data::{
+        Direction,
+        Flip,
+        Group,
+        Switch,
+    },
+};
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+/// Move each switch's flips by up to `minutes` either way,
+/// all of them by the same amount so an on and off that
+/// are close together keep their order. The amount is cut
+/// down where it would move one of them off `date`
+pub fn jitter(date: NaiveDate, flips: Vec<Flip>, switches: &[Switch], minutes: i64) -> Vec<Flip> {
+    let mut spans: HashMap<i32, (i64, i64)> = HashMap::new();
+    for f in &flips {
+        let at = to_minutes(f.hour, f.minute);
+        let span = spans.entry(owner(switches, f.code)).or_insert((at, at));
+        span.0 = span.0.min(at);
+        span.1 = span.1.max(at);
+    }
+    let offsets: HashMap<i32, i64> = spans.into_iter()
+        .map(|(owner, (first, last))| {
+            let mut rng = Rng::new(date, &[i64::from(owner)]);
+            let offset = rng.below(minutes as u64 * 2 + 1) as i64 - minutes;
+            (owner, offset.max(-first).min(MINUTES_PER_DAY - 1 - last))
+        })
+        .collect();
+    flips.into_iter()
+        .map(|f| {
+            let offset = offsets[&owner(switches, f.code)];
+            let (hour, minute) = from_minutes(to_minutes(f.hour, f.minute) + offset);
+            Flip {
+                hour,
+                minute,
+                ..f
+            }
+        })
+        .collect()
+}
+
+/// The on code of the switch `code` belongs to,
+/// codes that don't belong to a switch stand alone
+fn owner(switches: &[Switch], code: i32) -> i32 {
+    switches.iter()
+        .find(|s| s.on_code == code || s.off_code == code)
+        .map(|s| s.on_code)
+        .unwrap_or(code)
+}
+
+/// Extra on and off flips for each switch in `rooms`. The
+/// window between `cycles_from` and `cycles_until` is split
+/// evenly between the cycles so they never overlap
+pub fn cycles(date: NaiveDate, rooms: &[Group], config: &VacationConfig) -> Vec<Flip> {
+    if config.cycles == 0 {
+        return Vec::new();
+    }
+    let from = time_minutes(config.cycles_from);
+    let window = time_minutes(config.cycles_until) - from;
+    let slot = window / i64::from(config.cycles);
+    if slot < 1 {
+        return Vec::new();
+    }
+    let longest = config.cycle_minutes.min(slot);
+    let shortest = (longest / 2).max(1);
+    let mut ret = Vec::new();
+    for room in rooms {
+        let mut rng = Rng::new(date, &[i64::from(room.id)]);
+        for i in 0..i64::from(config.cycles) {
+            let length = shortest + rng.below((longest - shortest + 1) as u64) as i64;
+            let on = from + i * slot + rng.below((slot - length + 1) as u64) as i64;
+            ret.extend(room_flips(room, Direction::On, on));
+            ret.extend(room_flips(room, Direction::Off, on + length));
+        }
+    }
+    ret
+}
+
+fn room_flips(room: &Group, direction: Direction, at: i64) -> Vec<Flip> {
+    let (hour, minute) = from_minutes(at);
+    room.codes(direction)
+        .into_iter()
+        .map(|code| Flip {
+            hour,
+            minute,
+            code,
+            duration: None,
+        })
+        .collect()
+}
+
+fn to_minutes(hour: i32, minute: i32) -> i64 {
+    i64::from(hour) * 60 + i64::from(minute)
+}
+
+fn time_minutes(time: NaiveTime) -> i64 {
+    to_minutes(time.hour() as i32, time.minute() as i32)
+}
+
+/// Clamped to the day
+fn from_minutes(minutes: i64) -> (i32, i32) {
+    let minutes = minutes.max(0).min(MINUTES_PER_DAY - 1);
+    ((minutes / 60) as i32, (minutes % 60) as i32)
+}
+
+/// splitmix64, seeded from the date and
+/// whatever should make a sequence unique
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(date: NaiveDate, parts: &[i64]) -> Rng {
+        let mut ret = Rng {
+            state: date.num_days_from_ce() as u64,
+        };
+        for part in parts {
+            ret.state = ret.next() ^ *part as u64;
+        }
+        ret
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// 0 up to but not including `n`
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn flip(hour: i32, minute: i32, code: i32) -> Flip {
+        Flip {
+            hour,
+            minute,
+            code,
+            duration: None,
+        }
+    }
+
+    fn monday() -> NaiveDate {
+        NaiveDate::from_ymd(2018, 10, 1)
+    }
+
+    fn living_room() -> Group {
+        Group {
+            id: 1,
+            name: "Living Room".to_string(),
+            switches: vec![
+                Switch { id: 1, name: "Lamp".to_string(), on_code: 11, off_code: 12 },
+                Switch { id: 2, name: "Tree".to_string(), on_code: 21, off_code: 22 },
+            ],
+        }
+    }
+
+    #[test]
+    fn jitter_each_day() {
+        let flips = vec![flip(7, 0, 1), flip(19, 30, 2), flip(0, 5, 3), flip(23, 55, 4)];
+        let moved = jitter(monday(), flips.clone(), &[], 15);
+        assert_eq!(moved, jitter(monday(), flips.clone(), &[], 15));
+        assert_ne!(moved, jitter(monday().succ(), flips.clone(), &[], 15));
+        for (before, after) in flips.iter().zip(moved.iter()) {
+            assert_eq!(before.code, after.code);
+            let offset = to_minutes(after.hour, after.minute) - to_minutes(before.hour, before.minute);
+            assert!(offset.abs() <= 15, "{:?} moved to {:?}", before, after);
+        }
+        assert_eq!(jitter(monday(), flips.clone(), &[], 0), flips);
+        // never moved off the day
+        let edges = jitter(monday(), vec![flip(0, 0, 5), flip(23, 59, 6)], &[], 60);
+        assert!(edges.iter().all(|f| f.hour >= 0 && f.hour < 24));
+    }
+
+    #[test]
+    fn jitter_keeps_order() {
+        // an on and off 10 minutes apart with 30 minutes
+        // of jitter are still 10 minutes apart, every day
+        let switches = living_room().switches;
+        let mut date = monday();
+        for _ in 0..60 {
+            let moved = jitter(date, vec![flip(20, 0, 11), flip(20, 10, 12), flip(20, 5, 21)], &switches, 30);
+            let on = to_minutes(moved[0].hour, moved[0].minute);
+            let off = to_minutes(moved[1].hour, moved[1].minute);
+            assert_eq!(off - on, 10, "{:?} on {}", moved, date);
+            assert!((on - to_minutes(20, 0)).abs() <= 30, "{:?} on {}", moved, date);
+            date = date.succ();
+        }
+        // a switch's flips near midnight move together too
+        let moved = jitter(monday(), vec![flip(23, 45, 11), flip(23, 55, 12)], &switches, 30);
+        let on = to_minutes(moved[0].hour, moved[0].minute);
+        let off = to_minutes(moved[1].hour, moved[1].minute);
+        assert_eq!(off - on, 10, "{:?}", moved);
+    }
+
+    #[test]
+    fn extra_cycles() {
+        let config = VacationConfig::default();
+        let flips = cycles(monday(), &[living_room()], &config);
+        assert_eq!(flips, cycles(monday(), &[living_room()], &config));
+        // on and off for both switches, twice
+        assert_eq!(flips.len(), 8);
+        let mut last_off = to_minutes(18, 0);
+        for cycle in flips.chunks(4) {
+            assert_eq!(cycle.iter().map(|f| f.code).collect::<Vec<_>>(), vec![11, 21, 12, 22]);
+            let on = to_minutes(cycle[0].hour, cycle[0].minute);
+            let off = to_minutes(cycle[2].hour, cycle[2].minute);
+            assert!(on >= last_off, "{:?}", flips);
+            assert!(off > on && off - on <= config.cycle_minutes, "{:?}", flips);
+            last_off = off;
+        }
+        assert!(last_off <= to_minutes(23, 0));
+        let none = VacationConfig {
+            cycles: 0,
+            ..VacationConfig::default()
+        };
+        assert!(cycles(monday(), &[living_room()], &none).is_empty());
+    }
+}
@@ -1 +1 @@
-psql robohome_again -f ../../migrations/initial.down.sql && psql robohome_again -f ../../migrations/initial.sql && psql robohome_again -f ../../migrations/initial.seed.sql && cargo run
\ No newline at end of file
+psql robohome_again -f ../../migrations/postgres/021_pause.down.sql && psql robohome_again -f ../../migrations/postgres/020_timed_flips.down.sql && psql robohome_again -f ../../migrations/postgres/019_vacation.down.sql && psql robohome_again -f ../../migrations/postgres/018_schedule_state.down.sql && psql robohome_again -f ../../migrations/postgres/017_weather_adjustment.down.sql && psql robohome_again -f ../../migrations/postgres/016_refresh_log.down.sql && psql robohome_again -f ../../migrations/postgres/015_twilight_special_times.down.sql && psql robohome_again -f ../../migrations/postgres/014_solar_noon_kind.down.sql && psql robohome_again -f ../../migrations/postgres/013_astronomical_dusk_kind.down.sql && psql robohome_again -f ../../migrations/postgres/012_astronomical_dawn_kind.down.sql && psql robohome_again -f ../../migrations/postgres/011_nautical_dusk_kind.down.sql && psql robohome_again -f ../../migrations/postgres/010_nautical_dawn_kind.down.sql && psql robohome_again -f ../../migrations/postgres/009_civil_dusk_kind.down.sql && psql robohome_again -f ../../migrations/postgres/008_civil_dawn_kind.down.sql && psql robohome_again -f ../../migrations/postgres/007_special_time_dates.down.sql && psql robohome_again -f ../../migrations/postgres/006_solar_offsets.down.sql && psql robohome_again -f ../../migrations/postgres/005_seasons.down.sql && psql robohome_again -f ../../migrations/postgres/004_dated_flips.down.sql && psql robohome_again -f ../../migrations/postgres/003_scenes.down.sql && psql robohome_again -f ../../migrations/postgres/002_groups.down.sql && psql robohome_again -f ../../migrations/postgres/001_initial.down.sql && psql robohome_again -f ../../migrations/postgres/001_initial.sql && psql robohome_again -f ../../migrations/seed.sql && cargo run
@@ -0,0 +1,659 @@
+//! Runtime configuration shared by all of
+//! the binaries. Settings are read from a TOML
+//! file (`ROBOHOME_CONFIG` or `/etc/robohome/robohome.toml`)
+//! and then any `ROBOHOME_*` environment variables
+//! are applied on top
+use chrono::NaiveTime;
+use chrono_tz::Tz;
+use serde::{
+    Deserialize,
+    Deserializer,
+    de::Error as DeError,
+};
+use toml;
+
+use std::{
+    env,
+    fmt::Display,
+    fs::File,
+    io::Read,
+    net::SocketAddr,
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
+
+use data::PoolConfig;
+use Error;
+
+const DEFAULT_PATH: &str = "/etc/robohome/robohome.toml";
+/// Far enough ahead to ride out a long provider outage
+const MAX_DAYS_AHEAD: u32 = 30;
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub amqp: AmqpConfig,
+    pub flipper: FlipperConfig,
+    pub location: LocationConfig,
+    pub remote: RemoteConfig,
+    pub refresher: RefresherConfig,
+    pub schedule: ScheduleConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    /// `postgres://`, `sqlite://` or `memory://`
+    pub url: String,
+    pub pool_size: u32,
+    /// Seconds to wait for a pooled connection
+    pub connection_timeout: u64,
+    pub health_check: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AmqpConfig {
+    pub url: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FlipperConfig {
+    pub bind: SocketAddr,
+}
+
+/// Where the house is
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LocationConfig {
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    #[serde(deserialize_with = "deserialize_time_zone")]
+    pub time_zone: Tz,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// The GPIO pin the transmitter is wired to
+    pub rf_pin: u8,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RefresherConfig {
+    /// Defaults to `wunderground` when `api_url`
+    /// is set and `calculate` when it isn't
+    pub provider: Option<SunProvider>,
+    /// The provider's endpoint, required for
+    /// `wunderground` and optional for `sunrise_sunset`
+    pub api_url: Option<String>,
+    /// How many more times to ask a provider
+    /// that couldn't be reached
+    pub retries: u32,
+    /// Seconds to wait before the first retry,
+    /// doubled for each one after that
+    pub retry_delay: u64,
+    /// Alert when the newest sun times are
+    /// more than this many days old
+    pub stale_days: i64,
+    /// Keep running and refresh once a day
+    /// instead of exiting after one refresh
+    pub daemon: bool,
+    /// The local time to refresh at in daemon mode
+    #[serde(deserialize_with = "deserialize_time")]
+    pub refresh_at: NaiveTime,
+    /// How many days after today to refresh as well
+    pub days_ahead: u32,
+    /// An Open-Meteo style forecast endpoint, the
+    /// evening times aren't moved for cloud cover
+    /// when it isn't set
+    pub weather_url: Option<String>,
+    /// Minutes to move dusk and sunset earlier
+    /// when the sky is completely covered
+    pub cloud_adjustment: i32,
+}
+
+/// Where refresher gets each day's sun times
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SunProvider {
+    /// Worked out locally from `location`
+    Calculate,
+    /// A sunrise-sunset.org style JSON API
+    SunriseSunset,
+    /// A Wunderground style astronomy endpoint
+    Wunderground,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// What to do with the flips that were due
+    /// while the scheduler wasn't running
+    pub catch_up: CatchUp,
+    /// Minutes back `grace` catches up
+    pub grace: i64,
+    pub vacation: VacationConfig,
+}
+
+/// What the scheduler does while vacation
+/// mode is turned on
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct VacationConfig {
+    /// Minutes either side of its time each
+    /// flip can be moved, different every day
+    pub jitter: i64,
+    /// Groups to turn on and off a few
+    /// extra times each day
+    pub rooms: Vec<String>,
+    /// How many extra times to turn each room on
+    pub cycles: u32,
+    /// The local time the extra cycles can start at
+    #[serde(deserialize_with = "deserialize_time")]
+    pub cycles_from: NaiveTime,
+    /// The local time the extra cycles are over by
+    #[serde(deserialize_with = "deserialize_time")]
+    pub cycles_until: NaiveTime,
+    /// The longest an extra cycle leaves a room on
+    pub cycle_minutes: i64,
+}
+
+/// A setting a binary can't run without,
+/// checked by `load`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Requirement {
+    /// `database.url`
+    Database,
+    /// `amqp.url`
+    Amqp,
+}
+
+/// How the scheduler treats flips it
+/// missed, on startup
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CatchUp {
+    /// Send none of them
+    Skip,
+    /// Send the last one for each switch
+    Latest,
+    /// Send the ones due in the last `grace` minutes
+    Grace,
+}
+
+impl Config {
+    /// Load, override and validate the configuration,
+    /// `needs` being the settings the caller has to have
+    pub fn load(needs: &[Requirement]) -> Result<Config, Error> {
+        let mut ret = match env::var("ROBOHOME_CONFIG") {
+            Ok(path) => Config::from_file(path)?,
+            Err(_) => if Path::new(DEFAULT_PATH).exists() {
+                Config::from_file(DEFAULT_PATH)?
+            } else {
+                Config::default()
+            },
+        };
+        ret.apply_overrides(|key| env::var(key).ok())?;
+        ret.validate_for(needs)?;
+        Ok(ret)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Config, Error> {
+        let mut f = File::open(path)?;
+        let mut s = String::new();
+        f.read_to_string(&mut s)?;
+        Config::from_str(&s)
+    }
+
+    /// Replace any setting that has a matching
+    /// `ROBOHOME_*` value
+    pub fn apply_overrides<F>(&mut self, lookup: F) -> Result<(), Error>
+    where F: Fn(&str) -> Option<String> {
+        if let Some(url) = lookup("ROBOHOME_DATABASE_URL") {
+            self.database.url = url;
+        }
+        if let Some(url) = lookup("ROBOHOME_AMQP_URL") {
+            self.amqp.url = url;
+        }
+        if let Some(bind) = parse_override(&lookup, "ROBOHOME_BIND")? {
+            self.flipper.bind = bind;
+        }
+        if let Some(lat) = parse_override(&lookup, "ROBOHOME_LATITUDE")? {
+            self.location.latitude = Some(lat);
+        }
+        if let Some(lon) = parse_override(&lookup, "ROBOHOME_LONGITUDE")? {
+            self.location.longitude = Some(lon);
+        }
+        if let Some(tz) = parse_override(&lookup, "ROBOHOME_TIME_ZONE")? {
+            self.location.time_zone = tz;
+        }
+        if let Some(pin) = parse_override(&lookup, "ROBOHOME_RF_PIN")? {
+            self.remote.rf_pin = pin;
+        }
+        if let Some(url) = lookup("ROBOHOME_API_URL") {
+            self.refresher.api_url = Some(url);
+        }
+        if let Some(provider) = parse_override(&lookup, "ROBOHOME_SUN_PROVIDER")? {
+            self.refresher.provider = Some(provider);
+        }
+        if let Some(url) = lookup("ROBOHOME_WEATHER_URL") {
+            self.refresher.weather_url = Some(url);
+        }
+        if let Some(daemon) = parse_override(&lookup, "ROBOHOME_REFRESHER_DAEMON")? {
+            self.refresher.daemon = daemon;
+        }
+        if let Some(catch_up) = parse_override(&lookup, "ROBOHOME_CATCH_UP")? {
+            self.schedule.catch_up = catch_up;
+        }
+        Ok(())
+    }
+
+    /// `validate` and make sure each of `needs` is set
+    pub fn validate_for(&self, needs: &[Requirement]) -> Result<(), Error> {
+        for need in needs {
+            match need {
+                Requirement::Database => if self.database.url.trim().is_empty() {
+                    return Err(Error::new("database.url is required"));
+                },
+                Requirement::Amqp => if self.amqp.url.trim().is_empty() {
+                    return Err(Error::new("amqp.url is required"));
+                },
+            }
+        }
+        self.validate()
+    }
+
+    /// Check the values that are set, whichever
+    /// binary ends up using them
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.database.pool_size == 0 {
+            return Err(Error::new("database.pool_size must be at least 1"));
+        }
+        if let Some(lat) = self.location.latitude {
+            if lat < -90.0 || lat > 90.0 {
+                return Err(Error::Other(format!("location.latitude {} is out of range", lat)));
+            }
+        }
+        if let Some(lon) = self.location.longitude {
+            if lon < -180.0 || lon > 180.0 {
+                return Err(Error::Other(format!("location.longitude {} is out of range", lon)));
+            }
+        }
+        if self.refresher.stale_days < 0 {
+            return Err(Error::new("refresher.stale_days can't be negative"));
+        }
+        if self.refresher.cloud_adjustment < 0 {
+            return Err(Error::new("refresher.cloud_adjustment can't be negative"));
+        }
+        if self.refresher.days_ahead > MAX_DAYS_AHEAD {
+            return Err(Error::Other(format!("refresher.days_ahead can't be more than {}", MAX_DAYS_AHEAD)));
+        }
+        if self.schedule.grace < 0 {
+            return Err(Error::new("schedule.grace can't be negative"));
+        }
+        if self.schedule.vacation.jitter < 0 {
+            return Err(Error::new("schedule.vacation.jitter can't be negative"));
+        }
+        if self.schedule.vacation.cycles_from >= self.schedule.vacation.cycles_until {
+            return Err(Error::new("schedule.vacation.cycles_from must be before cycles_until"));
+        }
+        if self.schedule.vacation.cycle_minutes < 1 {
+            return Err(Error::new("schedule.vacation.cycle_minutes must be at least 1"));
+        }
+        if self.refresher.provider() == SunProvider::Wunderground {
+            if self.refresher.api_url.is_none() {
+                return Err(Error::new("refresher.api_url is required for the wunderground provider"));
+            }
+            // it only has today, the days after are calculated
+            if self.refresher.days_ahead > 0 && self.coordinates().is_err() {
+                return Err(Error::new("refresher.days_ahead needs location.latitude and location.longitude for the wunderground provider"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The configured latitude and longitude, for
+    /// the binaries that can't run without them
+    pub fn coordinates(&self) -> Result<(f64, f64), Error> {
+        match (self.location.latitude, self.location.longitude) {
+            (Some(lat), Some(lon)) => Ok((lat, lon)),
+            _ => Err(Error::new("location.latitude and location.longitude are required")),
+        }
+    }
+}
+
+impl Default for RefresherConfig {
+    fn default() -> Self {
+        RefresherConfig {
+            provider: None,
+            api_url: None,
+            retries: 3,
+            retry_delay: 5,
+            stale_days: 2,
+            daemon: false,
+            refresh_at: NaiveTime::from_hms(2, 0, 0),
+            days_ahead: 3,
+            weather_url: None,
+            cloud_adjustment: 30,
+        }
+    }
+}
+
+impl RefresherConfig {
+    pub fn provider(&self) -> SunProvider {
+        match (self.provider, &self.api_url) {
+            (Some(provider), _) => provider,
+            (None, Some(_)) => SunProvider::Wunderground,
+            (None, None) => SunProvider::Calculate,
+        }
+    }
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        ScheduleConfig {
+            catch_up: CatchUp::Latest,
+            grace: 15,
+            vacation: VacationConfig::default(),
+        }
+    }
+}
+
+impl Default for VacationConfig {
+    fn default() -> Self {
+        VacationConfig {
+            jitter: 15,
+            rooms: Vec::new(),
+            cycles: 2,
+            cycles_from: NaiveTime::from_hms(18, 0, 0),
+            cycles_until: NaiveTime::from_hms(23, 0, 0),
+            cycle_minutes: 45,
+        }
+    }
+}
+
+impl SunProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SunProvider::Calculate => "calculate",
+            SunProvider::SunriseSunset => "sunrise_sunset",
+            SunProvider::Wunderground => "wunderground",
+        }
+    }
+}
+
+impl FromStr for SunProvider {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<SunProvider, Error> {
+        match s {
+            "calculate" => Ok(SunProvider::Calculate),
+            "sunrise_sunset" => Ok(SunProvider::SunriseSunset),
+            "wunderground" => Ok(SunProvider::Wunderground),
+            _ => Err(Error::Other(format!("Unknown sun provider {}", s))),
+        }
+    }
+}
+
+impl CatchUp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CatchUp::Skip => "skip",
+            CatchUp::Latest => "latest",
+            CatchUp::Grace => "grace",
+        }
+    }
+}
+
+impl FromStr for CatchUp {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<CatchUp, Error> {
+        match s {
+            "skip" => Ok(CatchUp::Skip),
+            "latest" => Ok(CatchUp::Latest),
+            "grace" => Ok(CatchUp::Grace),
+            _ => Err(Error::Other(format!("Unknown catch up policy {}", s))),
+        }
+    }
+}
+
+impl FromStr for Config {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Config, Error> {
+        let ret = toml::from_str(s)?;
+        Ok(ret)
+    }
+}
+
+impl DatabaseConfig {
+    pub fn pool(&self) -> PoolConfig {
+        PoolConfig {
+            max_size: self.pool_size,
+            connection_timeout: Duration::from_secs(self.connection_timeout),
+            test_on_check_out: self.health_check,
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        let pool = PoolConfig::default();
+        DatabaseConfig {
+            url: String::new(),
+            pool_size: pool.max_size,
+            connection_timeout: pool.connection_timeout.as_secs(),
+            health_check: pool.test_on_check_out,
+        }
+    }
+}
+
+impl Default for AmqpConfig {
+    fn default() -> Self {
+        AmqpConfig {
+            url: "amqp://localhost//".to_string(),
+        }
+    }
+}
+
+impl Default for FlipperConfig {
+    fn default() -> Self {
+        FlipperConfig {
+            bind: ([0, 0, 0, 0], 3434).into(),
+        }
+    }
+}
+
+impl Default for LocationConfig {
+    fn default() -> Self {
+        LocationConfig {
+            latitude: None,
+            longitude: None,
+            time_zone: Tz::UTC,
+        }
+    }
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        RemoteConfig {
+            rf_pin: 17,
+        }
+    }
+}
+
+fn parse_override<F, T>(lookup: &F, key: &str) -> Result<Option<T>, Error>
+where F: Fn(&str) -> Option<String>,
+      T: FromStr,
+      T::Err: Display {
+    match lookup(key) {
+        Some(value) => value.parse()
+            .map(Some)
+            .map_err(|e| Error::Other(format!("Invalid value for {}: {}", key, e))),
+        None => Ok(None),
+    }
+}
+
+fn deserialize_time_zone<'de, D>(d: D) -> Result<Tz, D::Error>
+where D: Deserializer<'de> {
+    let name = String::deserialize(d)?;
+    name.parse()
+        .map_err(|e| DeError::custom(format!("Invalid time zone {}: {}", name, e)))
+}
+
+/// `HH:MM` or `HH:MM:SS`
+fn deserialize_time<'de, D>(d: D) -> Result<NaiveTime, D::Error>
+where D: Deserializer<'de> {
+    let s = String::deserialize(d)?;
+    NaiveTime::parse_from_str(&s, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(&s, "%H:%M:%S"))
+        .map_err(|e| DeError::custom(format!("Invalid time {}: {}", s, e)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../../../robohome.example.toml");
+
+    const EVERYTHING: &[Requirement] = &[Requirement::Database, Requirement::Amqp];
+
+    #[test]
+    fn example_is_valid() {
+        let config: Config = EXAMPLE.parse().unwrap();
+        config.validate_for(EVERYTHING).unwrap();
+        assert_eq!(config.location.time_zone, Tz::America__Chicago);
+        assert!(config.coordinates().is_ok());
+    }
+
+    #[test]
+    fn defaults_need_a_database() {
+        let mut config: Config = "".parse().unwrap();
+        assert!(config.validate_for(EVERYTHING).is_err());
+        assert!(config.validate_for(&[Requirement::Amqp]).is_ok());
+        config.amqp.url = String::new();
+        assert!(config.validate_for(&[Requirement::Amqp]).is_err());
+        assert_eq!(config.flipper.bind, ([0, 0, 0, 0], 3434).into());
+        assert!(config.coordinates().is_err());
+    }
+
+    #[test]
+    fn env_overrides() {
+        let mut config: Config = EXAMPLE.parse().unwrap();
+        config.apply_overrides(|key| match key {
+            "ROBOHOME_DATABASE_URL" => Some("sqlite:///var/lib/robohome.db".to_string()),
+            "ROBOHOME_TIME_ZONE" => Some("Europe/Berlin".to_string()),
+            "ROBOHOME_RF_PIN" => Some("27".to_string()),
+            _ => None,
+        }).unwrap();
+        assert_eq!(config.database.url, "sqlite:///var/lib/robohome.db");
+        assert_eq!(config.location.time_zone, Tz::Europe__Berlin);
+        assert_eq!(config.remote.rf_pin, 27);
+        let bad = config.apply_overrides(|key| match key {
+            "ROBOHOME_LATITUDE" => Some("north".to_string()),
+            _ => None,
+        });
+        assert!(bad.is_err());
+    }
+
+    #[test]
+    fn sun_providers() {
+        let config: Config = "".parse().unwrap();
+        assert_eq!(config.refresher.provider(), SunProvider::Calculate);
+        let config: Config = "[refresher]\napi_url = \"http://localhost/astronomy.json\"".parse().unwrap();
+        assert_eq!(config.refresher.provider(), SunProvider::Wunderground);
+        let mut config: Config = "[database]\nurl = \"memory://\"\n[refresher]\nprovider = \"sunrise_sunset\"".parse().unwrap();
+        assert_eq!(config.refresher.provider(), SunProvider::SunriseSunset);
+        assert!(config.validate().is_ok());
+        config.apply_overrides(|key| match key {
+            "ROBOHOME_SUN_PROVIDER" => Some("wunderground".to_string()),
+            _ => None,
+        }).unwrap();
+        assert_eq!(config.refresher.provider(), SunProvider::Wunderground);
+        assert!(config.validate().is_err());
+        // the days after today are calculated
+        config.refresher.api_url = Some("http://localhost/astronomy.json".to_string());
+        assert!(config.validate().is_err());
+        config.refresher.days_ahead = 0;
+        assert!(config.validate().is_ok());
+        assert!("[refresher]\nprovider = \"almanac\"".parse::<Config>().is_err());
+        assert_eq!("sunrise_sunset".parse::<SunProvider>().unwrap().as_str(), "sunrise_sunset");
+        assert_eq!(config.refresher.retries, 3);
+    }
+
+    #[test]
+    fn refresher_daemon() {
+        let config: Config = "".parse().unwrap();
+        assert!(!config.refresher.daemon);
+        assert_eq!(config.refresher.refresh_at, NaiveTime::from_hms(2, 0, 0));
+        let mut config: Config = "[database]\nurl = \"memory://\"\n[refresher]\nrefresh_at = \"03:30\"\ndays_ahead = 5".parse().unwrap();
+        assert_eq!(config.refresher.refresh_at, NaiveTime::from_hms(3, 30, 0));
+        assert_eq!(config.refresher.days_ahead, 5);
+        config.apply_overrides(|key| match key {
+            "ROBOHOME_REFRESHER_DAEMON" => Some("true".to_string()),
+            _ => None,
+        }).unwrap();
+        assert!(config.refresher.daemon);
+        assert!(config.validate().is_ok());
+        config.refresher.days_ahead = 31;
+        assert!(config.validate().is_err());
+        assert!("[refresher]\nrefresh_at = \"25:00\"".parse::<Config>().is_err());
+    }
+
+    #[test]
+    fn weather() {
+        let mut config: Config = "[database]\nurl = \"memory://\"".parse().unwrap();
+        assert_eq!(config.refresher.weather_url, None);
+        assert_eq!(config.refresher.cloud_adjustment, 30);
+        config.apply_overrides(|key| match key {
+            "ROBOHOME_WEATHER_URL" => Some("https://api.open-meteo.com/v1/forecast".to_string()),
+            _ => None,
+        }).unwrap();
+        assert_eq!(config.refresher.weather_url.as_ref().map(String::as_str), Some("https://api.open-meteo.com/v1/forecast"));
+        config.refresher.cloud_adjustment = -10;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn catch_up() {
+        let mut config: Config = "[database]\nurl = \"memory://\"\n[schedule]\ncatch_up = \"grace\"\ngrace = 30".parse().unwrap();
+        assert_eq!(config.schedule.catch_up, CatchUp::Grace);
+        assert_eq!(config.schedule.grace, 30);
+        config.apply_overrides(|key| match key {
+            "ROBOHOME_CATCH_UP" => Some("skip".to_string()),
+            _ => None,
+        }).unwrap();
+        assert_eq!(config.schedule.catch_up.as_str(), "skip");
+        config.schedule.grace = -1;
+        assert!(config.validate().is_err());
+        assert_eq!("".parse::<Config>().unwrap().schedule.catch_up, CatchUp::Latest);
+        assert!("[schedule]\ncatch_up = \"all\"".parse::<Config>().is_err());
+    }
+
+    #[test]
+    fn vacation() {
+        let config: Config = "".parse().unwrap();
+        assert_eq!(config.schedule.vacation.jitter, 15);
+        assert!(config.schedule.vacation.rooms.is_empty());
+        let mut config: Config = "[database]\nurl = \"memory://\"\n[schedule.vacation]\njitter = 20\nrooms = [\"Living Room\"]\ncycles_from = \"19:30\"".parse().unwrap();
+        assert_eq!(config.schedule.vacation.jitter, 20);
+        assert_eq!(config.schedule.vacation.rooms, vec!["Living Room".to_string()]);
+        assert_eq!(config.schedule.vacation.cycles_from, NaiveTime::from_hms(19, 30, 0));
+        assert!(config.validate().is_ok());
+        config.schedule.vacation.cycles_until = NaiveTime::from_hms(19, 0, 0);
+        assert!(config.validate().is_err());
+        config.schedule.vacation.cycles_until = NaiveTime::from_hms(23, 0, 0);
+        config.schedule.vacation.jitter = -5;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert!("[location]\ntime_zone = \"Mars/Olympus\"".parse::<Config>().is_err());
+        let config: Config = "[database]\nurl = \"memory://\"\n[location]\nlatitude = 91.0".parse().unwrap();
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,1107 @@
+//! An in memory store, this mirrors the
+//! behavior of the postgres functions so
+//! it can stand in for a real database
+use chrono::{
+    DateTime,
+    NaiveDate,
+    Utc,
+};
+use uuid::Uuid;
+
+use std::sync::{
+    Mutex,
+    MutexGuard,
+};
+
+use super::{
+    DayOfTheWeek,
+    Direction,
+    Flip,
+    FlipKind,
+    Group,
+    Hold,
+    Pause,
+    PendingOff,
+    Refresh,
+    Scene,
+    SceneMember,
+    ScheduledFlip,
+    Season,
+    SpecialTime,
+    Store,
+    Switch,
+    Vacation,
+};
+use Error;
+
+#[derive(Default)]
+pub struct MemoryStore {
+    tables: Mutex<Tables>,
+}
+
+#[derive(Default)]
+struct Tables {
+    switches: Vec<Switch>,
+    flips: Vec<FlipRow>,
+    groups: Vec<GroupRow>,
+    members: Vec<MemberRow>,
+    scenes: Vec<SceneRow>,
+    scene_members: Vec<SceneMemberRow>,
+    authorizations: Vec<AuthRow>,
+    tokens: Vec<TokenRow>,
+    special_times: Vec<SpecialTime>,
+    refreshes: Vec<Refresh>,
+    last_processed: Option<DateTime<Utc>>,
+    vacation: bool,
+    pending_offs: Vec<PendingOff>,
+    pause: Pause,
+    holds: Vec<Hold>,
+    next_id: i32,
+}
+
+/// Exactly one of `switch_id`, `group_id`
+/// or `scene_id` is set
+struct FlipRow {
+    switch_id: Option<i32>,
+    group_id: Option<i32>,
+    scene_id: Option<i32>,
+    flip: ScheduledFlip,
+}
+
+struct GroupRow {
+    id: i32,
+    name: String,
+}
+
+struct MemberRow {
+    group_id: i32,
+    switch_id: i32,
+}
+
+struct SceneRow {
+    id: i32,
+    name: String,
+}
+
+struct SceneMemberRow {
+    scene_id: i32,
+    switch_id: i32,
+    direction: Direction,
+}
+
+struct AuthRow {
+    id: i32,
+    token: Uuid,
+    issued: DateTime<Utc>,
+}
+
+struct TokenRow {
+    auth_id: i32,
+    private: Vec<u8>,
+    shared: Vec<u8>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    fn tables(&self) -> Result<MutexGuard<'_, Tables>, Error> {
+        self.tables.lock().map_err(|_| Error::new("memory store lock poisoned"))
+    }
+}
+
+impl Tables {
+    fn next_id(&mut self) -> i32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn day_flips<F>(&self, date: NaiveDate, filter: F) -> Vec<Flip>
+    where F: Fn(&Flip) -> bool {
+        let special_times = self.special_times_for(date);
+        self.flips.iter()
+            .filter(|row| row.flip.fires_on(date))
+            .flat_map(|row| {
+                let (hour, minute) = row.flip.time_with(&special_times);
+                let duration = row.flip.duration;
+                self.targets(row).into_iter().map(move |(s, direction)| Flip {
+                    hour,
+                    minute,
+                    code: s.code(direction),
+                    duration: match direction {
+                        Direction::On => duration,
+                        Direction::Off => None,
+                    },
+                })
+            })
+            .filter(|f| filter(f))
+            .collect()
+    }
+
+    /// The special time nearest to `date` for each
+    /// kind, the later date wins a tie
+    fn special_times_for(&self, date: NaiveDate) -> Vec<SpecialTime> {
+        let mut ret: Vec<SpecialTime> = Vec::new();
+        for st in self.special_times.iter() {
+            let distance = (st.date - date).num_days().abs();
+            match ret.iter().position(|r| r.kind == st.kind) {
+                Some(idx) => {
+                    let current = (ret[idx].date - date).num_days().abs();
+                    if distance < current || (distance == current && st.date > ret[idx].date) {
+                        ret[idx] = st.clone();
+                    }
+                },
+                None => ret.push(st.clone()),
+            }
+        }
+        ret
+    }
+
+    /// The switches a flip applies to, paired with
+    /// the direction each one should be moved
+    fn targets(&self, row: &FlipRow) -> Vec<(&Switch, Direction)> {
+        let direction = row.flip.direction;
+        if let Some(id) = row.switch_id {
+            self.switches.iter()
+                .filter(|s| s.id == id)
+                .map(|s| (s, direction))
+                .collect()
+        } else if let Some(id) = row.group_id {
+            self.members_of(id).into_iter()
+                .map(|s| (s, direction))
+                .collect()
+        } else if let Some(id) = row.scene_id {
+            self.scene_members_of(id).into_iter()
+                .map(|(s, member)| match direction {
+                    Direction::On => (s, member),
+                    Direction::Off => (s, Direction::Off),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn scene_members_of(&self, scene_id: i32) -> Vec<(&Switch, Direction)> {
+        self.switches.iter()
+            .filter_map(|s| {
+                self.scene_members.iter()
+                    .find(|m| m.scene_id == scene_id && m.switch_id == s.id)
+                    .map(|m| (s, m.direction))
+            })
+            .collect()
+    }
+
+    fn scene(&self, row: &SceneRow) -> Scene {
+        Scene {
+            id: row.id,
+            name: row.name.clone(),
+            members: self.scene_members_of(row.id).into_iter()
+                .map(|(s, direction)| SceneMember {
+                    switch: s.clone(),
+                    direction,
+                })
+                .collect(),
+        }
+    }
+
+    fn members_of(&self, group_id: i32) -> Vec<&Switch> {
+        self.switches.iter()
+            .filter(|s| self.members.iter().any(|m| m.group_id == group_id && m.switch_id == s.id))
+            .collect()
+    }
+
+    fn group(&self, row: &GroupRow) -> Group {
+        Group {
+            id: row.id,
+            name: row.name.clone(),
+            switches: self.members_of(row.id).into_iter().cloned().collect(),
+        }
+    }
+
+    fn scheduled<F>(&self, filter: F) -> Vec<ScheduledFlip>
+    where F: Fn(&FlipRow) -> bool {
+        let mut ret: Vec<ScheduledFlip> = self.flips.iter()
+            .filter(|row| filter(row))
+            .map(|row| row.flip.clone())
+            .collect();
+        ret.sort_by_key(|f| (f.hour, f.minute));
+        ret
+    }
+
+    fn auth_for(&self, token: &Uuid) -> Option<&AuthRow> {
+        self.authorizations.iter().find(|a| &a.token == token)
+    }
+
+    fn token_for(&self, token: &Uuid) -> Option<&TokenRow> {
+        let auth = self.auth_for(token)?;
+        self.tokens.iter().find(|t| t.auth_id == auth.id)
+    }
+}
+
+impl Store for MemoryStore {
+    fn migrate(&self) -> Result<Vec<i32>, Error> {
+        Ok(Vec::new())
+    }
+
+    // **********
+    // CREATE
+    // **********
+    fn new_switch(&self, name: &str, on_code: i32, off_code: i32) -> Result<Switch, Error> {
+        let mut t = self.tables()?;
+        let ret = Switch {
+            id: t.next_id(),
+            name: name.to_string(),
+            on_code,
+            off_code,
+        };
+        t.switches.push(ret.clone());
+        Ok(ret)
+    }
+
+    fn new_scheduled_flip(&self, sw_id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind, offset: i32, duration: Option<i32>, season: Option<Season>) -> Result<ScheduledFlip, Error> {
+        let mut t = self.tables()?;
+        if !t.switches.iter().any(|s| s.id == sw_id) {
+            return Err(Error::Other(format!("switch {} does not exist", sw_id)));
+        }
+        let flip = ScheduledFlip {
+            id: t.next_id(),
+            hour,
+            minute,
+            dow,
+            direction,
+            kind,
+            offset,
+            date: None,
+            season,
+            duration,
+        };
+        t.flips.push(FlipRow {
+            switch_id: Some(sw_id),
+            group_id: None,
+            scene_id: None,
+            flip: flip.clone(),
+        });
+        Ok(flip)
+    }
+
+    fn new_dated_flip(&self, sw_id: i32, date: NaiveDate, hour: i32, minute: i32, direction: Direction, kind: FlipKind) -> Result<ScheduledFlip, Error> {
+        let mut t = self.tables()?;
+        if !t.switches.iter().any(|s| s.id == sw_id) {
+            return Err(Error::Other(format!("switch {} does not exist", sw_id)));
+        }
+        let flip = ScheduledFlip {
+            id: t.next_id(),
+            hour,
+            minute,
+            dow: 0.into(),
+            direction,
+            kind,
+            offset: 0,
+            date: Some(date),
+            season: None,
+            duration: None,
+        };
+        t.flips.push(FlipRow {
+            switch_id: Some(sw_id),
+            group_id: None,
+            scene_id: None,
+            flip: flip.clone(),
+        });
+        Ok(flip)
+    }
+
+    fn new_group(&self, name: &str) -> Result<Group, Error> {
+        let mut t = self.tables()?;
+        let id = t.next_id();
+        t.groups.push(GroupRow {
+            id,
+            name: name.to_string(),
+        });
+        Ok(Group {
+            id,
+            name: name.to_string(),
+            switches: Vec::new(),
+        })
+    }
+
+    fn add_group_member(&self, group_id: i32, switch_id: i32) -> Result<(), Error> {
+        let mut t = self.tables()?;
+        if !t.groups.iter().any(|g| g.id == group_id) {
+            return Err(Error::Other(format!("group {} does not exist", group_id)));
+        }
+        if !t.switches.iter().any(|s| s.id == switch_id) {
+            return Err(Error::Other(format!("switch {} does not exist", switch_id)));
+        }
+        if !t.members.iter().any(|m| m.group_id == group_id && m.switch_id == switch_id) {
+            t.members.push(MemberRow {
+                group_id,
+                switch_id,
+            });
+        }
+        Ok(())
+    }
+
+    fn new_group_flip(&self, group_id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind) -> Result<ScheduledFlip, Error> {
+        let mut t = self.tables()?;
+        if !t.groups.iter().any(|g| g.id == group_id) {
+            return Err(Error::Other(format!("group {} does not exist", group_id)));
+        }
+        let flip = ScheduledFlip {
+            id: t.next_id(),
+            hour,
+            minute,
+            dow,
+            direction,
+            kind,
+            offset: 0,
+            date: None,
+            season: None,
+            duration: None,
+        };
+        t.flips.push(FlipRow {
+            switch_id: None,
+            group_id: Some(group_id),
+            scene_id: None,
+            flip: flip.clone(),
+        });
+        Ok(flip)
+    }
+
+    fn new_scene(&self, name: &str) -> Result<Scene, Error> {
+        let mut t = self.tables()?;
+        let id = t.next_id();
+        t.scenes.push(SceneRow {
+            id,
+            name: name.to_string(),
+        });
+        Ok(Scene {
+            id,
+            name: name.to_string(),
+            members: Vec::new(),
+        })
+    }
+
+    fn set_scene_member(&self, scene_id: i32, switch_id: i32, direction: Direction) -> Result<(), Error> {
+        let mut t = self.tables()?;
+        if !t.scenes.iter().any(|s| s.id == scene_id) {
+            return Err(Error::Other(format!("scene {} does not exist", scene_id)));
+        }
+        if !t.switches.iter().any(|s| s.id == switch_id) {
+            return Err(Error::Other(format!("switch {} does not exist", switch_id)));
+        }
+        if let Some(member) = t.scene_members.iter_mut().find(|m| m.scene_id == scene_id && m.switch_id == switch_id) {
+            member.direction = direction;
+            return Ok(());
+        }
+        t.scene_members.push(SceneMemberRow {
+            scene_id,
+            switch_id,
+            direction,
+        });
+        Ok(())
+    }
+
+    fn new_scene_flip(&self, scene_id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind) -> Result<ScheduledFlip, Error> {
+        let mut t = self.tables()?;
+        if !t.scenes.iter().any(|s| s.id == scene_id) {
+            return Err(Error::Other(format!("scene {} does not exist", scene_id)));
+        }
+        let flip = ScheduledFlip {
+            id: t.next_id(),
+            hour,
+            minute,
+            dow,
+            direction,
+            kind,
+            offset: 0,
+            date: None,
+            season: None,
+            duration: None,
+        };
+        t.flips.push(FlipRow {
+            switch_id: None,
+            group_id: None,
+            scene_id: Some(scene_id),
+            flip: flip.clone(),
+        });
+        Ok(flip)
+    }
+
+    fn new_auth(&self) -> Result<Uuid, Error> {
+        let mut t = self.tables()?;
+        let id = t.next_id();
+        let token = Uuid::new_v4();
+        t.authorizations.push(AuthRow {
+            id,
+            token,
+            issued: Utc::now(),
+        });
+        Ok(token)
+    }
+
+    fn new_token(&self, private: &[u8], shared: &[u8], token: Uuid) -> Result<(), Error> {
+        let mut t = self.tables()?;
+        let auth_id = t.auth_for(&token)
+            .map(|a| a.id)
+            .ok_or(Error::new("Token not found"))?;
+        t.tokens.push(TokenRow {
+            auth_id,
+            private: private.to_vec(),
+            shared: shared.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn new_refresh(&self, date: NaiveDate, source: &str, succeeded: bool, attempts: i32, message: Option<&str>) -> Result<Refresh, Error> {
+        let mut t = self.tables()?;
+        let refresh = Refresh {
+            id: t.next_id(),
+            refreshed: Utc::now(),
+            date,
+            source: source.to_string(),
+            succeeded,
+            attempts,
+            message: message.map(str::to_string),
+        };
+        t.refreshes.push(refresh.clone());
+        Ok(refresh)
+    }
+
+    fn new_pending_off(&self, code: i32, due: DateTime<Utc>) -> Result<PendingOff, Error> {
+        let mut t = self.tables()?;
+        let off = PendingOff {
+            id: t.next_id(),
+            code,
+            due,
+        };
+        t.pending_offs.push(off.clone());
+        Ok(off)
+    }
+
+    fn new_hold(&self, switch_id: i32, until: Option<DateTime<Utc>>) -> Result<Hold, Error> {
+        let mut t = self.tables()?;
+        if !t.switches.iter().any(|s| s.id == switch_id) {
+            return Err(Error::Other(format!("switch {} does not exist", switch_id)));
+        }
+        let hold = Hold {
+            switch_id,
+            until,
+        };
+        t.holds.retain(|h| h.switch_id != switch_id);
+        t.holds.push(hold);
+        Ok(hold)
+    }
+
+    // **********
+    // READ
+    // **********
+    fn get_flips_for_minute(&self, hour: i32, minute: i32, date: NaiveDate) -> Result<Vec<Flip>, Error> {
+        let t = self.tables()?;
+        Ok(t.day_flips(date, |f| f.hour == hour && f.minute == minute))
+    }
+
+    fn get_flips_for_day(&self, date: NaiveDate) -> Result<Vec<Flip>, Error> {
+        let t = self.tables()?;
+        Ok(t.day_flips(date, |_| true))
+    }
+
+    fn get_special_times(&self, date: NaiveDate) -> Result<Vec<SpecialTime>, Error> {
+        let t = self.tables()?;
+        Ok(t.special_times_for(date))
+    }
+
+    fn get_latest_special_date(&self) -> Result<Option<NaiveDate>, Error> {
+        let t = self.tables()?;
+        Ok(t.special_times.iter().map(|st| st.date).max())
+    }
+
+    fn get_last_refresh(&self) -> Result<Option<Refresh>, Error> {
+        let t = self.tables()?;
+        Ok(t.refreshes.last().cloned())
+    }
+
+    fn get_last_processed(&self) -> Result<Option<DateTime<Utc>>, Error> {
+        let t = self.tables()?;
+        Ok(t.last_processed)
+    }
+
+    fn get_vacation(&self) -> Result<Vacation, Error> {
+        let t = self.tables()?;
+        Ok(Vacation {
+            enabled: t.vacation,
+        })
+    }
+
+    fn get_pending_offs(&self) -> Result<Vec<PendingOff>, Error> {
+        let t = self.tables()?;
+        let mut ret = t.pending_offs.clone();
+        ret.sort_by_key(|off| (off.due, off.id));
+        Ok(ret)
+    }
+
+    fn get_pause(&self) -> Result<Pause, Error> {
+        let t = self.tables()?;
+        Ok(t.pause)
+    }
+
+    fn get_holds(&self) -> Result<Vec<Hold>, Error> {
+        let t = self.tables()?;
+        let mut ret = t.holds.clone();
+        ret.sort_by_key(|h| h.switch_id);
+        Ok(ret)
+    }
+
+    fn get_all_switches(&self) -> Result<Vec<Switch>, Error> {
+        let t = self.tables()?;
+        Ok(t.switches.clone())
+    }
+
+    fn get_flips_for_switch(&self, switch_id: i32) -> Result<Vec<ScheduledFlip>, Error> {
+        let t = self.tables()?;
+        Ok(t.scheduled(|row| row.switch_id == Some(switch_id)))
+    }
+
+    fn get_flip(&self, id: i32) -> Result<ScheduledFlip, Error> {
+        let t = self.tables()?;
+        t.scheduled(|row| row.flip.id == id)
+            .pop()
+            .ok_or(Error::Other(format!("flip {} does not exist", id)))
+    }
+
+    fn get_all_groups(&self) -> Result<Vec<Group>, Error> {
+        let t = self.tables()?;
+        let mut ret: Vec<Group> = t.groups.iter().map(|g| t.group(g)).collect();
+        ret.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+        Ok(ret)
+    }
+
+    fn get_group(&self, group_id: i32) -> Result<Group, Error> {
+        let t = self.tables()?;
+        t.groups.iter()
+            .find(|g| g.id == group_id)
+            .map(|g| t.group(g))
+            .ok_or(Error::Other(format!("group {} does not exist", group_id)))
+    }
+
+    fn get_flips_for_group(&self, group_id: i32) -> Result<Vec<ScheduledFlip>, Error> {
+        let t = self.tables()?;
+        Ok(t.scheduled(|row| row.group_id == Some(group_id)))
+    }
+
+    fn get_all_scenes(&self) -> Result<Vec<Scene>, Error> {
+        let t = self.tables()?;
+        let mut ret: Vec<Scene> = t.scenes.iter().map(|s| t.scene(s)).collect();
+        ret.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+        Ok(ret)
+    }
+
+    fn get_scene(&self, scene_id: i32) -> Result<Scene, Error> {
+        let t = self.tables()?;
+        t.scenes.iter()
+            .find(|s| s.id == scene_id)
+            .map(|s| t.scene(s))
+            .ok_or(Error::Other(format!("scene {} does not exist", scene_id)))
+    }
+
+    fn get_flips_for_scene(&self, scene_id: i32) -> Result<Vec<ScheduledFlip>, Error> {
+        let t = self.tables()?;
+        Ok(t.scheduled(|row| row.scene_id == Some(scene_id)))
+    }
+
+    fn get_auth_age(&self, token: &Uuid) -> Result<DateTime<Utc>, Error> {
+        let t = self.tables()?;
+        let auth = t.auth_for(token).ok_or(Error::new("Token not found"))?;
+        if t.tokens.iter().any(|tk| tk.auth_id == auth.id) {
+            return Err(Error::new("Token not found"));
+        }
+        Ok(auth.issued)
+    }
+
+    fn get_private_shared(&self, token: &Uuid) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let t = self.tables()?;
+        t.token_for(token)
+            .map(|tk| (tk.private.clone(), tk.shared.clone()))
+            .ok_or(Error::new("Unauthorized token provided"))
+    }
+
+    fn check_token(&self, token: &Uuid, shared: &[u8]) -> Result<bool, Error> {
+        let t = self.tables()?;
+        Ok(t.token_for(token)
+            .map(|tk| tk.shared.as_slice() == shared)
+            .unwrap_or(false))
+    }
+
+    // **********
+    // UPDATE
+    // **********
+    fn update_switch(&self, id: i32, name: &str, on_code: i32, off_code: i32) -> Result<Switch, Error> {
+        let mut t = self.tables()?;
+        let sw = t.switches.iter_mut()
+            .find(|s| s.id == id)
+            .ok_or(Error::new("Nothing returned from switch update"))?;
+        sw.name = name.to_string();
+        sw.on_code = on_code;
+        sw.off_code = off_code;
+        Ok(sw.clone())
+    }
+
+    fn update_flip(&self, id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind, offset: i32, duration: Option<i32>, season: Option<Season>) -> Result<ScheduledFlip, Error> {
+        let mut t = self.tables()?;
+        let row = t.flips.iter_mut()
+            .find(|row| row.flip.id == id)
+            .ok_or(Error::new("Nothing returned from flip update"))?;
+        row.flip.hour = hour;
+        row.flip.minute = minute;
+        row.flip.dow = dow;
+        row.flip.direction = direction;
+        row.flip.kind = kind;
+        row.flip.offset = offset;
+        row.flip.duration = duration;
+        row.flip.season = season;
+        Ok(row.flip.clone())
+    }
+
+    fn update_group(&self, id: i32, name: &str) -> Result<Group, Error> {
+        let mut t = self.tables()?;
+        let idx = t.groups.iter()
+            .position(|g| g.id == id)
+            .ok_or(Error::new("Nothing returned from group update"))?;
+        t.groups[idx].name = name.to_string();
+        Ok(t.group(&t.groups[idx]))
+    }
+
+    fn update_scene(&self, id: i32, name: &str) -> Result<Scene, Error> {
+        let mut t = self.tables()?;
+        let idx = t.scenes.iter()
+            .position(|s| s.id == id)
+            .ok_or(Error::new("Nothing returned from scene update"))?;
+        t.scenes[idx].name = name.to_string();
+        Ok(t.scene(&t.scenes[idx]))
+    }
+
+    fn update_special_times(&self, times: &[SpecialTime]) -> Result<i32, Error> {
+        let mut t = self.tables()?;
+        for time in times {
+            t.special_times.retain(|st| st.date != time.date || st.kind != time.kind);
+            t.special_times.push(time.clone());
+        }
+        Ok(times.len() as i32)
+    }
+
+    fn update_last_processed(&self, at: DateTime<Utc>) -> Result<(), Error> {
+        let mut t = self.tables()?;
+        t.last_processed = Some(at);
+        Ok(())
+    }
+
+    fn update_vacation(&self, vacation: Vacation) -> Result<(), Error> {
+        let mut t = self.tables()?;
+        t.vacation = vacation.enabled;
+        Ok(())
+    }
+
+    fn update_pause(&self, pause: Pause) -> Result<(), Error> {
+        let mut t = self.tables()?;
+        t.pause = pause;
+        Ok(())
+    }
+
+    // **********
+    // DELETE
+    // **********
+    fn remove_switch(&self, id: i32) -> Result<i32, Error> {
+        let mut t = self.tables()?;
+        let before = t.switches.len();
+        t.switches.retain(|s| s.id != id);
+        t.flips.retain(|row| row.switch_id != Some(id));
+        t.members.retain(|m| m.switch_id != id);
+        t.scene_members.retain(|m| m.switch_id != id);
+        t.holds.retain(|h| h.switch_id != id);
+        Ok((before - t.switches.len()) as i32)
+    }
+
+    fn remove_flip(&self, id: i32) -> Result<i32, Error> {
+        let mut t = self.tables()?;
+        let before = t.flips.len();
+        t.flips.retain(|row| row.flip.id != id);
+        Ok((before - t.flips.len()) as i32)
+    }
+
+    fn remove_group(&self, id: i32) -> Result<i32, Error> {
+        let mut t = self.tables()?;
+        let before = t.groups.len();
+        t.groups.retain(|g| g.id != id);
+        t.flips.retain(|row| row.group_id != Some(id));
+        t.members.retain(|m| m.group_id != id);
+        Ok((before - t.groups.len()) as i32)
+    }
+
+    fn remove_group_member(&self, group_id: i32, switch_id: i32) -> Result<i32, Error> {
+        let mut t = self.tables()?;
+        let before = t.members.len();
+        t.members.retain(|m| !(m.group_id == group_id && m.switch_id == switch_id));
+        Ok((before - t.members.len()) as i32)
+    }
+
+    fn remove_expired_flips(&self, date: NaiveDate) -> Result<i32, Error> {
+        let mut t = self.tables()?;
+        let before = t.flips.len();
+        t.flips.retain(|row| row.flip.date.map(|d| d >= date).unwrap_or(true));
+        Ok((before - t.flips.len()) as i32)
+    }
+
+    fn remove_scene(&self, id: i32) -> Result<i32, Error> {
+        let mut t = self.tables()?;
+        let before = t.scenes.len();
+        t.scenes.retain(|s| s.id != id);
+        t.flips.retain(|row| row.scene_id != Some(id));
+        t.scene_members.retain(|m| m.scene_id != id);
+        Ok((before - t.scenes.len()) as i32)
+    }
+
+    fn remove_scene_member(&self, scene_id: i32, switch_id: i32) -> Result<i32, Error> {
+        let mut t = self.tables()?;
+        let before = t.scene_members.len();
+        t.scene_members.retain(|m| !(m.scene_id == scene_id && m.switch_id == switch_id));
+        Ok((before - t.scene_members.len()) as i32)
+    }
+
+    fn remove_pending_off(&self, id: i32) -> Result<i32, Error> {
+        let mut t = self.tables()?;
+        let before = t.pending_offs.len();
+        t.pending_offs.retain(|off| off.id != id);
+        Ok((before - t.pending_offs.len()) as i32)
+    }
+
+    fn remove_hold(&self, switch_id: i32) -> Result<i32, Error> {
+        let mut t = self.tables()?;
+        let before = t.holds.len();
+        t.holds.retain(|h| h.switch_id != switch_id);
+        Ok((before - t.holds.len()) as i32)
+    }
+
+    fn remove_expired_holds(&self, through: DateTime<Utc>) -> Result<i32, Error> {
+        let mut t = self.tables()?;
+        let before = t.holds.len();
+        t.holds.retain(|h| h.until.map(|until| until > through).unwrap_or(true));
+        Ok((before - t.holds.len()) as i32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use data::MonthDay;
+
+    fn monday() -> NaiveDate {
+        NaiveDate::from_ymd(2018, 10, 1)
+    }
+
+    fn sunday() -> NaiveDate {
+        NaiveDate::from_ymd(2018, 10, 7)
+    }
+
+    fn solar_day(date: NaiveDate, predawn: (i32, i32), sunrise: (i32, i32),
+                 dusk: (i32, i32), sunset: (i32, i32)) -> Vec<SpecialTime> {
+        vec![
+            (FlipKind::PreDawn, predawn),
+            (FlipKind::Sunrise, sunrise),
+            (FlipKind::Dusk, dusk),
+            (FlipKind::Sunset, sunset),
+        ].into_iter()
+            .map(|(kind, (hour, minute))| SpecialTime { date, kind, hour, minute, adjustment: 0 })
+            .collect()
+    }
+
+    #[test]
+    fn day_selection() {
+        let store = MemoryStore::new();
+        let sw = store.new_switch("living room", 1, 2).unwrap();
+        // weekdays
+        store.new_scheduled_flip(sw.id, 7, 0, 31.into(), Direction::On, FlipKind::Custom, 0, None, None).unwrap();
+        // sunday
+        store.new_scheduled_flip(sw.id, 9, 30, 64.into(), Direction::Off, FlipKind::Custom, 0, None, None).unwrap();
+        let weekday = store.get_flips_for_day(monday()).unwrap();
+        assert_eq!(weekday, vec![Flip { hour: 7, minute: 0, code: 1, duration: None }]);
+        let weekend = store.get_flips_for_day(sunday()).unwrap();
+        assert_eq!(weekend, vec![Flip { hour: 9, minute: 30, code: 2, duration: None }]);
+        assert!(store.get_flips_for_minute(9, 30, monday()).unwrap().is_empty());
+        assert_eq!(store.get_flips_for_minute(9, 30, sunday()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn remove_cascades() {
+        let store = MemoryStore::new();
+        let sw = store.new_switch("tree", 1, 2).unwrap();
+        let other = store.new_switch("porch", 3, 4).unwrap();
+        store.new_scheduled_flip(sw.id, 7, 0, 127.into(), Direction::On, FlipKind::Custom, 0, None, None).unwrap();
+        store.new_scheduled_flip(other.id, 8, 0, 127.into(), Direction::On, FlipKind::Custom, 0, None, None).unwrap();
+        assert_eq!(store.remove_switch(sw.id).unwrap(), 1);
+        assert!(store.get_flips_for_switch(sw.id).unwrap().is_empty());
+        assert_eq!(store.get_flips_for_day(monday()).unwrap(), vec![Flip { hour: 8, minute: 0, code: 3, duration: None }]);
+        assert_eq!(store.remove_switch(sw.id).unwrap(), 0);
+    }
+
+    #[test]
+    fn dated_flips() {
+        let store = MemoryStore::new();
+        let porch = store.new_switch("porch", 1, 2).unwrap();
+        let christmas_eve = NaiveDate::from_ymd(2026, 12, 24);
+        let flip = store.new_dated_flip(porch.id, christmas_eve, 18, 30, Direction::On, FlipKind::Custom).unwrap();
+        store.new_scheduled_flip(porch.id, 23, 0, 127.into(), Direction::Off, FlipKind::Custom, 0, None, None).unwrap();
+        assert_eq!(store.get_flips_for_day(christmas_eve).unwrap().len(), 2);
+        assert_eq!(store.get_flips_for_minute(18, 30, christmas_eve).unwrap(), vec![Flip { hour: 18, minute: 30, code: 1, duration: None }]);
+        // the same weekday a week later only has the recurring flip
+        let next_week = NaiveDate::from_ymd(2026, 12, 31);
+        assert_eq!(store.get_flips_for_day(next_week).unwrap(), vec![Flip { hour: 23, minute: 0, code: 2, duration: None }]);
+        assert_eq!(store.get_flips_for_switch(porch.id).unwrap()[0], flip);
+        assert_eq!(store.remove_expired_flips(christmas_eve).unwrap(), 0);
+        assert_eq!(store.remove_expired_flips(christmas_eve.succ()).unwrap(), 1);
+        assert_eq!(store.get_flips_for_switch(porch.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn seasonal_flips() {
+        let store = MemoryStore::new();
+        let tree = store.new_switch("X-mas Tree", 1, 2).unwrap();
+        let christmas = Season {
+            start: MonthDay { month: 11, day: 25 },
+            end: MonthDay { month: 1, day: 6 },
+        };
+        let flip = store.new_scheduled_flip(tree.id, 18, 0, 127.into(), Direction::On, FlipKind::Custom, 0, None, Some(christmas)).unwrap();
+        assert_eq!(store.get_flips_for_day(NaiveDate::from_ymd(2026, 12, 24)).unwrap().len(), 1);
+        assert_eq!(store.get_flips_for_day(NaiveDate::from_ymd(2027, 1, 6)).unwrap().len(), 1);
+        assert!(store.get_flips_for_day(NaiveDate::from_ymd(2027, 1, 7)).unwrap().is_empty());
+        assert!(store.get_flips_for_day(NaiveDate::from_ymd(2026, 7, 4)).unwrap().is_empty());
+        let updated = store.update_flip(flip.id, 18, 0, 127.into(), Direction::On, FlipKind::Custom, 0, None, None).unwrap();
+        assert_eq!(updated.season, None);
+        assert_eq!(store.get_flips_for_day(NaiveDate::from_ymd(2026, 7, 4)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn group_flips_expand() {
+        let store = MemoryStore::new();
+        let lamp = store.new_switch("lamp", 1, 2).unwrap();
+        let tree = store.new_switch("tree", 3, 4).unwrap();
+        let porch = store.new_switch("porch", 5, 6).unwrap();
+        let living_room = store.new_group("living room").unwrap();
+        store.add_group_member(living_room.id, lamp.id).unwrap();
+        store.add_group_member(living_room.id, tree.id).unwrap();
+        store.add_group_member(living_room.id, tree.id).unwrap();
+        assert!(store.add_group_member(living_room.id, -1).is_err());
+        let flip = store.new_group_flip(living_room.id, 18, 0, 127.into(), Direction::On, FlipKind::Custom).unwrap();
+        store.new_scheduled_flip(porch.id, 18, 0, 127.into(), Direction::On, FlipKind::Custom, 0, None, None).unwrap();
+        let mut codes: Vec<i32> = store.get_flips_for_minute(18, 0, monday()).unwrap()
+            .into_iter()
+            .map(|f| f.code)
+            .collect();
+        codes.sort();
+        assert_eq!(codes, vec![1, 3, 5]);
+        assert_eq!(store.get_flips_for_group(living_room.id).unwrap(), vec![flip]);
+        assert!(store.get_flips_for_switch(lamp.id).unwrap().is_empty());
+        let group = store.get_group(living_room.id).unwrap();
+        assert_eq!(group.codes(Direction::Off), vec![2, 4]);
+        // removing a member switch only shrinks the group
+        store.remove_switch(tree.id).unwrap();
+        assert_eq!(store.get_flips_for_day(monday()).unwrap().len(), 2);
+        assert_eq!(store.remove_group(living_room.id).unwrap(), 1);
+        assert_eq!(store.get_flips_for_day(monday()).unwrap(), vec![Flip { hour: 18, minute: 0, code: 5, duration: None }]);
+        assert!(store.get_all_groups().unwrap().is_empty());
+    }
+
+    #[test]
+    fn scene_flips() {
+        let store = MemoryStore::new();
+        let lamp = store.new_switch("lamp", 1, 2).unwrap();
+        let tree = store.new_switch("tree", 3, 4).unwrap();
+        let movie = store.new_scene("movie night").unwrap();
+        store.set_scene_member(movie.id, lamp.id, Direction::On).unwrap();
+        store.set_scene_member(movie.id, tree.id, Direction::On).unwrap();
+        store.set_scene_member(movie.id, tree.id, Direction::Off).unwrap();
+        let scene = store.get_scene(movie.id).unwrap();
+        assert_eq!(scene.codes(Direction::On), vec![1, 4]);
+        assert_eq!(scene.codes(Direction::Off), vec![2, 4]);
+        let start = store.new_scene_flip(movie.id, 20, 0, 127.into(), Direction::On, FlipKind::Custom).unwrap();
+        store.new_scene_flip(movie.id, 23, 0, 127.into(), Direction::Off, FlipKind::Custom).unwrap();
+        let codes: Vec<i32> = store.get_flips_for_minute(20, 0, monday()).unwrap().into_iter().map(|f| f.code).collect();
+        assert_eq!(codes, vec![1, 4]);
+        let codes: Vec<i32> = store.get_flips_for_minute(23, 0, monday()).unwrap().into_iter().map(|f| f.code).collect();
+        assert_eq!(codes, vec![2, 4]);
+        assert_eq!(store.get_flips_for_scene(movie.id).unwrap()[0], start);
+        assert_eq!(store.remove_scene_member(movie.id, lamp.id).unwrap(), 1);
+        assert_eq!(store.update_scene(movie.id, "late movie").unwrap().members.len(), 1);
+        assert_eq!(store.remove_scene(movie.id).unwrap(), 1);
+        assert!(store.get_flips_for_day(monday()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn special_times_move_flips() {
+        let store = MemoryStore::new();
+        let sw = store.new_switch("porch", 1, 2).unwrap();
+        let custom = store.new_scheduled_flip(sw.id, 23, 0, 127.into(), Direction::Off, FlipKind::Custom, 0, None, None).unwrap();
+        let sunset = store.new_scheduled_flip(sw.id, 0, 0, 127.into(), Direction::On, FlipKind::Sunset, 0, None, None).unwrap();
+        let before_sunset = store.new_scheduled_flip(sw.id, 0, 0, 127.into(), Direction::On, FlipKind::Sunset, -20, None, None).unwrap();
+        let after_sunrise = store.new_scheduled_flip(sw.id, 0, 0, 127.into(), Direction::Off, FlipKind::Sunrise, 45, None, None).unwrap();
+        let before_predawn = store.new_scheduled_flip(sw.id, 0, 0, 127.into(), Direction::On, FlipKind::PreDawn, -30, None, None).unwrap();
+        // without any special times solar flips keep their own time
+        assert_eq!(store.get_flips_for_minute(0, 0, monday()).unwrap().len(), 4);
+        let ct = store.update_special_times(&solar_day(monday(), (0, 10), (7, 10), (16, 45), (17, 45))).unwrap();
+        assert_eq!(ct, 4);
+        let times: Vec<(i32, i32)> = store.get_flips_for_day(monday()).unwrap()
+            .into_iter()
+            .map(|f| (f.hour, f.minute))
+            .collect();
+        assert!(times.contains(&(17, 45)));
+        assert!(times.contains(&(17, 25)));
+        assert!(times.contains(&(7, 55)));
+        // wraps back to the previous evening instead of a negative hour
+        assert!(times.contains(&(23, 40)));
+        assert!(times.contains(&(23, 0)));
+        // the flips themselves are left alone
+        for id in &[sunset.id, before_sunset.id, after_sunrise.id, before_predawn.id] {
+            let flip = store.get_flips_for_switch(sw.id).unwrap().into_iter().find(|f| f.id == *id).unwrap();
+            assert_eq!((flip.hour, flip.minute), (0, 0));
+        }
+        assert_eq!(store.get_flips_for_switch(sw.id).unwrap()[4].id, custom.id);
+    }
+
+    #[test]
+    fn nearest_special_time() {
+        let store = MemoryStore::new();
+        let sw = store.new_switch("porch", 1, 2).unwrap();
+        store.new_scheduled_flip(sw.id, 18, 0, 127.into(), Direction::On, FlipKind::Sunset, 0, None, None).unwrap();
+        let first = NaiveDate::from_ymd(2026, 10, 1);
+        let later = NaiveDate::from_ymd(2026, 10, 5);
+        store.update_special_times(&solar_day(first, (6, 0), (7, 0), (17, 30), (18, 30))).unwrap();
+        store.update_special_times(&solar_day(later, (6, 5), (7, 5), (17, 20), (18, 20))).unwrap();
+        // replacing a date doesn't add another row
+        store.update_special_times(&solar_day(later, (6, 6), (7, 6), (17, 21), (18, 21))).unwrap();
+        assert_eq!(store.get_special_times(later).unwrap().len(), 4);
+        let time = |date: NaiveDate| {
+            let flip = &store.get_flips_for_day(date).unwrap()[0];
+            (flip.hour, flip.minute)
+        };
+        assert_eq!(time(first), (18, 30));
+        assert_eq!(time(NaiveDate::from_ymd(2026, 9, 1)), (18, 30));
+        assert_eq!(time(NaiveDate::from_ymd(2026, 10, 2)), (18, 30));
+        // a tie goes to the later date
+        assert_eq!(time(NaiveDate::from_ymd(2026, 10, 3)), (18, 21));
+        assert_eq!(time(NaiveDate::from_ymd(2026, 12, 25)), (18, 21));
+    }
+
+    #[test]
+    fn twilight_kinds() {
+        let store = MemoryStore::new();
+        let sw = store.new_switch("porch", 1, 2).unwrap();
+        store.new_scheduled_flip(sw.id, 0, 0, 127.into(), Direction::On, FlipKind::CivilDusk, 0, None, None).unwrap();
+        store.new_scheduled_flip(sw.id, 0, 0, 127.into(), Direction::Off, FlipKind::AstronomicalDawn, 10, None, None).unwrap();
+        store.new_scheduled_flip(sw.id, 0, 0, 127.into(), Direction::Off, FlipKind::SolarNoon, 0, None, None).unwrap();
+        let times = vec![
+            SpecialTime { date: monday(), kind: FlipKind::CivilDusk, hour: 18, minute: 12, adjustment: 0 },
+            SpecialTime { date: monday(), kind: FlipKind::AstronomicalDawn, hour: 5, minute: 20, adjustment: 0 },
+            SpecialTime { date: monday(), kind: FlipKind::SolarNoon, hour: 12, minute: 41, adjustment: 0 },
+        ];
+        assert_eq!(store.update_special_times(&times).unwrap(), 3);
+        assert_eq!(store.get_flips_for_minute(18, 12, monday()).unwrap(), vec![Flip { hour: 18, minute: 12, code: 1, duration: None }]);
+        assert_eq!(store.get_flips_for_minute(5, 30, monday()).unwrap(), vec![Flip { hour: 5, minute: 30, code: 2, duration: None }]);
+        assert_eq!(store.get_flips_for_minute(12, 41, monday()).unwrap().len(), 1);
+        // dusk isn't civil dusk
+        assert!(store.get_flips_for_minute(0, 0, monday()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn refresh_log() {
+        let store = MemoryStore::new();
+        assert_eq!(store.get_last_refresh().unwrap(), None);
+        assert_eq!(store.get_latest_special_date().unwrap(), None);
+        store.update_special_times(&solar_day(monday(), (6, 0), (7, 0), (17, 30), (18, 30))).unwrap();
+        store.update_special_times(&solar_day(sunday(), (6, 5), (7, 5), (17, 20), (18, 20))).unwrap();
+        assert_eq!(store.get_latest_special_date().unwrap(), Some(sunday()));
+        store.new_refresh(monday(), "wunderground", true, 1, None).unwrap();
+        let failed = store.new_refresh(sunday(), "stored", false, 3, Some("Request failed")).unwrap();
+        assert_eq!(store.get_last_refresh().unwrap(), Some(failed));
+    }
+
+    #[test]
+    fn last_processed() {
+        let store = MemoryStore::new();
+        assert_eq!(store.get_last_processed().unwrap(), None);
+        let at = monday().and_hms(12, 0, 0);
+        let at = DateTime::<Utc>::from_utc(at, Utc);
+        store.update_last_processed(at).unwrap();
+        assert_eq!(store.get_last_processed().unwrap(), Some(at));
+    }
+
+    #[test]
+    fn vacation() {
+        let store = MemoryStore::new();
+        assert!(!store.get_vacation().unwrap().enabled);
+        store.update_vacation(Vacation { enabled: true }).unwrap();
+        assert!(store.get_vacation().unwrap().enabled);
+    }
+
+    #[test]
+    fn timed_flips() {
+        let store = MemoryStore::new();
+        let fan = store.new_switch("fan", 1, 2).unwrap();
+        let on = store.new_scheduled_flip(fan.id, 7, 0, 127.into(), Direction::On, FlipKind::Custom, 0, None, None).unwrap();
+        let off = store.new_scheduled_flip(fan.id, 9, 0, 127.into(), Direction::Off, FlipKind::Custom, 0, None, None).unwrap();
+        store.update_flip(on.id, 7, 0, 127.into(), Direction::On, FlipKind::Custom, 0, Some(30), None).unwrap();
+        store.update_flip(off.id, 9, 0, 127.into(), Direction::Off, FlipKind::Custom, 0, Some(30), None).unwrap();
+        assert_eq!(store.get_flips_for_switch(fan.id).unwrap()[0].duration, Some(30));
+        // only the codes that turn a switch on are timed
+        assert_eq!(store.get_flips_for_day(monday()).unwrap(), vec![
+            Flip { hour: 7, minute: 0, code: 1, duration: Some(30) },
+            Flip { hour: 9, minute: 0, code: 2, duration: None },
+        ]);
+        let later = store.new_pending_off(2, Utc.ymd(2018, 10, 1).and_hms(12, 30, 0)).unwrap();
+        let sooner = store.new_pending_off(4, Utc.ymd(2018, 10, 1).and_hms(12, 0, 0)).unwrap();
+        assert_eq!(store.get_pending_offs().unwrap(), vec![sooner.clone(), later.clone()]);
+        assert_eq!(store.remove_pending_off(sooner.id).unwrap(), 1);
+        assert_eq!(store.remove_pending_off(sooner.id).unwrap(), 0);
+        assert_eq!(store.get_pending_offs().unwrap(), vec![later]);
+    }
+
+    #[test]
+    fn pause_and_holds() {
+        let store = MemoryStore::new();
+        assert_eq!(store.get_pause().unwrap(), Pause::default());
+        let resume_at = Utc.ymd(2018, 10, 2).and_hms(12, 0, 0);
+        let pause = Pause {
+            paused: true,
+            resume_at: Some(resume_at),
+        };
+        store.update_pause(pause).unwrap();
+        assert_eq!(store.get_pause().unwrap(), pause);
+        let lamp = store.new_switch("lamp", 1, 2).unwrap();
+        let fan = store.new_switch("fan", 3, 4).unwrap();
+        assert!(store.new_hold(-1, None).is_err());
+        store.new_hold(fan.id, Some(resume_at)).unwrap();
+        store.new_hold(lamp.id, Some(resume_at)).unwrap();
+        // holding again replaces the old hold
+        store.new_hold(lamp.id, None).unwrap();
+        assert_eq!(store.get_holds().unwrap(), vec![
+            Hold { switch_id: lamp.id, until: None },
+            Hold { switch_id: fan.id, until: Some(resume_at) },
+        ]);
+        assert_eq!(store.remove_expired_holds(resume_at).unwrap(), 1);
+        assert_eq!(store.remove_hold(lamp.id).unwrap(), 1);
+        assert!(store.get_holds().unwrap().is_empty());
+    }
+
+    #[test]
+    fn auth_exchange() {
+        let store = MemoryStore::new();
+        let auth = store.new_auth().unwrap();
+        assert!(store.get_auth_age(&auth).is_ok());
+        assert!(store.get_private_shared(&auth).is_err());
+        store.new_token(&[1, 2, 3], &[4, 5, 6], auth).unwrap();
+        assert!(store.get_auth_age(&auth).is_err());
+        assert_eq!(store.get_private_shared(&auth).unwrap(), (vec![1, 2, 3], vec![4, 5, 6]));
+        assert!(store.check_token(&auth, &[4, 5, 6]).unwrap());
+        assert!(!store.check_token(&auth, &[7]).unwrap());
+    }
+}
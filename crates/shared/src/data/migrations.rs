@@ -0,0 +1,279 @@
+//! Schema migrations, embedded so that each
+//! binary can bring its database up to date
+//! on boot. The applied versions are recorded
+//! in the `schema_version` table
+
+/// A single step forward for the schema
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub up: &'static str,
+    /// Postgres migrations that can't run inside a
+    /// transaction hold a single statement, so a
+    /// failure can't leave one half applied
+    pub transaction: bool,
+}
+
+/// A database that has the initial schema
+/// but no `schema_version` table was set up
+/// by hand before migrations were embedded
+pub const BASELINE: i32 = 1;
+
+/// The key for the advisory lock held while
+/// migrating postgres, "robohome" in ASCII
+pub const POSTGRES_LOCK: i64 = 0x726f_626f_686f_6d65;
+
+pub const POSTGRES_VERSION_TABLE: &str = "CREATE TABLE IF NOT EXISTS public.schema_version (
+    version INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    applied TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    CONSTRAINT schema_version_pkey PRIMARY KEY (version)
+);";
+
+pub const POSTGRES: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        up: include_str!("../../../../migrations/postgres/001_initial.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 2,
+        name: "groups",
+        up: include_str!("../../../../migrations/postgres/002_groups.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 3,
+        name: "scenes",
+        up: include_str!("../../../../migrations/postgres/003_scenes.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 4,
+        name: "dated_flips",
+        up: include_str!("../../../../migrations/postgres/004_dated_flips.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 5,
+        name: "seasons",
+        up: include_str!("../../../../migrations/postgres/005_seasons.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 6,
+        name: "solar_offsets",
+        up: include_str!("../../../../migrations/postgres/006_solar_offsets.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 7,
+        name: "special_time_dates",
+        up: include_str!("../../../../migrations/postgres/007_special_time_dates.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 8,
+        name: "civil_dawn_kind",
+        up: include_str!("../../../../migrations/postgres/008_civil_dawn_kind.sql"),
+        transaction: false,
+    },
+    Migration {
+        version: 9,
+        name: "civil_dusk_kind",
+        up: include_str!("../../../../migrations/postgres/009_civil_dusk_kind.sql"),
+        transaction: false,
+    },
+    Migration {
+        version: 10,
+        name: "nautical_dawn_kind",
+        up: include_str!("../../../../migrations/postgres/010_nautical_dawn_kind.sql"),
+        transaction: false,
+    },
+    Migration {
+        version: 11,
+        name: "nautical_dusk_kind",
+        up: include_str!("../../../../migrations/postgres/011_nautical_dusk_kind.sql"),
+        transaction: false,
+    },
+    Migration {
+        version: 12,
+        name: "astronomical_dawn_kind",
+        up: include_str!("../../../../migrations/postgres/012_astronomical_dawn_kind.sql"),
+        transaction: false,
+    },
+    Migration {
+        version: 13,
+        name: "astronomical_dusk_kind",
+        up: include_str!("../../../../migrations/postgres/013_astronomical_dusk_kind.sql"),
+        transaction: false,
+    },
+    Migration {
+        version: 14,
+        name: "solar_noon_kind",
+        up: include_str!("../../../../migrations/postgres/014_solar_noon_kind.sql"),
+        transaction: false,
+    },
+    Migration {
+        version: 15,
+        name: "twilight_special_times",
+        up: include_str!("../../../../migrations/postgres/015_twilight_special_times.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 16,
+        name: "refresh_log",
+        up: include_str!("../../../../migrations/postgres/016_refresh_log.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 17,
+        name: "weather_adjustment",
+        up: include_str!("../../../../migrations/postgres/017_weather_adjustment.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 18,
+        name: "schedule_state",
+        up: include_str!("../../../../migrations/postgres/018_schedule_state.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 19,
+        name: "vacation",
+        up: include_str!("../../../../migrations/postgres/019_vacation.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 20,
+        name: "timed_flips",
+        up: include_str!("../../../../migrations/postgres/020_timed_flips.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 21,
+        name: "pause",
+        up: include_str!("../../../../migrations/postgres/021_pause.sql"),
+        transaction: true,
+    },
+];
+
+pub const SQLITE_VERSION_TABLE: &str = "CREATE TABLE IF NOT EXISTS schema_version (
+    version INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    applied TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);";
+
+pub const SQLITE: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        up: include_str!("../../../../migrations/sqlite/001_initial.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 2,
+        name: "groups",
+        up: include_str!("../../../../migrations/sqlite/002_groups.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 3,
+        name: "scenes",
+        up: include_str!("../../../../migrations/sqlite/003_scenes.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 4,
+        name: "dated_flips",
+        up: include_str!("../../../../migrations/sqlite/004_dated_flips.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 5,
+        name: "seasons",
+        up: include_str!("../../../../migrations/sqlite/005_seasons.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 6,
+        name: "solar_offsets",
+        up: include_str!("../../../../migrations/sqlite/006_solar_offsets.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 7,
+        name: "special_time_dates",
+        up: include_str!("../../../../migrations/sqlite/007_special_time_dates.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 8,
+        name: "twilight_kinds",
+        up: include_str!("../../../../migrations/sqlite/008_twilight_kinds.sql"),
+        transaction: true,
+    },
+    // postgres 9 to 14 add the rest of the twilight
+    // kinds and 15 only replaces a function, versions
+    // are kept in step with the postgres list
+    Migration {
+        version: 16,
+        name: "refresh_log",
+        up: include_str!("../../../../migrations/sqlite/016_refresh_log.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 17,
+        name: "weather_adjustment",
+        up: include_str!("../../../../migrations/sqlite/017_weather_adjustment.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 18,
+        name: "schedule_state",
+        up: include_str!("../../../../migrations/sqlite/018_schedule_state.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 19,
+        name: "vacation",
+        up: include_str!("../../../../migrations/sqlite/019_vacation.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 20,
+        name: "timed_flips",
+        up: include_str!("../../../../migrations/sqlite/020_timed_flips.sql"),
+        transaction: true,
+    },
+    Migration {
+        version: 21,
+        name: "pause",
+        up: include_str!("../../../../migrations/sqlite/021_pause.sql"),
+        transaction: true,
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The non-empty statements in `m`, a statement
+    /// can't hold a `;` of its own
+    fn statements(m: &Migration) -> Vec<&'static str> {
+        m.up.split(';')
+            .map(str::trim)
+            .filter(|s| s.lines().any(|l| !l.trim().is_empty() && !l.trim().starts_with("--")))
+            .collect()
+    }
+
+    #[test]
+    fn single_statement_outside_transactions() {
+        for m in POSTGRES.iter().filter(|m| !m.transaction) {
+            assert_eq!(statements(m).len(), 1, "{} runs outside a transaction", m.name);
+        }
+        // sqlite applies every migration in one transaction
+        assert!(SQLITE.iter().all(|m| m.transaction));
+    }
+}
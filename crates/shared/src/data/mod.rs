@@ -0,0 +1,1004 @@
+use super::Error;
+use config::DatabaseConfig;
+use time;
+
+use chrono::{
+    Utc,
+    Timelike,
+    Datelike,
+    Weekday,
+    DateTime,
+    NaiveDate,
+};
+use chrono_tz::Tz;
+
+use uuid::{
+    Uuid,
+};
+
+use serde::{
+    Deserialize,
+    Deserializer,
+};
+
+use std::{
+    fmt::{
+        Debug,
+        Display,
+        Formatter,
+        Result as FmtRes,
+    },
+    str::FromStr,
+    sync::Arc,
+};
+
+mod memory;
+mod migrations;
+mod pg;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use self::memory::MemoryStore;
+pub use self::pg::{
+    Database,
+    PoolConfig,
+};
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteStore;
+
+/// Open the store described by the
+/// `[database]` configuration
+pub fn connect(config: &DatabaseConfig) -> Result<Arc<dyn Store>, Error> {
+    open(&config.url, config.pool())
+}
+
+/// Open a store based on the scheme of `url`
+///
+/// - `sqlite://<path>` a SQLite file (requires the `sqlite` feature)
+/// - `memory://` an empty in memory store
+/// - anything else is handed to postgres
+pub fn open(url: &str, pool: PoolConfig) -> Result<Arc<dyn Store>, Error> {
+    if url.starts_with("sqlite://") {
+        open_sqlite(&url["sqlite://".len()..])
+    } else if url.starts_with("memory://") {
+        Ok(Arc::new(MemoryStore::new()))
+    } else {
+        Ok(Arc::new(Database::with_config(url, pool)?))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn open_sqlite(path: &str) -> Result<Arc<dyn Store>, Error> {
+    if path == ":memory:" {
+        Ok(Arc::new(SqliteStore::open_in_memory()?))
+    } else {
+        Ok(Arc::new(SqliteStore::open(path)?))
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite(_path: &str) -> Result<Arc<dyn Store>, Error> {
+    Err(Error::new("robohome was built without the sqlite feature"))
+}
+
+const MINUTES_PER_DAY: i32 = 24 * 60;
+
+// **********
+// TYPES
+// **********
+/// A single switch
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Switch {
+    pub id: i32,
+    pub name: String,
+    pub on_code: i32,
+    pub off_code: i32,
+}
+/// A named collection of switches,
+/// usually everything in one room
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Group {
+    pub id: i32,
+    pub name: String,
+    pub switches: Vec<Switch>,
+}
+
+/// A request to flip every
+/// switch in a group
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupFlip {
+    pub group_id: i32,
+    pub direction: Direction,
+}
+
+/// A request to put every member of
+/// a scene in its stored direction
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SceneActivation {
+    pub scene_id: i32,
+}
+
+/// A request to turn a switch on now
+/// and back off after `minutes`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TimedFlip {
+    pub switch_id: i32,
+    pub minutes: i32,
+}
+
+/// An off code waiting to be sent
+/// once a timed flip has run its course
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PendingOff {
+    pub id: i32,
+    pub code: i32,
+    pub due: DateTime<Utc>,
+}
+
+/// Whether the scheduler is making the
+/// house look lived-in while we are away
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Vacation {
+    pub enabled: bool,
+}
+
+/// Whether the scheduler is holding back
+/// every flip, and when it should carry on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Pause {
+    pub paused: bool,
+    /// Paused until it is turned back
+    /// off when this isn't set
+    #[serde(default)]
+    pub resume_at: Option<DateTime<Utc>>,
+}
+
+/// A switch the scheduler leaves alone
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Hold {
+    pub switch_id: i32,
+    /// Held until it is released
+    /// when this isn't set
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// A named set of switches, each with
+/// the direction it should be moved
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scene {
+    pub id: i32,
+    pub name: String,
+    pub members: Vec<SceneMember>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SceneMember {
+    pub switch: Switch,
+    pub direction: Direction,
+}
+
+/// A regularly scheduled
+/// flip
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScheduledFlip {
+    pub id: i32,
+    pub hour: i32,
+    pub minute: i32,
+    pub dow: DayOfTheWeek,
+    pub direction: Direction,
+    pub kind: FlipKind,
+    /// Minutes before (negative) or after the
+    /// solar event, ignored for `Custom` flips
+    #[serde(default)]
+    pub offset: i32,
+    /// Set for a one-off flip, which only
+    /// fires on this date and ignores `dow`
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+    /// Limits a recurring flip to part
+    /// of the year
+    #[serde(default)]
+    pub season: Option<Season>,
+    /// Minutes an `On` flip leaves its
+    /// switches on before turning them off
+    #[serde(default)]
+    pub duration: Option<i32>,
+}
+
+/// A request to schedule a new
+/// flip for a switch
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NewFlip {
+    pub switch_id: i32,
+    pub hour: i32,
+    pub minute: i32,
+    pub dow: DayOfTheWeek,
+    pub direction: Direction,
+    pub kind: FlipKind,
+    #[serde(default)]
+    pub offset: i32,
+    #[serde(default)]
+    pub season: Option<Season>,
+    #[serde(default)]
+    pub duration: Option<i32>,
+}
+
+/// An edit to a scheduled flip, any
+/// field left out keeps its stored value
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct FlipUpdate {
+    pub id: i32,
+    pub hour: i32,
+    pub minute: i32,
+    pub dow: DayOfTheWeek,
+    pub direction: Direction,
+    pub kind: FlipKind,
+    #[serde(default)]
+    pub offset: Option<i32>,
+    /// `Some(None)` when the season was
+    /// sent as `null` to clear it
+    #[serde(default, deserialize_with = "present")]
+    pub season: Option<Option<Season>>,
+    /// `Some(None)` when the duration was
+    /// sent as `null` to clear it
+    #[serde(default, deserialize_with = "present")]
+    pub duration: Option<Option<i32>>,
+}
+
+impl FlipUpdate {
+    /// The stored `flip` with this
+    /// edit made to it
+    pub fn apply(self, flip: ScheduledFlip) -> ScheduledFlip {
+        ScheduledFlip {
+            hour: self.hour,
+            minute: self.minute,
+            dow: self.dow,
+            direction: self.direction,
+            kind: self.kind,
+            offset: self.offset.unwrap_or(flip.offset),
+            season: self.season.unwrap_or(flip.season),
+            duration: self.duration.unwrap_or(flip.duration),
+            ..flip
+        }
+    }
+}
+
+/// Wraps a field that was sent, even as `null`,
+/// so it can be told apart from a missing one
+fn present<'de, D, T>(d: D) -> Result<Option<T>, D::Error>
+where D: Deserializer<'de>, T: Deserialize<'de> {
+    T::deserialize(d).map(Some)
+}
+
+/// The part of the year a flip is active,
+/// `start` is after `end` for a season that
+/// wraps the new year
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Season {
+    pub start: MonthDay,
+    pub end: MonthDay,
+}
+
+/// A day of any year
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct MonthDay {
+    pub month: i32,
+    pub day: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSql, FromSql)]
+#[postgres(name = "flipkind")]
+pub enum FlipKind {
+    Custom,
+    PreDawn,
+    Sunrise,
+    Dusk,
+    Sunset,
+    CivilDawn,
+    CivilDusk,
+    NauticalDawn,
+    NauticalDusk,
+    AstronomicalDawn,
+    AstronomicalDusk,
+    SolarNoon,
+}
+
+/// When a solar event happens on a date
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpecialTime {
+    pub date: NaiveDate,
+    pub kind: FlipKind,
+    pub hour: i32,
+    pub minute: i32,
+    /// Minutes the time was moved by for
+    /// the weather, already in `hour` and `minute`
+    pub adjustment: i32,
+}
+
+/// The outcome of one refresher run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Refresh {
+    pub id: i32,
+    pub refreshed: DateTime<Utc>,
+    /// The date the sun times were for
+    pub date: NaiveDate,
+    /// The provider the times came from, or
+    /// `stored` when none could be reached
+    pub source: String,
+    pub succeeded: bool,
+    pub attempts: i32,
+    pub message: Option<String>,
+}
+
+/// An instance of a
+/// flip action
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Flip {
+    pub hour: i32,
+    pub minute: i32,
+    pub code: i32,
+    /// Minutes until the switch should be
+    /// turned back off, only for on codes. Always
+    /// written since bincode needs every field
+    #[serde(default)]
+    pub duration: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSql, FromSql)]
+#[postgres(name = "flipdirection")]
+/// A flip direction
+pub enum Direction {
+    On,
+    Off,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct DayOfTheWeek {
+    monday: bool,
+    tuesday: bool,
+    wednesday: bool,
+    thursday: bool,
+    friday: bool,
+    saturday: bool,
+    sunday: bool,
+}
+
+pub struct Authorization {
+    pub created: DateTime<Utc>,
+    pub code: Uuid,
+}
+
+/// A Authorized user's token
+pub enum TokenState {
+    /// This token is valid
+    Valid,
+    /// This token is not valid
+    Invalid,
+    /// This token is valid but should be
+    /// regenerated
+    Expired,
+}
+
+/// Everything robohome needs to persist,
+/// implemented for postgres by `Database` and
+/// in memory by `MemoryStore`
+pub trait Store: Send + Sync {
+    /// Bring the schema up to date, returns
+    /// the versions that were applied
+    fn migrate(&self) -> Result<Vec<i32>, Error>;
+
+    // **********
+    // CREATE
+    // **********
+    fn new_switch(&self, name: &str, on_code: i32, off_code: i32) -> Result<Switch, Error>;
+    fn new_scheduled_flip(&self, sw_id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind, offset: i32, duration: Option<i32>, season: Option<Season>) -> Result<ScheduledFlip, Error>;
+    fn new_group(&self, name: &str) -> Result<Group, Error>;
+    /// Add a switch to a group, adding a switch
+    /// that is already a member does nothing
+    fn add_group_member(&self, group_id: i32, switch_id: i32) -> Result<(), Error>;
+    /// Schedule a flip that only fires on `date`
+    fn new_dated_flip(&self, sw_id: i32, date: NaiveDate, hour: i32, minute: i32, direction: Direction, kind: FlipKind) -> Result<ScheduledFlip, Error>;
+    /// Schedule a flip for every switch in a group
+    fn new_group_flip(&self, group_id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind) -> Result<ScheduledFlip, Error>;
+    fn new_scene(&self, name: &str) -> Result<Scene, Error>;
+    /// Add a switch to a scene or change the
+    /// direction of an existing member
+    fn set_scene_member(&self, scene_id: i32, switch_id: i32, direction: Direction) -> Result<(), Error>;
+    /// Schedule a scene, `On` activates the scene
+    /// and `Off` turns every member off
+    fn new_scene_flip(&self, scene_id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind) -> Result<ScheduledFlip, Error>;
+    /// Issue a new authorization token
+    /// that can be used for a key exchange
+    fn new_auth(&self) -> Result<Uuid, Error>;
+    fn new_token(&self, private: &[u8], shared: &[u8], token: Uuid) -> Result<(), Error>;
+    /// Record the outcome of a refresher run
+    fn new_refresh(&self, date: NaiveDate, source: &str, succeeded: bool, attempts: i32, message: Option<&str>) -> Result<Refresh, Error>;
+    /// Remember to send `code` at `due`, so the
+    /// end of a timed flip survives a restart
+    fn new_pending_off(&self, code: i32, due: DateTime<Utc>) -> Result<PendingOff, Error>;
+    /// Hold a switch, replacing any
+    /// hold it already has
+    fn new_hold(&self, switch_id: i32, until: Option<DateTime<Utc>>) -> Result<Hold, Error>;
+
+    // **********
+    // READ
+    // **********
+    /// Group and scene flips are expanded into
+    /// one `Flip` for each member switch
+    fn get_flips_for_minute(&self, hour: i32, minute: i32, date: NaiveDate) -> Result<Vec<Flip>, Error>;
+    /// Everything that fires on `date`, the recurring
+    /// flips for its day of the week that are in season
+    /// and any one-off flips for that date. Solar flips
+    /// are moved to the special time nearest `date`
+    fn get_flips_for_day(&self, date: NaiveDate) -> Result<Vec<Flip>, Error>;
+    /// The special time nearest to `date`
+    /// for each kind that has been recorded
+    fn get_special_times(&self, date: NaiveDate) -> Result<Vec<SpecialTime>, Error>;
+    /// The latest date any special time was recorded for
+    fn get_latest_special_date(&self) -> Result<Option<NaiveDate>, Error>;
+    /// The most recent refresher run
+    fn get_last_refresh(&self) -> Result<Option<Refresh>, Error>;
+    /// The instant the scheduler last sent the flips that were due
+    fn get_last_processed(&self) -> Result<Option<DateTime<Utc>>, Error>;
+    /// Off until it has been turned on
+    fn get_vacation(&self) -> Result<Vacation, Error>;
+    /// The off codes still waiting to be sent, soonest first
+    fn get_pending_offs(&self) -> Result<Vec<PendingOff>, Error>;
+    /// Not paused until it has been paused
+    fn get_pause(&self) -> Result<Pause, Error>;
+    fn get_holds(&self) -> Result<Vec<Hold>, Error>;
+    fn get_all_switches(&self) -> Result<Vec<Switch>, Error>;
+    fn get_flips_for_switch(&self, switch_id: i32) -> Result<Vec<ScheduledFlip>, Error>;
+    fn get_flip(&self, id: i32) -> Result<ScheduledFlip, Error>;
+    fn get_all_groups(&self) -> Result<Vec<Group>, Error>;
+    fn get_group(&self, group_id: i32) -> Result<Group, Error>;
+    fn get_flips_for_group(&self, group_id: i32) -> Result<Vec<ScheduledFlip>, Error>;
+    fn get_all_scenes(&self) -> Result<Vec<Scene>, Error>;
+    fn get_scene(&self, scene_id: i32) -> Result<Scene, Error>;
+    fn get_flips_for_scene(&self, scene_id: i32) -> Result<Vec<ScheduledFlip>, Error>;
+    /// Get the time an authorization token was
+    /// issued, this fails if the token has already
+    /// been exchanged
+    fn get_auth_age(&self, token: &Uuid) -> Result<DateTime<Utc>, Error>;
+    fn get_private_shared(&self, token: &Uuid) -> Result<(Vec<u8>, Vec<u8>), Error>;
+    fn check_token(&self, token: &Uuid, shared: &[u8]) -> Result<bool, Error>;
+
+    /// The flips for the current minute in `tz`
+    fn get_flips_this_minute(&self, tz: &Tz) -> Result<Vec<Flip>, Error> {
+        let now = time::local_now(Utc::now(), tz);
+        self.get_flips_for_minute(now.hour() as i32, now.minute() as i32, now.date())
+    }
+
+    /// The flips for the current date in `tz`
+    fn get_flips_for_today(&self, tz: &Tz) -> Result<Vec<Flip>, Error> {
+        self.get_flips_for_day(time::today(Utc::now(), tz))
+    }
+
+    // **********
+    // UPDATE
+    // **********
+    fn update_switch(&self, id: i32, name: &str, on_code: i32, off_code: i32) -> Result<Switch, Error>;
+    fn update_flip(&self, id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind, offset: i32, duration: Option<i32>, season: Option<Season>) -> Result<ScheduledFlip, Error>;
+    fn update_group(&self, id: i32, name: &str) -> Result<Group, Error>;
+    fn update_scene(&self, id: i32, name: &str) -> Result<Scene, Error>;
+    /// Record each special time, replacing any that was
+    /// already recorded for its date and kind, returns
+    /// the number recorded
+    fn update_special_times(&self, times: &[SpecialTime]) -> Result<i32, Error>;
+    fn update_last_processed(&self, at: DateTime<Utc>) -> Result<(), Error>;
+    fn update_vacation(&self, vacation: Vacation) -> Result<(), Error>;
+    fn update_pause(&self, pause: Pause) -> Result<(), Error>;
+
+    // **********
+    // DELETE
+    // **********
+    /// Remove a switch and all of its flips
+    fn remove_switch(&self, id: i32) -> Result<i32, Error>;
+    fn remove_flip(&self, id: i32) -> Result<i32, Error>;
+    /// Remove a group and all of its flips,
+    /// the member switches are left alone
+    fn remove_group(&self, id: i32) -> Result<i32, Error>;
+    fn remove_group_member(&self, group_id: i32, switch_id: i32) -> Result<i32, Error>;
+    /// Remove the one-off flips dated before
+    /// `date`, returns the number removed
+    fn remove_expired_flips(&self, date: NaiveDate) -> Result<i32, Error>;
+    /// Remove a scene and all of its flips
+    fn remove_scene(&self, id: i32) -> Result<i32, Error>;
+    fn remove_scene_member(&self, scene_id: i32, switch_id: i32) -> Result<i32, Error>;
+    /// Remove a pending off once it has been
+    /// sent, returns the number removed
+    fn remove_pending_off(&self, id: i32) -> Result<i32, Error>;
+    fn remove_hold(&self, switch_id: i32) -> Result<i32, Error>;
+    /// Remove the holds that ran out at or before
+    /// `through`, returns the number removed
+    fn remove_expired_holds(&self, through: DateTime<Utc>) -> Result<i32, Error>;
+}
+
+impl Switch {
+    /// The code to send to move this
+    /// switch in `direction`
+    pub fn code(&self, direction: Direction) -> i32 {
+        match direction {
+            Direction::Off => self.off_code,
+            Direction::On => self.on_code,
+        }
+    }
+}
+
+impl ScheduledFlip {
+    /// If this flip should fire on `date`
+    pub fn fires_on(&self, date: NaiveDate) -> bool {
+        let day_matches = match self.date {
+            Some(d) => d == date,
+            None => {
+                let dow: i32 = self.dow.into();
+                let day: i32 = DayOfTheWeek::from(date.weekday()).into();
+                dow & day > 0
+            }
+        };
+        day_matches && self.season.map(|s| s.contains(date)).unwrap_or(true)
+    }
+
+    /// The hour and minute this flip fires, solar
+    /// flips follow the matching special time and
+    /// keep their own time if there isn't one
+    pub fn time_with(&self, special_times: &[SpecialTime]) -> (i32, i32) {
+        if self.kind == FlipKind::Custom {
+            return (self.hour, self.minute);
+        }
+        match special_times.iter().find(|s| s.kind == self.kind) {
+            Some(s) => offset_time(s.hour, s.minute, self.offset),
+            None => (self.hour, self.minute),
+        }
+    }
+}
+
+impl Season {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        let day = MonthDay::from(date);
+        if self.start <= self.end {
+            self.start <= day && day <= self.end
+        } else {
+            day >= self.start || day <= self.end
+        }
+    }
+
+    /// If both ends are real days, the 29th
+    /// of February is allowed
+    pub fn is_valid(&self) -> bool {
+        self.start.is_valid() && self.end.is_valid()
+    }
+}
+
+impl MonthDay {
+    pub fn is_valid(&self) -> bool {
+        self.month > 0 && self.day > 0
+            && NaiveDate::from_ymd_opt(2000, self.month as u32, self.day as u32).is_some()
+    }
+}
+
+impl Group {
+    /// One code for each member switch
+    pub fn codes(&self, direction: Direction) -> Vec<i32> {
+        self.switches.iter().map(|s| s.code(direction)).collect()
+    }
+}
+
+impl Pause {
+    /// If flips should be held back at `now`
+    pub fn is_paused(&self, now: DateTime<Utc>) -> bool {
+        self.paused && self.resume_at.map(|at| at > now).unwrap_or(true)
+    }
+}
+
+impl Hold {
+    /// If the switch is still held at `now`
+    pub fn is_held(&self, now: DateTime<Utc>) -> bool {
+        self.until.map(|until| until > now).unwrap_or(true)
+    }
+}
+
+impl Scene {
+    /// One code for each member switch, the same
+    /// rule the scheduler uses for a scene flip
+    pub fn codes(&self, direction: Direction) -> Vec<i32> {
+        self.members.iter()
+            .map(|m| match direction {
+                Direction::On => m.switch.code(m.direction),
+                Direction::Off => m.switch.off_code,
+            })
+            .collect()
+    }
+}
+
+// **********
+// MAPPINGS
+// **********
+impl Into<i32> for DayOfTheWeek {
+    fn into(self) -> i32 {
+        let mut ret = 0;
+        if self.monday {
+            ret += 1;
+        }
+        if self.tuesday {
+            ret += 2;
+        }
+        if self.wednesday {
+            ret += 4;
+        }
+        if self.thursday {
+            ret += 8;
+        }
+        if self.friday {
+            ret += 16;
+        }
+        if self.saturday {
+            ret += 32;
+        }
+        if self.sunday {
+            ret += 64;
+        }
+        ret
+    }
+}
+
+impl From<i32> for DayOfTheWeek {
+    fn from(v: i32) -> DayOfTheWeek {
+        DayOfTheWeek {
+            monday:    v &  1 > 0,
+            tuesday:   v &  2 > 0,
+            wednesday: v &  4 > 0,
+            thursday:  v &  8 > 0,
+            friday:    v & 16 > 0,
+            saturday:  v & 32 > 0,
+            sunday:    v & 64 > 0,
+        }
+    }
+}
+
+impl From<Weekday> for DayOfTheWeek {
+    fn from(wd: Weekday) -> DayOfTheWeek {
+        match wd {
+            Weekday::Mon => 1i32,
+            Weekday::Tue => 2i32,
+            Weekday::Wed => 4i32,
+            Weekday::Thu => 8i32,
+            Weekday::Fri => 16i32,
+            Weekday::Sat => 32i32,
+            Weekday::Sun => 64i32,
+        }.into()
+    }
+}
+
+/// Move `hour:minute` by `offset` minutes,
+/// wrapping around midnight
+pub fn offset_time(hour: i32, minute: i32, offset: i32) -> (i32, i32) {
+    let minutes = (hour * 60 + minute + offset) % MINUTES_PER_DAY;
+    let minutes = (minutes + MINUTES_PER_DAY) % MINUTES_PER_DAY;
+    (minutes / 60, minutes % 60)
+}
+
+/// Seasons are stored as a pair of
+/// nullable `month * 100 + day` columns
+fn season_columns(season: Option<Season>) -> (Option<i32>, Option<i32>) {
+    match season {
+        Some(s) => (Some(s.start.into()), Some(s.end.into())),
+        None => (None, None),
+    }
+}
+
+fn season_from_columns(start: Option<i32>, end: Option<i32>) -> Option<Season> {
+    match (start, end) {
+        (Some(start), Some(end)) => Some(Season {
+            start: start.into(),
+            end: end.into(),
+        }),
+        _ => None,
+    }
+}
+
+impl Into<i32> for MonthDay {
+    fn into(self) -> i32 {
+        self.month * 100 + self.day
+    }
+}
+
+impl From<i32> for MonthDay {
+    fn from(v: i32) -> MonthDay {
+        MonthDay {
+            month: v / 100,
+            day: v % 100,
+        }
+    }
+}
+
+impl From<NaiveDate> for MonthDay {
+    fn from(date: NaiveDate) -> MonthDay {
+        MonthDay {
+            month: date.month() as i32,
+            day: date.day() as i32,
+        }
+    }
+}
+
+impl FlipKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlipKind::Custom => "Custom",
+            FlipKind::PreDawn => "PreDawn",
+            FlipKind::Sunrise => "Sunrise",
+            FlipKind::Dusk => "Dusk",
+            FlipKind::Sunset => "Sunset",
+            FlipKind::CivilDawn => "CivilDawn",
+            FlipKind::CivilDusk => "CivilDusk",
+            FlipKind::NauticalDawn => "NauticalDawn",
+            FlipKind::NauticalDusk => "NauticalDusk",
+            FlipKind::AstronomicalDawn => "AstronomicalDawn",
+            FlipKind::AstronomicalDusk => "AstronomicalDusk",
+            FlipKind::SolarNoon => "SolarNoon",
+        }
+    }
+}
+
+impl FromStr for FlipKind {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<FlipKind, Error> {
+        match s {
+            "Custom" => Ok(FlipKind::Custom),
+            "PreDawn" => Ok(FlipKind::PreDawn),
+            "Sunrise" => Ok(FlipKind::Sunrise),
+            "Dusk" => Ok(FlipKind::Dusk),
+            "Sunset" => Ok(FlipKind::Sunset),
+            "CivilDawn" => Ok(FlipKind::CivilDawn),
+            "CivilDusk" => Ok(FlipKind::CivilDusk),
+            "NauticalDawn" => Ok(FlipKind::NauticalDawn),
+            "NauticalDusk" => Ok(FlipKind::NauticalDusk),
+            "AstronomicalDawn" => Ok(FlipKind::AstronomicalDawn),
+            "AstronomicalDusk" => Ok(FlipKind::AstronomicalDusk),
+            "SolarNoon" => Ok(FlipKind::SolarNoon),
+            _ => Err(Error::Other(format!("Unknown flip kind {}", s))),
+        }
+    }
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::On => "On",
+            Direction::Off => "Off",
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Direction, Error> {
+        match s {
+            "On" => Ok(Direction::On),
+            "Off" => Ok(Direction::Off),
+            _ => Err(Error::Other(format!("Unknown direction {}", s))),
+        }
+    }
+}
+
+impl From<bool> for Direction {
+    fn from(i: bool) -> Direction {
+        match i {
+            false => Direction::Off,
+            true => Direction::On,
+        }
+    }
+}
+
+impl Into<bool> for Direction {
+    fn into(self) -> bool {
+        match self {
+            Direction::Off => false,
+            Direction::On => true,
+        }
+    }
+}
+
+impl Display for DayOfTheWeek {
+    fn fmt(&self, f: &mut Formatter) -> FmtRes {
+        let mut s = String::with_capacity(7);
+        if self.monday {
+            s.push('M');
+        }
+        if self.tuesday {
+            s.push('T');
+        }
+        if self.wednesday {
+            s.push('W');
+        }
+        if self.thursday {
+            s.push('R');
+        }
+        if self.friday {
+            s.push('F')
+        }
+        if self.saturday {
+            s.push('S');
+        }
+        if self.saturday {
+            s.push('U');
+        }
+        write!(f, "{}", s)
+    }
+}
+
+impl Debug for DayOfTheWeek {
+    fn fmt(&self, f: &mut Formatter) -> FmtRes {
+        write!(f, "{}", self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{
+        Duration,
+        TimeZone,
+    };
+
+    #[test]
+    fn flips_over_ipc() {
+        let flips = vec![
+            Flip { hour: 7, minute: 0, code: 4543795, duration: None },
+            Flip { hour: 7, minute: 0, code: 4543795, duration: Some(45) },
+        ];
+        for flip in flips {
+            let bytes = ::bincode::serialize(&flip).unwrap();
+            let back: Flip = ::bincode::deserialize(&bytes).unwrap();
+            assert_eq!(back, flip);
+        }
+        let manual: Flip = ::serde_json::from_str(r#"{"hour": 7, "minute": 0, "code": 4543795}"#).unwrap();
+        assert_eq!(manual.duration, None);
+    }
+
+    #[test]
+    fn dow_int() {
+        for i in 0..128 {
+            let dow: DayOfTheWeek = i.into();
+            let back: i32 = dow.into();
+            assert_eq!(i, back);
+        }
+    }
+
+    #[test]
+    fn cron_dow() {
+        let weekdays = vec![
+            (Weekday::Mon, DayOfTheWeek {
+                monday: true,
+                tuesday: false,
+                wednesday: false,
+                thursday: false,
+                friday: false,
+                saturday: false,
+                sunday: false,
+            }),
+            (Weekday::Tue, DayOfTheWeek {
+                monday: false,
+                tuesday: true,
+                wednesday: false,
+                thursday: false,
+                friday: false,
+                saturday: false,
+                sunday: false,
+            }),
+            (Weekday::Wed, DayOfTheWeek{
+                monday: false,
+                tuesday: false,
+                wednesday: true,
+                thursday: false,
+                friday: false,
+                saturday: false,
+                sunday: false,
+            }),
+            (Weekday::Thu, DayOfTheWeek {
+                monday: false,
+                tuesday: false,
+                wednesday: false,
+                thursday: true,
+                friday: false,
+                saturday: false,
+                sunday: false,
+            }),
+            (Weekday::Fri, DayOfTheWeek {
+                monday: false,
+                tuesday: false,
+                wednesday: false,
+                thursday: false,
+                friday: true,
+                saturday: false,
+                sunday: false,
+            }),
+            (Weekday::Sat, DayOfTheWeek {
+                monday: false,
+                tuesday: false,
+                wednesday: false,
+                thursday: false,
+                friday: false,
+                saturday: true,
+                sunday: false,
+            }),
+            (Weekday::Sun, DayOfTheWeek {
+                monday: false,
+                tuesday: false,
+                wednesday: false,
+                thursday: false,
+                friday: false,
+                saturday: false,
+                sunday: true,
+            }),
+        ];
+        for (wd, dow) in weekdays {
+            let d: DayOfTheWeek = wd.into();
+            assert_eq!(d, dow);
+        }
+    }
+
+    #[test]
+    fn season_wraps() {
+        let christmas = Season {
+            start: MonthDay { month: 11, day: 25 },
+            end: MonthDay { month: 1, day: 6 },
+        };
+        assert!(christmas.contains(NaiveDate::from_ymd(2026, 11, 25)));
+        assert!(christmas.contains(NaiveDate::from_ymd(2026, 12, 31)));
+        assert!(christmas.contains(NaiveDate::from_ymd(2027, 1, 6)));
+        assert!(!christmas.contains(NaiveDate::from_ymd(2027, 1, 7)));
+        assert!(!christmas.contains(NaiveDate::from_ymd(2026, 7, 4)));
+        let summer = Season {
+            start: MonthDay { month: 6, day: 1 },
+            end: MonthDay { month: 8, day: 31 },
+        };
+        assert!(summer.contains(NaiveDate::from_ymd(2026, 7, 4)));
+        assert!(!summer.contains(NaiveDate::from_ymd(2026, 12, 31)));
+        let start: i32 = christmas.start.into();
+        assert_eq!(start, 1125);
+        assert_eq!(season_from_columns(Some(1125), Some(106)), Some(christmas));
+        assert_eq!(season_columns(None), (None, None));
+        assert!(christmas.is_valid());
+        assert!(MonthDay { month: 2, day: 29 }.is_valid());
+        assert!(!MonthDay { month: 2, day: 30 }.is_valid());
+        assert!(!MonthDay { month: 13, day: 1 }.is_valid());
+    }
+
+    #[test]
+    fn offsets_wrap() {
+        assert_eq!(offset_time(17, 45, -20), (17, 25));
+        assert_eq!(offset_time(6, 50, 45), (7, 35));
+        assert_eq!(offset_time(0, 10, -30), (23, 40));
+        assert_eq!(offset_time(23, 50, 15), (0, 5));
+        assert_eq!(offset_time(12, 0, -3 * 24 * 60), (12, 0));
+    }
+
+    #[test]
+    fn pauses_and_holds_run_out() {
+        let now = Utc.ymd(2018, 10, 1).and_hms(12, 0, 0);
+        assert!(!Pause::default().is_paused(now));
+        let pause = Pause {
+            paused: true,
+            resume_at: Some(now),
+        };
+        assert!(pause.is_paused(now - Duration::minutes(1)));
+        assert!(!pause.is_paused(now));
+        assert!(Pause { resume_at: None, ..pause }.is_paused(now));
+        let hold = Hold {
+            switch_id: 1,
+            until: Some(now),
+        };
+        assert!(hold.is_held(now - Duration::minutes(1)));
+        assert!(!hold.is_held(now));
+        assert!(Hold { until: None, ..hold }.is_held(now));
+    }
+}
@@ -0,0 +1,958 @@
+//! Postgres storage, all of the heavy
+//! lifting here is done by the functions
+//! defined in `migrations/postgres`
+use postgres::{
+    rows::Row,
+};
+use r2d2::{
+    Pool,
+    PooledConnection,
+};
+use r2d2_postgres::{
+    PostgresConnectionManager,
+    TlsMode,
+};
+
+use chrono::{
+    DateTime,
+    NaiveDate,
+    Utc,
+};
+
+use uuid::Uuid;
+
+use std::time::Duration;
+
+use super::{
+    migrations::{
+        self,
+        BASELINE,
+    },
+    DayOfTheWeek,
+    Direction,
+    Flip,
+    FlipKind,
+    Group,
+    Hold,
+    Pause,
+    PendingOff,
+    Refresh,
+    Scene,
+    SceneMember,
+    ScheduledFlip,
+    Season,
+    season_columns,
+    season_from_columns,
+    SpecialTime,
+    Store,
+    Switch,
+    Vacation,
+};
+use Error;
+
+type Connection = PooledConnection<PostgresConnectionManager>;
+
+/// Settings for the connection pool
+/// owned by a `Database`
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// The maximum number of open connections
+    pub max_size: u32,
+    /// How long to wait for a connection
+    /// before giving up
+    pub connection_timeout: Duration,
+    /// Check that a connection is still alive
+    /// before handing it out
+    pub test_on_check_out: bool,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 4,
+            connection_timeout: Duration::from_secs(5),
+            test_on_check_out: true,
+        }
+    }
+}
+
+/// A handle to the database, this
+/// is cheap to clone and should be
+/// constructed once and shared
+#[derive(Clone)]
+pub struct Database {
+    pool: Pool<PostgresConnectionManager>,
+}
+
+impl Database {
+    pub fn with_config(conn_str: &str, config: PoolConfig) -> Result<Database, Error> {
+        let manager = PostgresConnectionManager::new(conn_str, TlsMode::None)?;
+        let pool = Pool::builder()
+            .max_size(config.max_size)
+            .connection_timeout(config.connection_timeout)
+            .test_on_check_out(config.test_on_check_out)
+            .build(manager)?;
+        Ok(Database {
+            pool,
+        })
+    }
+
+    /// Verify that the database can be reached
+    pub fn health_check(&self) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        c.query("SELECT 1", &[])?;
+        Ok(())
+    }
+
+    fn get_connection(&self) -> Result<Connection, Error> {
+        let ret = self.pool.get()?;
+        Ok(ret)
+    }
+
+    fn group_members(c: &Connection, group_id: i32) -> Result<Vec<Switch>, Error> {
+        let ret = c.query("SELECT id, name, on_code, off_code
+                           FROM get_group_members($1)",
+                          &[&group_id])?
+                    .iter()
+                    .map(map_switch)
+                    .collect();
+        Ok(ret)
+    }
+
+    fn scene_members(c: &Connection, scene_id: i32) -> Result<Vec<SceneMember>, Error> {
+        let ret = c.query("SELECT id, name, on_code, off_code, direction
+                           FROM get_scene_members($1)",
+                          &[&scene_id])?
+                    .iter()
+                    .map(|r| SceneMember {
+                        direction: r.get(4),
+                        switch: map_switch(r),
+                    })
+                    .collect();
+        Ok(ret)
+    }
+
+    /// Apply the migrations `c` is missing, the caller
+    /// holds the migration lock
+    fn apply_migrations(c: &Connection) -> Result<Vec<i32>, Error> {
+        c.batch_execute(migrations::POSTGRES_VERSION_TABLE)?;
+        let current: Option<i32> = c.query("SELECT MAX(version)
+                                            FROM schema_version", &[])?
+                                    .iter()
+                                    .next()
+                                    .and_then(|r| r.get(0));
+        let current = match current {
+            Some(version) => version,
+            None => {
+                let existing: bool = c.query("SELECT to_regclass('public.switch') IS NOT NULL", &[])?
+                                    .iter()
+                                    .next()
+                                    .map(|r| r.get(0))
+                                    .unwrap_or(false);
+                if existing {
+                    c.execute("INSERT INTO schema_version (version, name)
+                               VALUES ($1, 'baseline')",
+                              &[&BASELINE])?;
+                    BASELINE
+                } else {
+                    0
+                }
+            }
+        };
+        let mut applied = Vec::new();
+        for m in migrations::POSTGRES.iter().filter(|m| m.version > current) {
+            if m.transaction {
+                let tx = c.transaction()?;
+                tx.batch_execute(m.up)?;
+                tx.execute("INSERT INTO schema_version (version, name)
+                            VALUES ($1, $2)",
+                           &[&m.version, &m.name])?;
+                tx.commit()?;
+            } else {
+                c.batch_execute(m.up)?;
+                c.execute("INSERT INTO schema_version (version, name)
+                           VALUES ($1, $2)",
+                          &[&m.version, &m.name])?;
+            }
+            applied.push(m.version);
+        }
+        Ok(applied)
+    }
+}
+
+impl Store for Database {
+    fn migrate(&self) -> Result<Vec<i32>, Error> {
+        let c = self.get_connection()?;
+        // every binary migrates on boot, the lock keeps
+        // two of them from applying the same migration
+        c.execute("SELECT pg_advisory_lock($1)", &[&migrations::POSTGRES_LOCK])?;
+        let ret = Self::apply_migrations(&c);
+        c.execute("SELECT pg_advisory_unlock($1)", &[&migrations::POSTGRES_LOCK])?;
+        ret
+    }
+
+    // **********
+    // CREATE
+    // **********
+    fn new_switch(&self, name: &str, on_code: i32, off_code: i32,) -> Result<Switch, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, name, on_code, off_code
+                           FROM new_switch($1, $2, $3)",
+                          &[&name, &on_code, &off_code])?
+            .iter()
+            .map(map_switch)
+            .next()
+            .ok_or(Error::new("nothing returned from new_switch"))?;
+        Ok(ret)
+    }
+
+    fn new_scheduled_flip(&self, sw_id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind, offset: i32, duration: Option<i32>, season: Option<Season>) -> Result<ScheduledFlip, Error> {
+        let c = self.get_connection()?;
+        let dow: i32 = dow.into();
+        let (start, end) = season_columns(season);
+        let ret = c.query("SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+                           FROM new_flip($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+                          &[&sw_id, &hour, &minute, &dow, &direction, &kind, &offset, &duration, &start, &end])?
+                    .iter()
+                    .map(map_scheduled_flip)
+                    .next()
+                    .ok_or(Error::new("nothing returned from new_flip"))?;
+        Ok(ret)
+    }
+
+    fn new_dated_flip(&self, sw_id: i32, date: NaiveDate, hour: i32, minute: i32, direction: Direction, kind: FlipKind) -> Result<ScheduledFlip, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+                           FROM new_dated_flip($1, $2, $3, $4, $5, $6)",
+                          &[&sw_id, &date, &hour, &minute, &direction, &kind])?
+                    .iter()
+                    .map(map_scheduled_flip)
+                    .next()
+                    .ok_or(Error::new("nothing returned from new_dated_flip"))?;
+        Ok(ret)
+    }
+
+    fn new_group(&self, name: &str) -> Result<Group, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, name
+                           FROM new_group($1)",
+                          &[&name])?
+                    .iter()
+                    .map(|r| Group {
+                        id: r.get(0),
+                        name: r.get(1),
+                        switches: Vec::new(),
+                    })
+                    .next()
+                    .ok_or(Error::new("nothing returned from new_group"))?;
+        Ok(ret)
+    }
+
+    fn add_group_member(&self, group_id: i32, switch_id: i32) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        c.query("SELECT *
+                FROM add_group_member($1, $2)",
+                &[&group_id, &switch_id])?;
+        Ok(())
+    }
+
+    fn new_group_flip(&self, group_id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind) -> Result<ScheduledFlip, Error> {
+        let c = self.get_connection()?;
+        let dow: i32 = dow.into();
+        let ret = c.query("SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+                           FROM new_group_flip($1, $2, $3, $4, $5, $6)",
+                          &[&group_id, &hour, &minute, &dow, &direction, &kind])?
+                    .iter()
+                    .map(map_scheduled_flip)
+                    .next()
+                    .ok_or(Error::new("nothing returned from new_group_flip"))?;
+        Ok(ret)
+    }
+
+    fn new_scene(&self, name: &str) -> Result<Scene, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, name
+                           FROM new_scene($1)",
+                          &[&name])?
+                    .iter()
+                    .map(|r| Scene {
+                        id: r.get(0),
+                        name: r.get(1),
+                        members: Vec::new(),
+                    })
+                    .next()
+                    .ok_or(Error::new("nothing returned from new_scene"))?;
+        Ok(ret)
+    }
+
+    fn set_scene_member(&self, scene_id: i32, switch_id: i32, direction: Direction) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        c.query("SELECT *
+                FROM set_scene_member($1, $2, $3)",
+                &[&scene_id, &switch_id, &direction])?;
+        Ok(())
+    }
+
+    fn new_scene_flip(&self, scene_id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind) -> Result<ScheduledFlip, Error> {
+        let c = self.get_connection()?;
+        let dow: i32 = dow.into();
+        let ret = c.query("SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+                           FROM new_scene_flip($1, $2, $3, $4, $5, $6)",
+                          &[&scene_id, &hour, &minute, &dow, &direction, &kind])?
+                    .iter()
+                    .map(map_scheduled_flip)
+                    .next()
+                    .ok_or(Error::new("nothing returned from new_scene_flip"))?;
+        Ok(ret)
+    }
+
+    fn new_auth(&self) -> Result<Uuid, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT new_auth()", &[])?
+                    .iter()
+                    .map(|r| r.get(0))
+                    .next()
+                    .ok_or(Error::new("nothing returned from new_auth"))?;
+        Ok(ret)
+    }
+
+    fn new_token(&self, private: &[u8], shared: &[u8], token: Uuid) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        c.query("SELECT *
+                FROM new_token($1, $2, $3)",
+                &[&private, &shared, &token])?;
+        Ok(())
+    }
+
+    fn new_refresh(&self, date: NaiveDate, source: &str, succeeded: bool, attempts: i32, message: Option<&str>) -> Result<Refresh, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, refreshed, date, source, succeeded, attempts, message
+                           FROM new_refresh($1, $2, $3, $4, $5)",
+                          &[&date, &source, &succeeded, &attempts, &message])?
+            .iter()
+            .map(map_refresh)
+            .next()
+            .ok_or(Error::new("nothing returned from new_refresh"))?;
+        Ok(ret)
+    }
+
+    fn new_pending_off(&self, code: i32, due: DateTime<Utc>) -> Result<PendingOff, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, code, due
+                           FROM new_pending_off($1, $2)",
+                          &[&code, &due])?
+            .iter()
+            .map(map_pending_off)
+            .next()
+            .ok_or(Error::new("nothing returned from new_pending_off"))?;
+        Ok(ret)
+    }
+
+    fn new_hold(&self, switch_id: i32, until: Option<DateTime<Utc>>) -> Result<Hold, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT switch_id, until
+                           FROM new_hold($1, $2)",
+                          &[&switch_id, &until])?
+            .iter()
+            .map(map_hold)
+            .next()
+            .ok_or(Error::new("nothing returned from new_hold"))?;
+        Ok(ret)
+    }
+
+    // **********
+    // READ
+    // **********
+    fn get_flips_for_minute(&self, hour: i32, minute: i32, date: NaiveDate) -> Result<Vec<Flip>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT hour, minute, code, duration
+                           FROM get_flips_for_minute($1, $2, $3)",
+                            &[&hour, &minute, &date])?
+                    .iter()
+                    .map(map_flip)
+                    .collect();
+
+        Ok(ret)
+    }
+
+    fn get_flips_for_day(&self, date: NaiveDate) -> Result<Vec<Flip>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT hour, minute, code, duration
+                           FROM get_flips_for_day($1)",
+                           &[&date])?
+                    .iter()
+                    .map(map_flip)
+                    .collect();
+        Ok(ret)
+    }
+
+    fn get_special_times(&self, date: NaiveDate) -> Result<Vec<SpecialTime>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT date, kind, hour, minute, adjustment
+                           FROM get_special_times($1)",
+                           &[&date])?
+                    .iter()
+                    .map(map_special_time)
+                    .collect();
+        Ok(ret)
+    }
+
+    fn get_latest_special_date(&self) -> Result<Option<NaiveDate>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT get_latest_special_date()", &[])?
+                    .iter()
+                    .next()
+                    .and_then(|r| r.get(0));
+        Ok(ret)
+    }
+
+    fn get_last_refresh(&self) -> Result<Option<Refresh>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, refreshed, date, source, succeeded, attempts, message
+                           FROM get_last_refresh()", &[])?
+                    .iter()
+                    .map(map_refresh)
+                    .next();
+        Ok(ret)
+    }
+
+    fn get_last_processed(&self) -> Result<Option<DateTime<Utc>>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT get_last_processed()", &[])?
+                    .iter()
+                    .next()
+                    .and_then(|r| r.get(0));
+        Ok(ret)
+    }
+
+    fn get_vacation(&self) -> Result<Vacation, Error> {
+        let c = self.get_connection()?;
+        let enabled = c.query("SELECT get_vacation()", &[])?
+                        .iter()
+                        .next()
+                        .map(|r| r.get(0))
+                        .unwrap_or(false);
+        Ok(Vacation {
+            enabled,
+        })
+    }
+
+    fn get_pending_offs(&self) -> Result<Vec<PendingOff>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, code, due
+                           FROM get_pending_offs()", &[])?
+                    .iter()
+                    .map(map_pending_off)
+                    .collect();
+        Ok(ret)
+    }
+
+    fn get_pause(&self) -> Result<Pause, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT paused, resume_at
+                           FROM get_pause()", &[])?
+                    .iter()
+                    .map(|r| Pause {
+                        paused: r.get(0),
+                        resume_at: r.get(1),
+                    })
+                    .next()
+                    .unwrap_or_default();
+        Ok(ret)
+    }
+
+    fn get_holds(&self) -> Result<Vec<Hold>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT switch_id, until
+                           FROM get_holds()", &[])?
+                    .iter()
+                    .map(map_hold)
+                    .collect();
+        Ok(ret)
+    }
+
+    fn get_all_switches(&self) -> Result<Vec<Switch>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, name, on_code, off_code
+                           FROM get_all_switches()", &[])?
+                        .iter()
+                        .map(map_switch).collect();
+        Ok(ret)
+    }
+
+    fn get_flips_for_switch(&self, switch_id: i32) -> Result<Vec<ScheduledFlip>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+                           FROM get_switch_flips($1)",
+                           &[&switch_id])?
+                    .iter()
+                    .map(map_scheduled_flip)
+                    .collect();
+        Ok(ret)
+    }
+
+    fn get_flip(&self, id: i32) -> Result<ScheduledFlip, Error> {
+        let c = self.get_connection()?;
+        c.query("SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+                 FROM get_flip($1)",
+                 &[&id])?
+            .iter()
+            .map(map_scheduled_flip)
+            .next()
+            .ok_or(Error::Other(format!("flip {} does not exist", id)))
+    }
+
+    fn get_all_groups(&self) -> Result<Vec<Group>, Error> {
+        let c = self.get_connection()?;
+        let rows: Vec<(i32, String)> = c.query("SELECT id, name
+                                                FROM get_all_groups()", &[])?
+                                        .iter()
+                                        .map(|r| (r.get(0), r.get(1)))
+                                        .collect();
+        let mut ret = Vec::with_capacity(rows.len());
+        for (id, name) in rows {
+            ret.push(Group {
+                id,
+                name,
+                switches: Self::group_members(&c, id)?,
+            });
+        }
+        Ok(ret)
+    }
+
+    fn get_group(&self, group_id: i32) -> Result<Group, Error> {
+        let c = self.get_connection()?;
+        let name: String = c.query("SELECT name
+                                    FROM get_group($1)",
+                                   &[&group_id])?
+                            .iter()
+                            .map(|r| r.get(0))
+                            .next()
+                            .ok_or(Error::Other(format!("group {} does not exist", group_id)))?;
+        Ok(Group {
+            id: group_id,
+            name,
+            switches: Self::group_members(&c, group_id)?,
+        })
+    }
+
+    fn get_flips_for_group(&self, group_id: i32) -> Result<Vec<ScheduledFlip>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+                           FROM get_group_flips($1)",
+                           &[&group_id])?
+                    .iter()
+                    .map(map_scheduled_flip)
+                    .collect();
+        Ok(ret)
+    }
+
+    fn get_all_scenes(&self) -> Result<Vec<Scene>, Error> {
+        let c = self.get_connection()?;
+        let rows: Vec<(i32, String)> = c.query("SELECT id, name
+                                                FROM get_all_scenes()", &[])?
+                                        .iter()
+                                        .map(|r| (r.get(0), r.get(1)))
+                                        .collect();
+        let mut ret = Vec::with_capacity(rows.len());
+        for (id, name) in rows {
+            ret.push(Scene {
+                id,
+                name,
+                members: Self::scene_members(&c, id)?,
+            });
+        }
+        Ok(ret)
+    }
+
+    fn get_scene(&self, scene_id: i32) -> Result<Scene, Error> {
+        let c = self.get_connection()?;
+        let name: String = c.query("SELECT name
+                                    FROM get_scene($1)",
+                                   &[&scene_id])?
+                            .iter()
+                            .map(|r| r.get(0))
+                            .next()
+                            .ok_or(Error::Other(format!("scene {} does not exist", scene_id)))?;
+        Ok(Scene {
+            id: scene_id,
+            name,
+            members: Self::scene_members(&c, scene_id)?,
+        })
+    }
+
+    fn get_flips_for_scene(&self, scene_id: i32) -> Result<Vec<ScheduledFlip>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+                           FROM get_scene_flips($1)",
+                           &[&scene_id])?
+                    .iter()
+                    .map(map_scheduled_flip)
+                    .collect();
+        Ok(ret)
+    }
+
+    fn get_auth_age(&self, token: &Uuid) -> Result<DateTime<Utc>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT *
+                            FROM check_auth($1)",
+                            &[token])?
+                    .iter()
+                    .map::<DateTime<Utc>, _>(|r| r.get(0))
+                    .next()
+                    .ok_or(Error::new("Failed to get auth token data"))?;
+        Ok(ret)
+    }
+
+    fn get_private_shared(&self, token: &Uuid) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let c = self.get_connection()?;
+        if let Some(pair) = c.query("SELECT private, shared
+                                     FROM get_token_pair($1)",
+                                     &[&token])?
+                                .iter()
+                                .map(|r| (r.get(0), r.get(1)))
+                                .next() {
+            Ok(pair)
+        } else {
+            Err(Error::new("Unauthorized token provided"))
+        }
+    }
+
+    fn check_token(&self, token: &Uuid, shared: &[u8]) -> Result<bool, Error> {
+        let c = self.get_connection()?;
+        if let Some((db_token, db_shared)) = c.query("SELECT token, shared
+                            FROM get_token($1)",
+                            &[&token])?
+                    .iter()
+                    .map::<(Uuid, Vec<u8>), _>(|r| (r.get(0), r.get(1)))
+                    .next()
+        {
+            Ok(token == &db_token &&  shared == db_shared.as_slice())
+        } else {
+            Ok(false)
+        }
+    }
+
+    // **********
+    // UPDATE
+    // **********
+    fn update_switch(&self, id: i32, name: &str, on_code: i32, off_code: i32) -> Result<Switch, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT id, name, on_code, off_code
+                           FROM update_switch($1, $2, $3, $4)",
+                           &[&id, &name, &on_code, &off_code])?
+                           .iter()
+                           .map(map_switch)
+                           .next()
+                           .ok_or(Error::new("Nothing returned from switch update"))?;
+        Ok(ret)
+    }
+
+    fn update_flip(&self, id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind, offset: i32, duration: Option<i32>, season: Option<Season>) -> Result<ScheduledFlip, Error> {
+        let c = self.get_connection()?;
+        let dow: i32 = dow.into();
+        let (start, end) = season_columns(season);
+        let ret = c.query("SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+                           FROM update_flip($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+                           &[&id, &hour, &minute, &dow, &direction, &kind, &offset, &duration, &start, &end])?
+                    .iter()
+                    .map(map_scheduled_flip)
+                    .next()
+                    .ok_or(Error::new("Nothing returned from flip update"))?;
+        Ok(ret)
+    }
+
+    fn update_group(&self, id: i32, name: &str) -> Result<Group, Error> {
+        let c = self.get_connection()?;
+        let id: i32 = c.query("SELECT id
+                               FROM update_group($1, $2)",
+                              &[&id, &name])?
+                        .iter()
+                        .filter_map(|r| r.get::<_, Option<i32>>(0))
+                        .next()
+                        .ok_or(Error::new("Nothing returned from group update"))?;
+        Ok(Group {
+            id,
+            name: name.to_string(),
+            switches: Self::group_members(&c, id)?,
+        })
+    }
+
+    fn update_scene(&self, id: i32, name: &str) -> Result<Scene, Error> {
+        let c = self.get_connection()?;
+        let id: i32 = c.query("SELECT id
+                               FROM update_scene($1, $2)",
+                              &[&id, &name])?
+                        .iter()
+                        .filter_map(|r| r.get::<_, Option<i32>>(0))
+                        .next()
+                        .ok_or(Error::new("Nothing returned from scene update"))?;
+        Ok(Scene {
+            id,
+            name: name.to_string(),
+            members: Self::scene_members(&c, id)?,
+        })
+    }
+
+    fn update_special_times(&self, times: &[SpecialTime]) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let tx = c.transaction()?;
+        let mut ct = 0;
+        for time in times {
+            ct += tx.query("SELECT *
+                            FROM update_special_time($1, $2, $3, $4, $5)",
+                           &[&time.date, &time.kind, &time.hour, &time.minute, &time.adjustment])?
+                        .iter()
+                        .map(|r| r.get::<_, i32>(0))
+                        .next()
+                        .ok_or(Error::Other(format!("Failed to get count from update_special_time")))?;
+        }
+        tx.commit()?;
+        Ok(ct)
+    }
+
+    fn update_last_processed(&self, at: DateTime<Utc>) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        c.query("SELECT * FROM update_last_processed($1)", &[&at])?;
+        Ok(())
+    }
+
+    fn update_vacation(&self, vacation: Vacation) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        c.query("SELECT * FROM update_vacation($1)", &[&vacation.enabled])?;
+        Ok(())
+    }
+
+    fn update_pause(&self, pause: Pause) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        c.query("SELECT * FROM update_pause($1, $2)", &[&pause.paused, &pause.resume_at])?;
+        Ok(())
+    }
+
+    // **********
+    // DELETE
+    // **********
+    fn remove_switch(&self, id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT *
+                           FROM remove_switch($1)",
+                           &[&id])?
+                    .iter()
+                    .next()
+                    .ok_or(Error::new("Unable to get remove count"))
+                    .map(|row| row.get(0))?;
+        Ok(ret)
+    }
+
+    fn remove_flip(&self, id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT *
+                           FROM remove_flip($1)",
+                           &[&id])?
+                    .iter()
+                    .next()
+                    .ok_or(Error::new("Unable to get remove count"))
+                    .map(|row| row.get(0))?;
+        Ok(ret)
+    }
+
+    fn remove_group(&self, id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT *
+                           FROM remove_group($1)",
+                           &[&id])?
+                    .iter()
+                    .next()
+                    .ok_or(Error::new("Unable to get remove count"))
+                    .map(|row| row.get(0))?;
+        Ok(ret)
+    }
+
+    fn remove_group_member(&self, group_id: i32, switch_id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT *
+                           FROM remove_group_member($1, $2)",
+                           &[&group_id, &switch_id])?
+                    .iter()
+                    .next()
+                    .ok_or(Error::new("Unable to get remove count"))
+                    .map(|row| row.get(0))?;
+        Ok(ret)
+    }
+
+    fn remove_expired_flips(&self, date: NaiveDate) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT *
+                           FROM remove_expired_flips($1)",
+                           &[&date])?
+                    .iter()
+                    .next()
+                    .ok_or(Error::new("Unable to get remove count"))
+                    .map(|row| row.get(0))?;
+        Ok(ret)
+    }
+
+    fn remove_scene(&self, id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT *
+                           FROM remove_scene($1)",
+                           &[&id])?
+                    .iter()
+                    .next()
+                    .ok_or(Error::new("Unable to get remove count"))
+                    .map(|row| row.get(0))?;
+        Ok(ret)
+    }
+
+    fn remove_scene_member(&self, scene_id: i32, switch_id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT *
+                           FROM remove_scene_member($1, $2)",
+                           &[&scene_id, &switch_id])?
+                    .iter()
+                    .next()
+                    .ok_or(Error::new("Unable to get remove count"))
+                    .map(|row| row.get(0))?;
+        Ok(ret)
+    }
+
+    fn remove_pending_off(&self, id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT *
+                           FROM remove_pending_off($1)",
+                           &[&id])?
+                    .iter()
+                    .next()
+                    .ok_or(Error::new("Unable to get remove count"))
+                    .map(|row| row.get(0))?;
+        Ok(ret)
+    }
+
+    fn remove_hold(&self, switch_id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT *
+                           FROM remove_hold($1)",
+                           &[&switch_id])?
+                    .iter()
+                    .next()
+                    .ok_or(Error::new("Unable to get remove count"))
+                    .map(|row| row.get(0))?;
+        Ok(ret)
+    }
+
+    fn remove_expired_holds(&self, through: DateTime<Utc>) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query("SELECT *
+                           FROM remove_expired_holds($1)",
+                           &[&through])?
+                    .iter()
+                    .next()
+                    .ok_or(Error::new("Unable to get remove count"))
+                    .map(|row| row.get(0))?;
+        Ok(ret)
+    }
+}
+
+// **********
+// MAPPINGS
+// **********
+fn map_switch(row: Row) -> Switch {
+    Switch {
+        id: row.get(0),
+        name: row.get(1),
+        on_code: row.get(2),
+        off_code: row.get(3),
+    }
+}
+
+fn map_scheduled_flip(row: Row) -> ScheduledFlip {
+    ScheduledFlip {
+        id: row.get(0),
+        hour: row.get(1),
+        minute: row.get(2),
+        dow: row.get::<_, i32>(3).into(),
+        direction: row.get(4),
+        kind: row.get(5),
+        offset: row.get(9),
+        date: row.get(6),
+        season: season_from_columns(row.get(7), row.get(8)),
+        duration: row.get(10),
+    }
+}
+
+fn map_hold(row: Row) -> Hold {
+    Hold {
+        switch_id: row.get(0),
+        until: row.get(1),
+    }
+}
+
+fn map_pending_off(row: Row) -> PendingOff {
+    PendingOff {
+        id: row.get(0),
+        code: row.get(1),
+        due: row.get(2),
+    }
+}
+
+fn map_special_time(row: Row) -> SpecialTime {
+    SpecialTime {
+        date: row.get(0),
+        kind: row.get(1),
+        hour: row.get(2),
+        minute: row.get(3),
+        adjustment: row.get(4),
+    }
+}
+
+fn map_refresh(row: Row) -> Refresh {
+    Refresh {
+        id: row.get(0),
+        refreshed: row.get(1),
+        date: row.get(2),
+        source: row.get(3),
+        succeeded: row.get(4),
+        attempts: row.get(5),
+        message: row.get(6),
+    }
+}
+
+fn map_flip(row: Row) -> Flip {
+    Flip {
+        hour: row.get(0),
+        minute: row.get(1),
+        code: row.get(2),
+        duration: row.get(3),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config::{Config, Requirement};
+
+    #[test]
+    fn db_round_trip() {
+        let config = Config::load(&[Requirement::Database]).expect("failed to load config");
+        let db = Database::with_config(&config.database.url, config.database.pool())
+            .expect("failed to connect to database");
+        println!("Creating test switch");
+        let sw1 = db.new_switch("test switch", 44444, 55555).expect("failed to insert new switch");
+        println!("Updating test switch");
+        let sw2 = db.update_switch(sw1.id, "updated test switch", sw1.on_code, 99999).expect("failed to update switch");
+        println!("Checking switches don't match");
+        assert!(sw1 != sw2);
+        assert!(sw1.id == sw2.id);
+        println!("Creating new flip");
+        let fl1 = db.new_scheduled_flip(sw2.id, 10, 0, 64.into(), Direction::On, FlipKind::Custom, 0, None, None).expect("failed to insert new flip");
+        println!("Updating flip");
+        let fl2 = db.update_flip(fl1.id, fl1.hour, 30, 128.into(), Direction::Off, FlipKind::PreDawn, 0, None, None).expect("failed to update flip");
+        println!("Checking flips don't match");
+        assert!(fl1 != fl2);
+        assert!(fl1.id == fl2.id);
+        println!("Removing flip");
+        db.remove_flip(fl1.id).expect("failed to remove flip");
+        println!("Removing switch");
+        db.remove_switch(sw1.id).expect("failed to remove switch");
+    }
+}
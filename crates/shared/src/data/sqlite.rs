@@ -0,0 +1,1327 @@
+//! SQLite storage, for running on a single
+//! board without a postgres server. SQLite has
+//! no stored procedures so the logic that lives
+//! in `migrations/postgres` for postgres
+//! is done here instead
+use chrono::{
+    Datelike,
+    DateTime,
+    NaiveDate,
+    Utc,
+};
+use rusqlite::{
+    Connection,
+    OptionalExtension,
+    Row,
+    TransactionBehavior,
+    NO_PARAMS,
+    types::{
+        FromSql,
+        FromSqlError,
+        FromSqlResult,
+        ToSql,
+        ToSqlOutput,
+        ValueRef,
+    },
+};
+use uuid::Uuid;
+
+use std::{
+    path::Path,
+    str::FromStr,
+    sync::{
+        Arc,
+        Mutex,
+        MutexGuard,
+    },
+};
+
+use super::{
+    migrations::{
+        self,
+        BASELINE,
+    },
+    DayOfTheWeek,
+    Direction,
+    Flip,
+    FlipKind,
+    Group,
+    Hold,
+    MonthDay,
+    Pause,
+    PendingOff,
+    Refresh,
+    Scene,
+    SceneMember,
+    ScheduledFlip,
+    Season,
+    season_columns,
+    season_from_columns,
+    SpecialTime,
+    Store,
+    Switch,
+    Vacation,
+};
+use Error;
+
+/// A handle to a SQLite database file, SQLite
+/// only allows one writer at a time so all
+/// access goes through a single connection
+#[derive(Clone)]
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    /// Open (or create) the database at `path`
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteStore, Error> {
+        let conn = Connection::open(path)?;
+        Self::with_connection(conn)
+    }
+
+    /// A database that only lives as long
+    /// as this handle
+    pub fn open_in_memory() -> Result<SqliteStore, Error> {
+        let conn = Connection::open_in_memory()?;
+        Self::with_connection(conn)
+    }
+
+    fn with_connection(conn: Connection) -> Result<SqliteStore, Error> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        Ok(SqliteStore {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    fn get_connection(&self) -> Result<MutexGuard<'_, Connection>, Error> {
+        self.conn.lock().map_err(|_| Error::new("sqlite connection lock poisoned"))
+    }
+
+    fn group_members(c: &Connection, group_id: i32) -> Result<Vec<Switch>, Error> {
+        let mut stmt = c.prepare("SELECT s.id, s.name, s.on_code, s.off_code
+                                  FROM switch AS s
+                                      JOIN group_member AS m
+                                      ON s.id = m.switch_id
+                                  WHERE m.group_id = ?1
+                                  ORDER BY s.id")?;
+        let ret = stmt.query_map(params![group_id], map_switch)?
+            .collect::<Result<Vec<Switch>, _>>()?;
+        Ok(ret)
+    }
+
+    fn scene_members(c: &Connection, scene_id: i32) -> Result<Vec<SceneMember>, Error> {
+        let mut stmt = c.prepare("SELECT s.id, s.name, s.on_code, s.off_code, m.direction
+                                  FROM switch AS s
+                                      JOIN scene_member AS m
+                                      ON s.id = m.switch_id
+                                  WHERE m.scene_id = ?1
+                                  ORDER BY s.id")?;
+        let ret = stmt.query_map(params![scene_id], |r| Ok(SceneMember {
+                switch: map_switch(r)?,
+                direction: r.get(4)?,
+            }))?
+            .collect::<Result<Vec<SceneMember>, _>>()?;
+        Ok(ret)
+    }
+
+    fn scheduled_flips(c: &Connection, column: &str, id: i32) -> Result<Vec<ScheduledFlip>, Error> {
+        let mut stmt = c.prepare(&format!("SELECT id, hour, minute, dow, direction, kind, date,
+                                           season_start, season_end, offset_minutes, duration
+                                           FROM flip
+                                           WHERE {} = ?1
+                                           ORDER BY hour, minute", column))?;
+        let ret = stmt.query_map(params![id], map_scheduled_flip)?
+            .collect::<Result<Vec<ScheduledFlip>, _>>()?;
+        Ok(ret)
+    }
+}
+
+// Solar flips take the special time nearest the date,
+// plus their offset, and keep their own time when
+// nothing has been recorded yet
+const DAY_FLIPS: &str = "SELECT minutes / 60 AS hour, minutes % 60 AS minute, code, duration
+                         FROM (
+                         SELECT COALESCE((
+                             SELECT ((st.hour * 60 + st.minute + f.offset_minutes) % 1440 + 1440) % 1440
+                             FROM special_time AS st
+                             WHERE st.kind = f.kind
+                               AND f.kind <> 'Custom'
+                             ORDER BY abs(julianday(st.date) - julianday(?2)), st.date DESC
+                             LIMIT 1
+                         ), f.hour * 60 + f.minute) AS minutes,
+                         CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+                             s.off_code
+                         ELSE
+                             s.on_code
+                         END AS code,
+                         CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+                             NULL
+                         ELSE
+                             f.duration
+                         END AS duration
+                         FROM flip AS f
+                             LEFT JOIN group_member AS m
+                             ON f.group_id = m.group_id
+                             LEFT JOIN scene_member AS sm
+                             ON f.scene_id = sm.scene_id
+                             JOIN switch AS s
+                             ON s.id = COALESCE(f.switch_id, m.switch_id, sm.switch_id)
+                         WHERE CASE WHEN f.date IS NULL THEN
+                             f.dow & ?1 > 0
+                         ELSE
+                             f.date = ?2
+                         END
+                         AND CASE WHEN f.season_start IS NULL THEN
+                             1
+                         WHEN f.season_start <= f.season_end THEN
+                             ?3 BETWEEN f.season_start AND f.season_end
+                         ELSE
+                             ?3 >= f.season_start OR ?3 <= f.season_end
+                         END
+                         )";
+
+impl Store for SqliteStore {
+    fn migrate(&self) -> Result<Vec<i32>, Error> {
+        let mut c = self.get_connection()?;
+        // every binary migrates on boot, holding the whole
+        // file keeps two of them from applying the same migration
+        let tx = c.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(migrations::SQLITE_VERSION_TABLE)?;
+        let current: Option<i32> = tx.query_row("SELECT MAX(version)
+                                                FROM schema_version",
+                                               NO_PARAMS,
+                                               |r| r.get(0))?;
+        let current = match current {
+            Some(version) => version,
+            None => {
+                let existing: bool = tx.query_row("SELECT COUNT(*) > 0
+                                                  FROM sqlite_master
+                                                  WHERE type = 'table'
+                                                  AND name = 'switch'",
+                                                 NO_PARAMS,
+                                                 |r| r.get(0))?;
+                if existing {
+                    tx.execute("INSERT INTO schema_version (version, name)
+                                VALUES (?1, 'baseline')",
+                               params![BASELINE])?;
+                    BASELINE
+                } else {
+                    0
+                }
+            }
+        };
+        let mut applied = Vec::new();
+        for m in migrations::SQLITE.iter().filter(|m| m.version > current) {
+            tx.execute_batch(m.up)?;
+            tx.execute("INSERT INTO schema_version (version, name)
+                        VALUES (?1, ?2)",
+                       params![m.version, m.name])?;
+            applied.push(m.version);
+        }
+        tx.commit()?;
+        Ok(applied)
+    }
+
+    // **********
+    // CREATE
+    // **********
+    fn new_switch(&self, name: &str, on_code: i32, off_code: i32) -> Result<Switch, Error> {
+        let c = self.get_connection()?;
+        c.execute("INSERT INTO switch (name, on_code, off_code)
+                   VALUES (?1, ?2, ?3)",
+                  params![name, on_code, off_code])?;
+        Ok(Switch {
+            id: c.last_insert_rowid() as i32,
+            name: name.to_string(),
+            on_code,
+            off_code,
+        })
+    }
+
+    fn new_scheduled_flip(&self, sw_id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind, offset: i32, duration: Option<i32>, season: Option<Season>) -> Result<ScheduledFlip, Error> {
+        let c = self.get_connection()?;
+        let dow_int: i32 = dow.into();
+        let (start, end) = season_columns(season);
+        c.execute("INSERT INTO flip (switch_id, hour, minute, dow, direction, kind, offset_minutes, duration, season_start, season_end)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                  params![sw_id, hour, minute, dow_int, direction, kind, offset, duration, start, end])?;
+        Ok(ScheduledFlip {
+            id: c.last_insert_rowid() as i32,
+            hour,
+            minute,
+            dow,
+            direction,
+            kind,
+            offset,
+            date: None,
+            season,
+            duration,
+        })
+    }
+
+    fn new_dated_flip(&self, sw_id: i32, date: NaiveDate, hour: i32, minute: i32, direction: Direction, kind: FlipKind) -> Result<ScheduledFlip, Error> {
+        let c = self.get_connection()?;
+        c.execute("INSERT INTO flip (switch_id, date, hour, minute, dow, direction, kind)
+                   VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6)",
+                  params![sw_id, date, hour, minute, direction, kind])?;
+        Ok(ScheduledFlip {
+            id: c.last_insert_rowid() as i32,
+            hour,
+            minute,
+            dow: 0.into(),
+            direction,
+            kind,
+            offset: 0,
+            date: Some(date),
+            season: None,
+            duration: None,
+        })
+    }
+
+    fn new_group(&self, name: &str) -> Result<Group, Error> {
+        let c = self.get_connection()?;
+        c.execute("INSERT INTO switch_group (name)
+                   VALUES (?1)",
+                  params![name])?;
+        Ok(Group {
+            id: c.last_insert_rowid() as i32,
+            name: name.to_string(),
+            switches: Vec::new(),
+        })
+    }
+
+    fn add_group_member(&self, group_id: i32, switch_id: i32) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        c.execute("INSERT OR IGNORE INTO group_member (group_id, switch_id)
+                   VALUES (?1, ?2)",
+                  params![group_id, switch_id])?;
+        Ok(())
+    }
+
+    fn new_group_flip(&self, group_id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind) -> Result<ScheduledFlip, Error> {
+        let c = self.get_connection()?;
+        let dow_int: i32 = dow.into();
+        c.execute("INSERT INTO flip (group_id, hour, minute, dow, direction, kind)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                  params![group_id, hour, minute, dow_int, direction, kind])?;
+        Ok(ScheduledFlip {
+            id: c.last_insert_rowid() as i32,
+            hour,
+            minute,
+            dow,
+            direction,
+            kind,
+            offset: 0,
+            date: None,
+            season: None,
+            duration: None,
+        })
+    }
+
+    fn new_scene(&self, name: &str) -> Result<Scene, Error> {
+        let c = self.get_connection()?;
+        c.execute("INSERT INTO scene (name)
+                   VALUES (?1)",
+                  params![name])?;
+        Ok(Scene {
+            id: c.last_insert_rowid() as i32,
+            name: name.to_string(),
+            members: Vec::new(),
+        })
+    }
+
+    fn set_scene_member(&self, scene_id: i32, switch_id: i32, direction: Direction) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        c.execute("INSERT OR REPLACE INTO scene_member (scene_id, switch_id, direction)
+                   VALUES (?1, ?2, ?3)",
+                  params![scene_id, switch_id, direction])?;
+        Ok(())
+    }
+
+    fn new_scene_flip(&self, scene_id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind) -> Result<ScheduledFlip, Error> {
+        let c = self.get_connection()?;
+        let dow_int: i32 = dow.into();
+        c.execute("INSERT INTO flip (scene_id, hour, minute, dow, direction, kind)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                  params![scene_id, hour, minute, dow_int, direction, kind])?;
+        Ok(ScheduledFlip {
+            id: c.last_insert_rowid() as i32,
+            hour,
+            minute,
+            dow,
+            direction,
+            kind,
+            offset: 0,
+            date: None,
+            season: None,
+            duration: None,
+        })
+    }
+
+    fn new_auth(&self) -> Result<Uuid, Error> {
+        let c = self.get_connection()?;
+        let token = Uuid::new_v4();
+        c.execute("INSERT INTO authorize (token, issued)
+                   VALUES (?1, ?2)",
+                  params![token.hyphenated().to_string(), Utc::now()])?;
+        Ok(token)
+    }
+
+    fn new_token(&self, private: &[u8], shared: &[u8], token: Uuid) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        let auth_id: i32 = c.query_row("SELECT id
+                                        FROM authorize
+                                        WHERE token = ?1",
+                                       params![token.hyphenated().to_string()],
+                                       |r| r.get(0))
+            .optional()?
+            .ok_or(Error::new("Token not found"))?;
+        c.execute("INSERT INTO token (private, shared, auth_id)
+                   VALUES (?1, ?2, ?3)",
+                  params![private, shared, auth_id])?;
+        Ok(())
+    }
+
+    fn new_refresh(&self, date: NaiveDate, source: &str, succeeded: bool, attempts: i32, message: Option<&str>) -> Result<Refresh, Error> {
+        let c = self.get_connection()?;
+        c.execute("INSERT INTO refresh_log (refreshed, date, source, succeeded, attempts, message)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                  params![Utc::now(), date, source, succeeded, attempts, message])?;
+        let ret = c.query_row("SELECT id, refreshed, date, source, succeeded, attempts, message
+                               FROM refresh_log
+                               WHERE id = ?1",
+                              params![c.last_insert_rowid()],
+                              map_refresh)?;
+        Ok(ret)
+    }
+
+    fn new_pending_off(&self, code: i32, due: DateTime<Utc>) -> Result<PendingOff, Error> {
+        let c = self.get_connection()?;
+        c.execute("INSERT INTO pending_off (code, due)
+                   VALUES (?1, ?2)",
+                  params![code, due])?;
+        Ok(PendingOff {
+            id: c.last_insert_rowid() as i32,
+            code,
+            due,
+        })
+    }
+
+    fn new_hold(&self, switch_id: i32, until: Option<DateTime<Utc>>) -> Result<Hold, Error> {
+        let c = self.get_connection()?;
+        c.execute("INSERT OR REPLACE INTO switch_hold (switch_id, until)
+                   VALUES (?1, ?2)",
+                  params![switch_id, until])?;
+        Ok(Hold {
+            switch_id,
+            until,
+        })
+    }
+
+    // **********
+    // READ
+    // **********
+    fn get_flips_for_minute(&self, hour: i32, minute: i32, date: NaiveDate) -> Result<Vec<Flip>, Error> {
+        let c = self.get_connection()?;
+        let dow: i32 = DayOfTheWeek::from(date.weekday()).into();
+        let day: i32 = MonthDay::from(date).into();
+        let mut stmt = c.prepare(&format!("{}
+                                           WHERE hour = ?4
+                                           AND minute = ?5", DAY_FLIPS))?;
+        let ret = stmt.query_map(params![dow, date, day, hour, minute], map_flip)?
+            .collect::<Result<Vec<Flip>, _>>()?;
+        Ok(ret)
+    }
+
+    fn get_flips_for_day(&self, date: NaiveDate) -> Result<Vec<Flip>, Error> {
+        let c = self.get_connection()?;
+        let dow: i32 = DayOfTheWeek::from(date.weekday()).into();
+        let day: i32 = MonthDay::from(date).into();
+        let mut stmt = c.prepare(DAY_FLIPS)?;
+        let ret = stmt.query_map(params![dow, date, day], map_flip)?
+            .collect::<Result<Vec<Flip>, _>>()?;
+        Ok(ret)
+    }
+
+    fn get_special_times(&self, date: NaiveDate) -> Result<Vec<SpecialTime>, Error> {
+        let c = self.get_connection()?;
+        let mut stmt = c.prepare("SELECT date, kind, hour, minute, adjustment
+                                  FROM special_time AS st
+                                  WHERE st.id = (
+                                      SELECT id
+                                      FROM special_time
+                                      WHERE kind = st.kind
+                                        AND hour IS NOT NULL
+                                        AND minute IS NOT NULL
+                                      ORDER BY abs(julianday(date) - julianday(?1)), date DESC
+                                      LIMIT 1
+                                  )")?;
+        let ret = stmt.query_map(params![date], map_special_time)?
+            .collect::<Result<Vec<SpecialTime>, _>>()?;
+        Ok(ret)
+    }
+
+    fn get_latest_special_date(&self) -> Result<Option<NaiveDate>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query_row("SELECT MAX(date)
+                               FROM special_time
+                               WHERE hour IS NOT NULL
+                                 AND minute IS NOT NULL",
+                              NO_PARAMS,
+                              |r| r.get(0))?;
+        Ok(ret)
+    }
+
+    fn get_last_refresh(&self) -> Result<Option<Refresh>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query_row("SELECT id, refreshed, date, source, succeeded, attempts, message
+                               FROM refresh_log
+                               ORDER BY refreshed DESC, id DESC
+                               LIMIT 1",
+                              NO_PARAMS,
+                              map_refresh)
+            .optional()?;
+        Ok(ret)
+    }
+
+    fn get_last_processed(&self) -> Result<Option<DateTime<Utc>>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query_row("SELECT last_processed
+                               FROM schedule_state
+                               WHERE id = 1",
+                              NO_PARAMS,
+                              |r| r.get(0))
+            .optional()?;
+        Ok(ret)
+    }
+
+    fn get_vacation(&self) -> Result<Vacation, Error> {
+        let c = self.get_connection()?;
+        let enabled = c.query_row("SELECT enabled
+                                   FROM vacation
+                                   WHERE id = 1",
+                                  NO_PARAMS,
+                                  |r| r.get(0))
+            .optional()?;
+        Ok(Vacation {
+            enabled: enabled.unwrap_or(false),
+        })
+    }
+
+    fn get_pending_offs(&self) -> Result<Vec<PendingOff>, Error> {
+        let c = self.get_connection()?;
+        let mut stmt = c.prepare("SELECT id, code, due
+                                  FROM pending_off
+                                  ORDER BY due, id")?;
+        let ret = stmt.query_map(NO_PARAMS, map_pending_off)?
+            .collect::<Result<Vec<PendingOff>, _>>()?;
+        Ok(ret)
+    }
+
+    fn get_pause(&self) -> Result<Pause, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query_row("SELECT paused, resume_at
+                               FROM schedule_pause
+                               WHERE id = 1",
+                              NO_PARAMS,
+                              |r| Ok(Pause {
+                                  paused: r.get(0)?,
+                                  resume_at: r.get(1)?,
+                              }))
+            .optional()?;
+        Ok(ret.unwrap_or_default())
+    }
+
+    fn get_holds(&self) -> Result<Vec<Hold>, Error> {
+        let c = self.get_connection()?;
+        let mut stmt = c.prepare("SELECT switch_id, until
+                                  FROM switch_hold
+                                  ORDER BY switch_id")?;
+        let ret = stmt.query_map(NO_PARAMS, map_hold)?
+            .collect::<Result<Vec<Hold>, _>>()?;
+        Ok(ret)
+    }
+
+    fn get_all_switches(&self) -> Result<Vec<Switch>, Error> {
+        let c = self.get_connection()?;
+        let mut stmt = c.prepare("SELECT id, name, on_code, off_code
+                                  FROM switch")?;
+        let ret = stmt.query_map(NO_PARAMS, map_switch)?
+            .collect::<Result<Vec<Switch>, _>>()?;
+        Ok(ret)
+    }
+
+    fn get_flips_for_switch(&self, switch_id: i32) -> Result<Vec<ScheduledFlip>, Error> {
+        let c = self.get_connection()?;
+        Self::scheduled_flips(&c, "switch_id", switch_id)
+    }
+
+    fn get_flip(&self, id: i32) -> Result<ScheduledFlip, Error> {
+        let c = self.get_connection()?;
+        Self::scheduled_flips(&c, "id", id)?
+            .pop()
+            .ok_or(Error::Other(format!("flip {} does not exist", id)))
+    }
+
+    fn get_all_groups(&self) -> Result<Vec<Group>, Error> {
+        let c = self.get_connection()?;
+        let mut stmt = c.prepare("SELECT id, name
+                                  FROM switch_group
+                                  ORDER BY name")?;
+        let rows = stmt.query_map(NO_PARAMS, |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<Result<Vec<(i32, String)>, _>>()?;
+        let mut ret = Vec::with_capacity(rows.len());
+        for (id, name) in rows {
+            ret.push(Group {
+                id,
+                name,
+                switches: Self::group_members(&c, id)?,
+            });
+        }
+        Ok(ret)
+    }
+
+    fn get_group(&self, group_id: i32) -> Result<Group, Error> {
+        let c = self.get_connection()?;
+        let name: String = c.query_row("SELECT name
+                                        FROM switch_group
+                                        WHERE id = ?1",
+                                       params![group_id],
+                                       |r| r.get(0))
+            .optional()?
+            .ok_or(Error::Other(format!("group {} does not exist", group_id)))?;
+        Ok(Group {
+            id: group_id,
+            name,
+            switches: Self::group_members(&c, group_id)?,
+        })
+    }
+
+    fn get_flips_for_group(&self, group_id: i32) -> Result<Vec<ScheduledFlip>, Error> {
+        let c = self.get_connection()?;
+        Self::scheduled_flips(&c, "group_id", group_id)
+    }
+
+    fn get_all_scenes(&self) -> Result<Vec<Scene>, Error> {
+        let c = self.get_connection()?;
+        let mut stmt = c.prepare("SELECT id, name
+                                  FROM scene
+                                  ORDER BY name")?;
+        let rows = stmt.query_map(NO_PARAMS, |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<Result<Vec<(i32, String)>, _>>()?;
+        let mut ret = Vec::with_capacity(rows.len());
+        for (id, name) in rows {
+            ret.push(Scene {
+                id,
+                name,
+                members: Self::scene_members(&c, id)?,
+            });
+        }
+        Ok(ret)
+    }
+
+    fn get_scene(&self, scene_id: i32) -> Result<Scene, Error> {
+        let c = self.get_connection()?;
+        let name: String = c.query_row("SELECT name
+                                        FROM scene
+                                        WHERE id = ?1",
+                                       params![scene_id],
+                                       |r| r.get(0))
+            .optional()?
+            .ok_or(Error::Other(format!("scene {} does not exist", scene_id)))?;
+        Ok(Scene {
+            id: scene_id,
+            name,
+            members: Self::scene_members(&c, scene_id)?,
+        })
+    }
+
+    fn get_flips_for_scene(&self, scene_id: i32) -> Result<Vec<ScheduledFlip>, Error> {
+        let c = self.get_connection()?;
+        Self::scheduled_flips(&c, "scene_id", scene_id)
+    }
+
+    fn get_auth_age(&self, token: &Uuid) -> Result<DateTime<Utc>, Error> {
+        let c = self.get_connection()?;
+        let ret = c.query_row("SELECT a.issued
+                               FROM authorize a
+                                   LEFT JOIN token t
+                                   ON a.id = t.auth_id
+                               WHERE a.token = ?1
+                               AND t.id IS NULL",
+                              params![token.hyphenated().to_string()],
+                              |r| r.get(0))
+            .optional()?
+            .ok_or(Error::new("Token not found"))?;
+        Ok(ret)
+    }
+
+    fn get_private_shared(&self, token: &Uuid) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let c = self.get_connection()?;
+        let ret = c.query_row("SELECT t.private, t.shared
+                               FROM token t
+                                   JOIN authorize a
+                                   ON t.auth_id = a.id
+                               WHERE a.token = ?1",
+                              params![token.hyphenated().to_string()],
+                              |r| Ok((r.get(0)?, r.get(1)?)))
+            .optional()?
+            .ok_or(Error::new("Unauthorized token provided"))?;
+        Ok(ret)
+    }
+
+    fn check_token(&self, token: &Uuid, shared: &[u8]) -> Result<bool, Error> {
+        let c = self.get_connection()?;
+        let db_shared: Option<Vec<u8>> = c.query_row("SELECT t.shared
+                                                      FROM token t
+                                                          JOIN authorize a
+                                                          ON t.auth_id = a.id
+                                                      WHERE a.token = ?1",
+                                                     params![token.hyphenated().to_string()],
+                                                     |r| r.get(0))
+            .optional()?;
+        Ok(db_shared.map(|s| s.as_slice() == shared).unwrap_or(false))
+    }
+
+    // **********
+    // UPDATE
+    // **********
+    fn update_switch(&self, id: i32, name: &str, on_code: i32, off_code: i32) -> Result<Switch, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("UPDATE switch
+                            SET name = ?2,
+                            on_code = ?3,
+                            off_code = ?4
+                            WHERE id = ?1",
+                           params![id, name, on_code, off_code])?;
+        if ct == 0 {
+            return Err(Error::new("Nothing returned from switch update"));
+        }
+        Ok(Switch {
+            id,
+            name: name.to_string(),
+            on_code,
+            off_code,
+        })
+    }
+
+    fn update_flip(&self, id: i32, hour: i32, minute: i32, dow: DayOfTheWeek, direction: Direction, kind: FlipKind, offset: i32, duration: Option<i32>, season: Option<Season>) -> Result<ScheduledFlip, Error> {
+        let c = self.get_connection()?;
+        let dow_int: i32 = dow.into();
+        let (start, end) = season_columns(season);
+        let ct = c.execute("UPDATE flip
+                            SET hour = ?2,
+                            minute = ?3,
+                            dow = ?4,
+                            direction = ?5,
+                            kind = ?6,
+                            offset_minutes = ?7,
+                            duration = ?8,
+                            season_start = ?9,
+                            season_end = ?10
+                            WHERE id = ?1",
+                           params![id, hour, minute, dow_int, direction, kind, offset, duration, start, end])?;
+        if ct == 0 {
+            return Err(Error::new("Nothing returned from flip update"));
+        }
+        let ret = c.query_row("SELECT id, hour, minute, dow, direction, kind, date,
+                               season_start, season_end, offset_minutes, duration
+                               FROM flip
+                               WHERE id = ?1", params![id], map_scheduled_flip)?;
+        Ok(ret)
+    }
+
+    fn update_group(&self, id: i32, name: &str) -> Result<Group, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("UPDATE switch_group
+                            SET name = ?2
+                            WHERE id = ?1",
+                           params![id, name])?;
+        if ct == 0 {
+            return Err(Error::new("Nothing returned from group update"));
+        }
+        Ok(Group {
+            id,
+            name: name.to_string(),
+            switches: Self::group_members(&c, id)?,
+        })
+    }
+
+    fn update_scene(&self, id: i32, name: &str) -> Result<Scene, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("UPDATE scene
+                            SET name = ?2
+                            WHERE id = ?1",
+                           params![id, name])?;
+        if ct == 0 {
+            return Err(Error::new("Nothing returned from scene update"));
+        }
+        Ok(Scene {
+            id,
+            name: name.to_string(),
+            members: Self::scene_members(&c, id)?,
+        })
+    }
+
+    fn update_special_times(&self, times: &[SpecialTime]) -> Result<i32, Error> {
+        let mut c = self.get_connection()?;
+        let tx = c.transaction()?;
+        let mut ct = 0;
+        for time in times {
+            ct += tx.execute("INSERT OR REPLACE INTO special_time (date, kind, hour, minute, adjustment)
+                              VALUES (?1, ?2, ?3, ?4, ?5)",
+                             params![time.date, time.kind, time.hour, time.minute, time.adjustment])?;
+        }
+        tx.commit()?;
+        Ok(ct as i32)
+    }
+
+    fn update_last_processed(&self, at: DateTime<Utc>) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        c.execute("INSERT OR REPLACE INTO schedule_state (id, last_processed)
+                   VALUES (1, ?1)",
+                  params![at])?;
+        Ok(())
+    }
+
+    fn update_vacation(&self, vacation: Vacation) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        c.execute("INSERT OR REPLACE INTO vacation (id, enabled)
+                   VALUES (1, ?1)",
+                  params![vacation.enabled])?;
+        Ok(())
+    }
+
+    fn update_pause(&self, pause: Pause) -> Result<(), Error> {
+        let c = self.get_connection()?;
+        c.execute("INSERT OR REPLACE INTO schedule_pause (id, paused, resume_at)
+                   VALUES (1, ?1, ?2)",
+                  params![pause.paused, pause.resume_at])?;
+        Ok(())
+    }
+
+    // **********
+    // DELETE
+    // **********
+    fn remove_switch(&self, id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("DELETE FROM switch WHERE id = ?1", params![id])?;
+        Ok(ct as i32)
+    }
+
+    fn remove_flip(&self, id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("DELETE FROM flip WHERE id = ?1", params![id])?;
+        Ok(ct as i32)
+    }
+
+    fn remove_group(&self, id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("DELETE FROM switch_group WHERE id = ?1", params![id])?;
+        Ok(ct as i32)
+    }
+
+    fn remove_group_member(&self, group_id: i32, switch_id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("DELETE FROM group_member
+                            WHERE group_id = ?1
+                            AND switch_id = ?2",
+                           params![group_id, switch_id])?;
+        Ok(ct as i32)
+    }
+
+    fn remove_expired_flips(&self, date: NaiveDate) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("DELETE FROM flip WHERE date < ?1", params![date])?;
+        Ok(ct as i32)
+    }
+
+    fn remove_scene(&self, id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("DELETE FROM scene WHERE id = ?1", params![id])?;
+        Ok(ct as i32)
+    }
+
+    fn remove_scene_member(&self, scene_id: i32, switch_id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("DELETE FROM scene_member
+                            WHERE scene_id = ?1
+                            AND switch_id = ?2",
+                           params![scene_id, switch_id])?;
+        Ok(ct as i32)
+    }
+
+    fn remove_pending_off(&self, id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("DELETE FROM pending_off WHERE id = ?1", params![id])?;
+        Ok(ct as i32)
+    }
+
+    fn remove_hold(&self, switch_id: i32) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("DELETE FROM switch_hold WHERE switch_id = ?1", params![switch_id])?;
+        Ok(ct as i32)
+    }
+
+    fn remove_expired_holds(&self, through: DateTime<Utc>) -> Result<i32, Error> {
+        let c = self.get_connection()?;
+        let ct = c.execute("DELETE FROM switch_hold WHERE until <= ?1", params![through])?;
+        Ok(ct as i32)
+    }
+}
+
+// **********
+// MAPPINGS
+// **********
+fn map_switch(row: &Row) -> Result<Switch, ::rusqlite::Error> {
+    Ok(Switch {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        on_code: row.get(2)?,
+        off_code: row.get(3)?,
+    })
+}
+
+fn map_scheduled_flip(row: &Row) -> Result<ScheduledFlip, ::rusqlite::Error> {
+    Ok(ScheduledFlip {
+        id: row.get(0)?,
+        hour: row.get(1)?,
+        minute: row.get(2)?,
+        dow: row.get::<_, i32>(3)?.into(),
+        direction: row.get(4)?,
+        kind: row.get(5)?,
+        offset: row.get(9)?,
+        date: row.get(6)?,
+        season: season_from_columns(row.get(7)?, row.get(8)?),
+        duration: row.get(10)?,
+    })
+}
+
+fn map_hold(row: &Row) -> Result<Hold, ::rusqlite::Error> {
+    Ok(Hold {
+        switch_id: row.get(0)?,
+        until: row.get(1)?,
+    })
+}
+
+fn map_pending_off(row: &Row) -> Result<PendingOff, ::rusqlite::Error> {
+    Ok(PendingOff {
+        id: row.get(0)?,
+        code: row.get(1)?,
+        due: row.get(2)?,
+    })
+}
+
+fn map_special_time(row: &Row) -> Result<SpecialTime, ::rusqlite::Error> {
+    Ok(SpecialTime {
+        date: row.get(0)?,
+        kind: row.get(1)?,
+        hour: row.get(2)?,
+        minute: row.get(3)?,
+        adjustment: row.get(4)?,
+    })
+}
+
+fn map_refresh(row: &Row) -> Result<Refresh, ::rusqlite::Error> {
+    Ok(Refresh {
+        id: row.get(0)?,
+        refreshed: row.get(1)?,
+        date: row.get(2)?,
+        source: row.get(3)?,
+        succeeded: row.get(4)?,
+        attempts: row.get(5)?,
+        message: row.get(6)?,
+    })
+}
+
+fn map_flip(row: &Row) -> Result<Flip, ::rusqlite::Error> {
+    Ok(Flip {
+        hour: row.get(0)?,
+        minute: row.get(1)?,
+        code: row.get(2)?,
+        duration: row.get(3)?,
+    })
+}
+
+// Enums are stored as their variant name, the
+// same text postgres uses for its enum types
+impl ToSql for FlipKind {
+    fn to_sql(&self) -> ::rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for FlipKind {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        FlipKind::from_str(value.as_str()?).map_err(|_| FromSqlError::InvalidType)
+    }
+}
+
+impl ToSql for Direction {
+    fn to_sql(&self) -> ::rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Direction {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        Direction::from_str(value.as_str()?).map_err(|_| FromSqlError::InvalidType)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{
+        Duration,
+        TimeZone,
+    };
+
+    fn monday() -> NaiveDate {
+        NaiveDate::from_ymd(2018, 10, 1)
+    }
+
+    fn solar_day(date: NaiveDate, predawn: (i32, i32), sunrise: (i32, i32),
+                 dusk: (i32, i32), sunset: (i32, i32)) -> Vec<SpecialTime> {
+        vec![
+            (FlipKind::PreDawn, predawn),
+            (FlipKind::Sunrise, sunrise),
+            (FlipKind::Dusk, dusk),
+            (FlipKind::Sunset, sunset),
+        ].into_iter()
+            .map(|(kind, (hour, minute))| SpecialTime { date, kind, hour, minute, adjustment: 0 })
+            .collect()
+    }
+
+    #[test]
+    fn sqlite_round_trip() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        let sw = db.new_switch("porch", 1, 2).unwrap();
+        let other = db.new_switch("tree", 3, 4).unwrap();
+        let on = db.new_scheduled_flip(sw.id, 7, 0, 31.into(), Direction::On, FlipKind::Custom, 0, None, None).unwrap();
+        let sunset = db.new_scheduled_flip(sw.id, 0, 0, 127.into(), Direction::Off, FlipKind::Sunset, 0, None, None).unwrap();
+        db.new_scheduled_flip(other.id, 8, 0, 64.into(), Direction::On, FlipKind::Custom, 0, None, None).unwrap();
+        assert_eq!(db.get_flips_for_day(monday()).unwrap().len(), 2);
+        assert_eq!(db.get_flips_for_minute(7, 0, monday()).unwrap(), vec![Flip { hour: 7, minute: 0, code: 1, duration: None }]);
+        assert_eq!(db.update_special_times(&solar_day(monday(), (6, 0), (7, 0), (16, 30), (17, 30))).unwrap(), 4);
+        assert_eq!(db.get_flips_for_minute(17, 30, monday()).unwrap(), vec![Flip { hour: 17, minute: 30, code: 2, duration: None }]);
+        let flips = db.get_flips_for_switch(sw.id).unwrap();
+        assert_eq!(flips[0].id, sunset.id);
+        assert_eq!(flips[1].id, on.id);
+        db.update_flip(on.id, 6, 45, 31.into(), Direction::On, FlipKind::Custom, 0, None, None).unwrap();
+        assert!(db.update_flip(-1, 6, 45, 31.into(), Direction::On, FlipKind::Custom, 0, None, None).is_err());
+        assert_eq!(db.remove_switch(sw.id).unwrap(), 1);
+        assert!(db.get_flips_for_switch(sw.id).unwrap().is_empty());
+        assert_eq!(db.get_all_switches().unwrap(), vec![other]);
+    }
+
+    #[test]
+    fn sqlite_solar_offsets() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        let sw = db.new_switch("porch", 1, 2).unwrap();
+        let before_sunset = db.new_scheduled_flip(sw.id, 0, 0, 127.into(), Direction::On, FlipKind::Sunset, -20, None, None).unwrap();
+        let before_predawn = db.new_scheduled_flip(sw.id, 0, 0, 127.into(), Direction::On, FlipKind::PreDawn, -30, None, None).unwrap();
+        assert_eq!(db.update_special_times(&solar_day(monday(), (0, 10), (7, 10), (16, 45), (17, 45))).unwrap(), 4);
+        assert_eq!(db.get_flips_for_minute(17, 25, monday()).unwrap().len(), 1);
+        assert_eq!(db.get_flips_for_minute(23, 40, monday()).unwrap().len(), 1);
+        let updated = db.update_flip(before_sunset.id, 0, 0, 127.into(), Direction::On, FlipKind::Sunset, 15, None, None).unwrap();
+        assert_eq!(updated.offset, 15);
+        assert_eq!(db.get_flips_for_minute(18, 0, monday()).unwrap().len(), 1);
+        // the flip rows keep their own time
+        let flips = db.get_flips_for_switch(sw.id).unwrap();
+        assert!(flips.iter().all(|f| (f.hour, f.minute) == (0, 0)));
+        assert_eq!(flips.iter().find(|f| f.id == before_predawn.id).unwrap().offset, -30);
+    }
+
+    #[test]
+    fn sqlite_nearest_special_time() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        let sw = db.new_switch("porch", 1, 2).unwrap();
+        db.new_scheduled_flip(sw.id, 18, 0, 127.into(), Direction::On, FlipKind::Sunset, 0, None, None).unwrap();
+        assert_eq!(db.get_flips_for_minute(18, 0, monday()).unwrap().len(), 1);
+        let first = NaiveDate::from_ymd(2026, 10, 1);
+        let later = NaiveDate::from_ymd(2026, 10, 5);
+        db.update_special_times(&solar_day(first, (6, 0), (7, 0), (17, 30), (18, 30))).unwrap();
+        db.update_special_times(&solar_day(later, (6, 5), (7, 5), (17, 20), (18, 20))).unwrap();
+        db.update_special_times(&solar_day(later, (6, 6), (7, 6), (17, 21), (18, 21))).unwrap();
+        let special = db.get_special_times(later).unwrap();
+        assert_eq!(special.len(), 4);
+        assert!(special.iter().all(|s| s.date == later));
+        let time = |date: NaiveDate| {
+            let flip = &db.get_flips_for_day(date).unwrap()[0];
+            (flip.hour, flip.minute)
+        };
+        assert_eq!(time(NaiveDate::from_ymd(2026, 9, 1)), (18, 30));
+        assert_eq!(time(NaiveDate::from_ymd(2026, 10, 2)), (18, 30));
+        assert_eq!(time(NaiveDate::from_ymd(2026, 10, 3)), (18, 21));
+        assert_eq!(time(NaiveDate::from_ymd(2026, 12, 25)), (18, 21));
+    }
+
+    #[test]
+    fn sqlite_twilight_kinds() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        let sw = db.new_switch("porch", 1, 2).unwrap();
+        let dusk = db.new_scheduled_flip(sw.id, 0, 0, 127.into(), Direction::On, FlipKind::NauticalDusk, -5, None, None).unwrap();
+        assert_eq!(dusk.kind, FlipKind::NauticalDusk);
+        db.new_scheduled_flip(sw.id, 0, 0, 127.into(), Direction::Off, FlipKind::CivilDawn, 0, None, None).unwrap();
+        let times = vec![
+            SpecialTime { date: monday(), kind: FlipKind::NauticalDusk, hour: 19, minute: 5, adjustment: 0 },
+            SpecialTime { date: monday(), kind: FlipKind::CivilDawn, hour: 6, minute: 31, adjustment: 0 },
+        ];
+        assert_eq!(db.update_special_times(&times).unwrap(), 2);
+        assert_eq!(db.get_flips_for_minute(19, 0, monday()).unwrap(), vec![Flip { hour: 19, minute: 0, code: 1, duration: None }]);
+        assert_eq!(db.get_flips_for_minute(6, 31, monday()).unwrap(), vec![Flip { hour: 6, minute: 31, code: 2, duration: None }]);
+        let kinds: Vec<FlipKind> = db.get_special_times(monday()).unwrap()
+            .into_iter()
+            .map(|st| st.kind)
+            .collect();
+        assert!(kinds.contains(&FlipKind::NauticalDusk));
+        assert!(kinds.contains(&FlipKind::CivilDawn));
+    }
+
+    #[test]
+    fn sqlite_weather_adjustment() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        let mut times = solar_day(monday(), (6, 0), (7, 0), (17, 30), (18, 30));
+        times[2] = SpecialTime { date: monday(), kind: FlipKind::Dusk, hour: 17, minute: 10, adjustment: -20 };
+        db.update_special_times(&times).unwrap();
+        let dusk = db.get_special_times(monday()).unwrap()
+            .into_iter()
+            .find(|st| st.kind == FlipKind::Dusk)
+            .unwrap();
+        assert_eq!(dusk, times[2]);
+    }
+
+    #[test]
+    fn sqlite_last_processed() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        assert_eq!(db.get_last_processed().unwrap(), None);
+        let at = Utc.ymd(2018, 10, 1).and_hms(12, 0, 0);
+        db.update_last_processed(at).unwrap();
+        db.update_last_processed(at + Duration::minutes(1)).unwrap();
+        assert_eq!(db.get_last_processed().unwrap(), Some(at + Duration::minutes(1)));
+    }
+
+    #[test]
+    fn sqlite_vacation() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        assert!(!db.get_vacation().unwrap().enabled);
+        db.update_vacation(Vacation { enabled: true }).unwrap();
+        assert!(db.get_vacation().unwrap().enabled);
+        db.update_vacation(Vacation { enabled: false }).unwrap();
+        assert!(!db.get_vacation().unwrap().enabled);
+    }
+
+    #[test]
+    fn sqlite_timed_flips() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        let fan = db.new_switch("fan", 1, 2).unwrap();
+        let on = db.new_scheduled_flip(fan.id, 7, 0, 127.into(), Direction::On, FlipKind::Custom, 0, None, None).unwrap();
+        let off = db.new_scheduled_flip(fan.id, 9, 0, 127.into(), Direction::Off, FlipKind::Custom, 0, None, None).unwrap();
+        let updated = db.update_flip(on.id, 7, 0, 127.into(), Direction::On, FlipKind::Custom, 0, Some(30), None).unwrap();
+        assert_eq!(updated.duration, Some(30));
+        db.update_flip(off.id, 9, 0, 127.into(), Direction::Off, FlipKind::Custom, 0, Some(30), None).unwrap();
+        assert!(db.update_flip(on.id, 7, 0, 127.into(), Direction::On, FlipKind::Custom, 0, Some(0), None).is_err());
+        assert_eq!(db.get_flips_for_minute(7, 0, monday()).unwrap(), vec![Flip { hour: 7, minute: 0, code: 1, duration: Some(30) }]);
+        assert_eq!(db.get_flips_for_minute(9, 0, monday()).unwrap(), vec![Flip { hour: 9, minute: 0, code: 2, duration: None }]);
+        let timed = db.new_scheduled_flip(fan.id, 8, 0, 127.into(), Direction::On, FlipKind::Custom, 0, Some(15), None).unwrap();
+        assert_eq!(timed.duration, Some(15));
+        assert_eq!(db.get_flips_for_switch(fan.id).unwrap()[1], timed);
+        assert!(db.new_scheduled_flip(fan.id, 8, 0, 127.into(), Direction::On, FlipKind::Custom, 0, Some(0), None).is_err());
+        let later = db.new_pending_off(2, Utc.ymd(2018, 10, 1).and_hms(12, 30, 0)).unwrap();
+        let sooner = db.new_pending_off(4, Utc.ymd(2018, 10, 1).and_hms(12, 0, 0)).unwrap();
+        assert_eq!(db.get_pending_offs().unwrap(), vec![sooner.clone(), later.clone()]);
+        assert_eq!(db.remove_pending_off(sooner.id).unwrap(), 1);
+        assert_eq!(db.get_pending_offs().unwrap(), vec![later]);
+    }
+
+    #[test]
+    fn sqlite_pause_and_holds() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        assert_eq!(db.get_pause().unwrap(), Pause::default());
+        let resume_at = Utc.ymd(2018, 10, 2).and_hms(12, 0, 0);
+        let pause = Pause {
+            paused: true,
+            resume_at: Some(resume_at),
+        };
+        db.update_pause(pause).unwrap();
+        assert_eq!(db.get_pause().unwrap(), pause);
+        let lamp = db.new_switch("lamp", 1, 2).unwrap();
+        let fan = db.new_switch("fan", 3, 4).unwrap();
+        assert!(db.new_hold(-1, None).is_err());
+        db.new_hold(fan.id, Some(resume_at)).unwrap();
+        db.new_hold(lamp.id, Some(resume_at)).unwrap();
+        db.new_hold(lamp.id, None).unwrap();
+        assert_eq!(db.get_holds().unwrap(), vec![
+            Hold { switch_id: lamp.id, until: None },
+            Hold { switch_id: fan.id, until: Some(resume_at) },
+        ]);
+        assert_eq!(db.remove_expired_holds(resume_at).unwrap(), 1);
+        // removing the switch takes its hold with it
+        db.remove_switch(lamp.id).unwrap();
+        assert!(db.get_holds().unwrap().is_empty());
+        assert_eq!(db.remove_hold(lamp.id).unwrap(), 0);
+    }
+
+    #[test]
+    fn sqlite_refresh_log() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        assert_eq!(db.get_last_refresh().unwrap(), None);
+        assert_eq!(db.get_latest_special_date().unwrap(), None);
+        let later = NaiveDate::from_ymd(2018, 10, 7);
+        db.update_special_times(&solar_day(later, (6, 5), (7, 5), (17, 20), (18, 20))).unwrap();
+        db.update_special_times(&solar_day(monday(), (6, 0), (7, 0), (17, 30), (18, 30))).unwrap();
+        assert_eq!(db.get_latest_special_date().unwrap(), Some(later));
+        let ok = db.new_refresh(monday(), "calculate", true, 1, None).unwrap();
+        assert_eq!(ok.message, None);
+        let failed = db.new_refresh(later, "stored", false, 3, Some("Request failed")).unwrap();
+        assert_eq!(failed.date, later);
+        assert!(!failed.succeeded);
+        assert_eq!(db.get_last_refresh().unwrap(), Some(failed));
+    }
+
+    #[test]
+    fn sqlite_auth() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        let auth = db.new_auth().unwrap();
+        assert!(db.get_auth_age(&auth).is_ok());
+        db.new_token(&[1, 2], &[3, 4], auth).unwrap();
+        assert!(db.get_auth_age(&auth).is_err());
+        assert_eq!(db.get_private_shared(&auth).unwrap(), (vec![1, 2], vec![3, 4]));
+        assert!(db.check_token(&auth, &[3, 4]).unwrap());
+    }
+
+    #[test]
+    fn sqlite_dated_flips() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        let porch = db.new_switch("porch", 1, 2).unwrap();
+        let christmas_eve = NaiveDate::from_ymd(2026, 12, 24);
+        let flip = db.new_dated_flip(porch.id, christmas_eve, 18, 30, Direction::On, FlipKind::Custom).unwrap();
+        db.new_scheduled_flip(porch.id, 23, 0, 127.into(), Direction::Off, FlipKind::Custom, 0, None, None).unwrap();
+        assert_eq!(db.get_flips_for_day(christmas_eve).unwrap().len(), 2);
+        assert_eq!(db.get_flips_for_minute(18, 30, christmas_eve).unwrap(), vec![Flip { hour: 18, minute: 30, code: 1, duration: None }]);
+        assert_eq!(db.get_flips_for_day(NaiveDate::from_ymd(2026, 12, 31)).unwrap().len(), 1);
+        assert_eq!(db.get_flips_for_switch(porch.id).unwrap()[0], flip);
+        assert_eq!(db.remove_expired_flips(christmas_eve).unwrap(), 0);
+        assert_eq!(db.remove_expired_flips(christmas_eve.succ()).unwrap(), 1);
+        assert_eq!(db.get_flips_for_switch(porch.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn sqlite_seasonal_flips() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        let tree = db.new_switch("X-mas Tree", 1, 2).unwrap();
+        let christmas = Season {
+            start: MonthDay { month: 11, day: 25 },
+            end: MonthDay { month: 1, day: 6 },
+        };
+        let flip = db.new_scheduled_flip(tree.id, 18, 0, 127.into(), Direction::On, FlipKind::Custom, 0, None, Some(christmas)).unwrap();
+        assert_eq!(db.get_flips_for_switch(tree.id).unwrap(), vec![flip.clone()]);
+        assert_eq!(db.get_flip(flip.id).unwrap(), flip);
+        assert!(db.get_flip(flip.id + 1).is_err());
+        assert_eq!(db.get_flips_for_minute(18, 0, NaiveDate::from_ymd(2026, 12, 24)).unwrap().len(), 1);
+        assert_eq!(db.get_flips_for_day(NaiveDate::from_ymd(2027, 1, 6)).unwrap().len(), 1);
+        assert!(db.get_flips_for_day(NaiveDate::from_ymd(2027, 1, 7)).unwrap().is_empty());
+        let summer = Season {
+            start: MonthDay { month: 6, day: 1 },
+            end: MonthDay { month: 8, day: 31 },
+        };
+        let updated = db.update_flip(flip.id, 18, 0, 127.into(), Direction::On, FlipKind::Custom, 0, None, Some(summer)).unwrap();
+        assert_eq!(updated.season, Some(summer));
+        assert_eq!(db.get_flips_for_day(NaiveDate::from_ymd(2026, 7, 4)).unwrap().len(), 1);
+        assert!(db.get_flips_for_day(NaiveDate::from_ymd(2026, 12, 24)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn sqlite_groups() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        let lamp = db.new_switch("lamp", 1, 2).unwrap();
+        let tree = db.new_switch("tree", 3, 4).unwrap();
+        let group = db.new_group("living room").unwrap();
+        db.add_group_member(group.id, lamp.id).unwrap();
+        db.add_group_member(group.id, tree.id).unwrap();
+        db.add_group_member(group.id, tree.id).unwrap();
+        let flip = db.new_group_flip(group.id, 18, 0, 127.into(), Direction::On, FlipKind::Custom).unwrap();
+        assert_eq!(db.get_flips_for_minute(18, 0, monday()).unwrap().len(), 2);
+        assert_eq!(db.get_flips_for_group(group.id).unwrap(), vec![flip]);
+        let group = db.update_group(group.id, "den").unwrap();
+        assert_eq!(group.codes(Direction::On), vec![1, 3]);
+        assert_eq!(db.get_all_groups().unwrap(), vec![group.clone()]);
+        assert_eq!(db.remove_group_member(group.id, lamp.id).unwrap(), 1);
+        assert_eq!(db.get_group(group.id).unwrap().switches, vec![tree]);
+        assert_eq!(db.remove_group(group.id).unwrap(), 1);
+        assert!(db.get_flips_for_day(monday()).unwrap().is_empty());
+        assert_eq!(db.get_all_switches().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn sqlite_scenes() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.migrate().expect("failed to migrate sqlite");
+        let lamp = db.new_switch("lamp", 1, 2).unwrap();
+        let tree = db.new_switch("tree", 3, 4).unwrap();
+        let scene = db.new_scene("movie night").unwrap();
+        db.set_scene_member(scene.id, lamp.id, Direction::On).unwrap();
+        db.set_scene_member(scene.id, tree.id, Direction::On).unwrap();
+        db.set_scene_member(scene.id, tree.id, Direction::Off).unwrap();
+        let flip = db.new_scene_flip(scene.id, 20, 0, 127.into(), Direction::On, FlipKind::Custom).unwrap();
+        let codes: Vec<i32> = db.get_flips_for_minute(20, 0, monday()).unwrap().into_iter().map(|f| f.code).collect();
+        assert_eq!(codes, vec![1, 4]);
+        assert_eq!(db.get_flips_for_scene(scene.id).unwrap(), vec![flip]);
+        let scene = db.get_scene(scene.id).unwrap();
+        assert_eq!(scene.codes(Direction::On), vec![1, 4]);
+        assert_eq!(db.get_all_scenes().unwrap(), vec![scene.clone()]);
+        assert_eq!(db.remove_scene_member(scene.id, tree.id).unwrap(), 1);
+        assert_eq!(db.update_scene(scene.id, "reading").unwrap().members.len(), 1);
+        assert_eq!(db.remove_scene(scene.id).unwrap(), 1);
+        assert!(db.get_flips_for_day(monday()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn sqlite_migrations() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        let applied = db.migrate().unwrap();
+        assert_eq!(applied.len(), migrations::SQLITE.len());
+        assert!(db.migrate().unwrap().is_empty());
+    }
+
+    #[test]
+    fn sqlite_concurrent_migrations() {
+        let path = ::std::env::temp_dir().join(format!("robohome-migrate-{}.sqlite", ::std::process::id()));
+        let _ = ::std::fs::remove_file(&path);
+        let handles: Vec<_> = (0..2).map(|_| {
+            let path = path.clone();
+            ::std::thread::spawn(move || SqliteStore::open(&path).unwrap().migrate())
+        }).collect();
+        let mut applied: Vec<i32> = handles.into_iter()
+            .flat_map(|h| h.join().unwrap().expect("failed to migrate sqlite"))
+            .collect();
+        applied.sort();
+        assert_eq!(applied, migrations::SQLITE.iter().map(|m| m.version).collect::<Vec<_>>());
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sqlite_existing_install() {
+        let db = SqliteStore::open_in_memory().expect("failed to open sqlite");
+        db.get_connection().unwrap().execute_batch(migrations::SQLITE[0].up).unwrap();
+        let sw = db.new_switch("porch", 1, 2).unwrap();
+        let applied = db.migrate().unwrap();
+        assert!(applied.iter().all(|v| *v > BASELINE));
+        assert_eq!(db.get_all_switches().unwrap(), vec![sw]);
+    }
+}
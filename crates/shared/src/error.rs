@@ -9,6 +9,7 @@ use std::{
         RecvError
     },
     num::ParseIntError as IError,
+    io::Error as IoError,
 };
 
 use serde::{
@@ -20,20 +21,29 @@ use serde::{
 };
 
 use serde_json::Error as JsonError;
+use toml::de::Error as TomlError;
 
 use amqp::AMQPError;
 use bincode;
 use postgres::Error as PError;
+use r2d2::Error as PoolError;
+#[cfg(feature = "sqlite")]
+use rusqlite::Error as SqliteError;
 use uuid::ParseError as UError;
 
 #[derive(Debug)]
 pub enum Error {
     Bin(bincode::Error),
+    Io(IoError),
     Json(JsonError),
     Mq(AMQPError),
     Other(String),
     Pg(PError),
+    Pool(PoolError),
     Recv(RecvError),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteError),
+    Toml(TomlError),
     Uuid(UError),
     U8(IError),
 }
@@ -48,11 +58,16 @@ impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> FmtRes {
         match self {
             Error::Bin(e) => e.fmt(f),
+            Error::Io(e) => e.fmt(f),
             Error::Json(e) => e.fmt(f),
             Error::Mq(e) => e.fmt(f),
             Error::Other(msg) => msg.fmt(f),
             Error::Pg(e) => e.fmt(f),
+            Error::Pool(e) => e.fmt(f),
             Error::Recv(e) => e.fmt(f),
+            #[cfg(feature = "sqlite")]
+            Error::Sqlite(e) => e.fmt(f),
+            Error::Toml(e) => e.fmt(f),
             Error::Uuid(e) => e.fmt(f),
             Error::U8(e) => e.fmt(f),
         }
@@ -63,11 +78,16 @@ impl StdError for Error {
     fn cause(&self) -> Option<&StdError> {
         match self {
             Error::Bin(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
             Error::Json(ref e) => Some(e),
             Error::Mq(ref e) => Some(e),
             Error::Other(_) => None,
             Error::Pg(ref e) => Some(e),
+            Error::Pool(ref e) => Some(e),
             Error::Recv(ref e) => Some(e),
+            #[cfg(feature = "sqlite")]
+            Error::Sqlite(ref e) => Some(e),
+            Error::Toml(ref e) => Some(e),
             Error::Uuid(ref e) => Some(e),
             Error::U8(ref e) => Some(e),
         }
@@ -86,24 +106,49 @@ impl From<bincode::Error> for Error {
     }
 }
 
+impl From<IoError> for Error {
+    fn from(other: IoError) -> Self {
+        Error::Io(other)
+    }
+}
+
 impl From<PError> for Error {
     fn from(other: PError) -> Self {
         Error::Pg(other)
     }
 }
 
+impl From<PoolError> for Error {
+    fn from(other: PoolError) -> Self {
+        Error::Pool(other)
+    }
+}
+
 impl From<RecvError> for Error {
     fn from(other: RecvError) -> Self {
         Error::Recv(other)
     }
 }
 
+#[cfg(feature = "sqlite")]
+impl From<SqliteError> for Error {
+    fn from(other: SqliteError) -> Self {
+        Error::Sqlite(other)
+    }
+}
+
 impl From<JsonError> for Error {
     fn from(other: JsonError) -> Self {
         Error::Json(other)
     }
 }
 
+impl From<TomlError> for Error {
+    fn from(other: TomlError) -> Self {
+        Error::Toml(other)
+    }
+}
+
 impl From<UError> for Error {
     fn from(other: UError) -> Self {
         Error::Uuid(other)
@@ -17,39 +17,66 @@ use amqp::{
 };
 use serde;
 use bincode;
+use chrono::NaiveDate;
 
-static CONN_STR: &str = include_str!("../../../mq_connection");
-
-pub fn send<T>(queue: &str, msg: &T) -> Result<(), Error>
-where T: serde::Serialize + Send {
-    let mut s = Session::open_url(CONN_STR).expect("Failed to connect to rabbit mq");
-    let mut c = s.open_channel(1)?;
-    let _ = c.queue_declare(queue, false, true, false, true, false, Table::new())?;
-    let p: BasicProperties = Default::default();
-    let msg = bincode::serialize(msg)?;
-    c.basic_publish("", queue, true, false, p, msg)?;
-    c.close(200, "")?;
-    s.close(200, "");
-    Ok(())
+/// Sent to the `alerts` queue when something
+/// needs a person to look at it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Alert {
+    /// Refresher couldn't record sun times for `date`
+    /// and the newest ones are for `latest`, if any
+    StaleSunTimes {
+        date: NaiveDate,
+        latest: Option<NaiveDate>,
+    },
+}
+
+/// A handle to the message broker,
+/// each call opens its own session
+#[derive(Debug, Clone)]
+pub struct Ipc {
+    url: String,
 }
 
 type Rec<T> = Receiver<Result<T, Error>>;
-pub fn listen<T: 'static>(queue: &'static str) -> Result<Rec<T>, Error>
-where for<'a> T: serde::Deserialize<'a> + Send {
-    let (tx, rx) = channel::<Result<T, Error>>();
-    let mut s = Session::open_url(CONN_STR)?;
-    let mut c = s.open_channel(1)?;
-    c.basic_prefetch(10)?;
-    let _ = c.queue_declare(queue, false, true, false, true, false, Table::new())?;
-    ::std::thread::spawn(move || {
-        let tx = tx;
-        c.basic_consume(move |_: &mut _, _, _, data: Vec<u8>| {
-            match bincode::deserialize(&data) {
-                Ok(v) => tx.send(Ok(v)).expect("Error sending message to listener"),
-                Err(e) => tx.send(Err(e.into())).expect("Error sending de error to listener"),
-            }
-        }, queue, "", true, true, false, false, Table::new()).expect("Unable to spawn consumer");
-        c.start_consuming();
-    });
-    Ok(rx)
-}
\ No newline at end of file
+
+impl Ipc {
+    pub fn new(url: &str) -> Ipc {
+        Ipc {
+            url: url.to_string(),
+        }
+    }
+
+    pub fn send<T>(&self, queue: &str, msg: &T) -> Result<(), Error>
+    where T: serde::Serialize + Send {
+        let mut s = Session::open_url(&self.url)?;
+        let mut c = s.open_channel(1)?;
+        let _ = c.queue_declare(queue, false, true, false, true, false, Table::new())?;
+        let p: BasicProperties = Default::default();
+        let msg = bincode::serialize(msg)?;
+        c.basic_publish("", queue, true, false, p, msg)?;
+        c.close(200, "")?;
+        s.close(200, "");
+        Ok(())
+    }
+
+    pub fn listen<T: 'static>(&self, queue: &'static str) -> Result<Rec<T>, Error>
+    where for<'a> T: serde::Deserialize<'a> + Send {
+        let (tx, rx) = channel::<Result<T, Error>>();
+        let mut s = Session::open_url(&self.url)?;
+        let mut c = s.open_channel(1)?;
+        c.basic_prefetch(10)?;
+        let _ = c.queue_declare(queue, false, true, false, true, false, Table::new())?;
+        ::std::thread::spawn(move || {
+            let tx = tx;
+            c.basic_consume(move |_: &mut _, _, _, data: Vec<u8>| {
+                match bincode::deserialize(&data) {
+                    Ok(v) => tx.send(Ok(v)).expect("Error sending message to listener"),
+                    Err(e) => tx.send(Err(e.into())).expect("Error sending de error to listener"),
+                }
+            }, queue, "", true, true, false, false, Table::new()).expect("Unable to spawn consumer");
+            c.start_consuming();
+        });
+        Ok(rx)
+    }
+}
@@ -1,18 +1,27 @@
 extern crate amqp;
 extern crate bincode;
 extern crate chrono;
+extern crate chrono_tz;
 #[macro_use]
 extern crate postgres;
 #[macro_use]
 extern crate postgres_derive;
+extern crate r2d2;
+extern crate r2d2_postgres;
+#[cfg(feature = "sqlite")]
+#[macro_use]
+extern crate rusqlite;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate toml;
 extern crate uuid;
 
+pub mod config;
 mod error;
 pub mod ipc;
 pub mod data;
+pub mod time;
 
 pub use error::Error;
\ No newline at end of file
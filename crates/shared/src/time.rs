@@ -0,0 +1,86 @@
+//! Flips and special times are stored in the
+//! home's wall-clock time, these convert between
+//! that and the instants they happen at
+use chrono::{
+    DateTime,
+    Duration,
+    NaiveDate,
+    NaiveDateTime,
+    TimeZone,
+    Utc,
+};
+use chrono_tz::Tz;
+
+/// The wall-clock date and time in `tz` at `now`
+pub fn local_now(now: DateTime<Utc>, tz: &Tz) -> NaiveDateTime {
+    now.with_timezone(tz).naive_local()
+}
+
+/// The date it is in `tz` at `now`
+pub fn today(now: DateTime<Utc>, tz: &Tz) -> NaiveDate {
+    local_now(now, tz).date()
+}
+
+/// The instant `local` happens in `tz`. A time repeated
+/// when the clocks go back happens the first time round
+/// and one skipped when they go forward happens as soon
+/// as the clocks change
+pub fn instant(local: NaiveDateTime, tz: &Tz) -> DateTime<Utc> {
+    let mut next = local;
+    loop {
+        if let Some(dt) = tz.from_local_datetime(&next).earliest() {
+            return dt.with_timezone(&Utc);
+        }
+        next = next + Duration::minutes(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono_tz::{
+        America::Chicago,
+        Pacific::Apia,
+    };
+
+    fn local(y: i32, m: u32, d: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(y, m, d).and_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn evening_is_still_today() {
+        // 19:30 CDT on a Monday is already Tuesday in UTC
+        let now = Utc.ymd(2018, 10, 2).and_hms(0, 30, 0);
+        assert_eq!(today(now, &Chicago), NaiveDate::from_ymd(2018, 10, 1));
+        assert_eq!(local_now(now, &Chicago), local(2018, 10, 1, 19, 30));
+        assert_eq!(today(now, &Tz::UTC), NaiveDate::from_ymd(2018, 10, 2));
+    }
+
+    #[test]
+    fn wall_clock_follows_dst() {
+        // 07:00 is 13:00 UTC in the winter and 12:00 in the summer
+        assert_eq!(instant(local(2019, 1, 7, 7, 0), &Chicago), Utc.ymd(2019, 1, 7).and_hms(13, 0, 0));
+        assert_eq!(instant(local(2019, 7, 8, 7, 0), &Chicago), Utc.ymd(2019, 7, 8).and_hms(12, 0, 0));
+    }
+
+    #[test]
+    fn skipped_times() {
+        // 02:00 to 02:59 don't happen on the 10th of March 2019, the
+        // clocks go from 01:59 CST to 03:00 CDT at 08:00 UTC
+        let change = Utc.ymd(2019, 3, 10).and_hms(8, 0, 0);
+        assert_eq!(instant(local(2019, 3, 10, 2, 0), &Chicago), change);
+        assert_eq!(instant(local(2019, 3, 10, 2, 30), &Chicago), change);
+        assert_eq!(instant(local(2019, 3, 10, 3, 0), &Chicago), change);
+        assert_eq!(instant(local(2019, 3, 10, 1, 59), &Chicago), change - Duration::minutes(1));
+        // the 30th of December 2011 was skipped entirely in Samoa
+        assert_eq!(instant(local(2011, 12, 30, 12, 0), &Apia), instant(local(2011, 12, 31, 0, 0), &Apia));
+    }
+
+    #[test]
+    fn repeated_times() {
+        // 01:00 to 01:59 happen twice on the 3rd of November
+        // 2019, first as CDT and then as CST
+        assert_eq!(instant(local(2019, 11, 3, 1, 30), &Chicago), Utc.ymd(2019, 11, 3).and_hms(6, 30, 0));
+        assert_eq!(instant(local(2019, 11, 3, 2, 0), &Chicago), Utc.ymd(2019, 11, 3).and_hms(8, 0, 0));
+    }
+}
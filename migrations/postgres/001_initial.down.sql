@@ -36,6 +36,7 @@ DROP TABLE public.switch;
 DROP TABLE public.token;
 DROP TABLE public.authorize;
 DROP TABLE public.special_time;
+DROP TABLE IF EXISTS public.schema_version;
 /************************
 --TYPES
 *************************/
@@ -0,0 +1,27 @@
+/************************
+* FUNCTIONS
+*************************/
+DROP FUNCTION IF EXISTS public.new_group(TEXT);
+DROP FUNCTION IF EXISTS public.add_group_member(INTEGER, INTEGER);
+DROP FUNCTION IF EXISTS public.new_group_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind);
+DROP FUNCTION IF EXISTS public.get_all_groups();
+DROP FUNCTION IF EXISTS public.get_group(INTEGER);
+DROP FUNCTION IF EXISTS public.get_group_members(INTEGER);
+DROP FUNCTION IF EXISTS public.get_group_flips(INTEGER);
+DROP FUNCTION IF EXISTS public.update_group(INTEGER, TEXT);
+DROP FUNCTION IF EXISTS public.remove_group(INTEGER);
+DROP FUNCTION IF EXISTS public.remove_group_member(INTEGER, INTEGER);
+/************************
+-- TABLES
+*************************/
+DELETE FROM public.flip WHERE group_id IS NOT NULL;
+ALTER TABLE public.flip
+    DROP CONSTRAINT IF EXISTS flip_target,
+    DROP COLUMN IF EXISTS group_id,
+    ALTER COLUMN switch_id SET NOT NULL;
+DROP TABLE IF EXISTS public.group_member;
+DROP TABLE IF EXISTS public.switch_group;
+/************************
+--SEQUENCES
+*************************/
+DROP SEQUENCE IF EXISTS public.switch_group_id_seq;
@@ -0,0 +1,308 @@
+/************************
+-- SEQUENCES
+*************************/
+CREATE SEQUENCE public.switch_group_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.switch_group_id_seq
+    OWNER to robot;
+
+/************************
+-- TABLES
+*************************/
+CREATE TABLE public.switch_group
+(
+    id INTEGER NOT NULL DEFAULT nextval('switch_group_id_seq'::regclass),
+    name character varying(255) COLLATE pg_catalog."default" NOT NULL,
+    CONSTRAINT switch_group_pkey PRIMARY KEY (id)
+)
+WITH (
+    OIDS = FALSE
+)
+TABLESPACE pg_default;
+
+CREATE TABLE public.group_member
+(
+    group_id INTEGER NOT NULL,
+    switch_id INTEGER NOT NULL,
+    CONSTRAINT group_member_pkey PRIMARY KEY (group_id, switch_id),
+    CONSTRAINT group_member_group FOREIGN KEY (group_id)
+        REFERENCES public.switch_group (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE,
+    CONSTRAINT group_member_switch FOREIGN KEY (switch_id)
+        REFERENCES public.switch (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE
+)
+WITH (
+    OIDS = FALSE
+)
+TABLESPACE pg_default;
+
+-- a flip now targets either a single
+-- switch or every switch in a group
+ALTER TABLE public.flip
+    ALTER COLUMN switch_id DROP NOT NULL,
+    ADD COLUMN group_id INTEGER,
+    ADD CONSTRAINT group_flip FOREIGN KEY (group_id)
+        REFERENCES public.switch_group (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE,
+    ADD CONSTRAINT flip_target CHECK ((switch_id IS NULL) <> (group_id IS NULL));
+
+ALTER TABLE public.switch_group
+    OWNER TO robot;
+
+ALTER TABLE public.group_member
+    OWNER TO robot;
+
+/************************
+* CREATE
+*************************/
+CREATE OR REPLACE FUNCTION public.new_group(
+    arg_name TEXT
+) RETURNS switch_group
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret switch_group;
+BEGIN
+    INSERT INTO switch_group (name)
+    VALUES (arg_name)
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.add_group_member(
+    arg_group INTEGER,
+    arg_switch INTEGER
+) RETURNS VOID
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+BEGIN
+    INSERT INTO group_member (group_id, switch_id)
+    VALUES (arg_group, arg_switch)
+    ON CONFLICT DO NOTHING;
+END;
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.new_group_flip(
+    arg_group INTEGER,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_dow INTEGER,
+    arg_direction public.FlipDirection,
+    arg_kind public.FlipKind)
+RETURNS flip
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    INSERT INTO public.flip (
+        group_id, hour, minute, dow, direction, kind)
+    VALUES (arg_group, arg_hour, arg_minute, arg_dow, arg_direction, arg_kind)
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.new_group(TEXT)
+    OWNER TO robot;
+
+ALTER FUNCTION public.add_group_member(INTEGER, INTEGER)
+    OWNER TO robot;
+
+ALTER FUNCTION public.new_group_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind)
+    OWNER TO robot;
+
+/************************
+* READ
+*************************/
+CREATE OR REPLACE FUNCTION public.get_all_groups()
+    RETURNS SETOF switch_group
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, name
+    FROM public.switch_group
+    ORDER BY name
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_group(
+    arg_group INTEGER
+)
+    RETURNS SETOF switch_group
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1
+AS $BODY$
+    SELECT id, name
+    FROM public.switch_group
+    WHERE id = arg_group
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_group_members(
+    arg_group INTEGER
+)
+    RETURNS SETOF switch
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT s.id, s.name, s.on_code, s.off_code
+    FROM public.switch AS s
+        JOIN public.group_member AS m
+        ON s.id = m.switch_id
+    WHERE m.group_id = arg_group
+    ORDER BY s.id
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_group_flips(
+    arg_group INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind
+    FROM public.flip
+    WHERE group_id = arg_group
+    ORDER BY hour, minute
+$BODY$;
+
+-- group flips are expanded into one
+-- row for each member switch
+CREATE OR REPLACE FUNCTION public.get_flips_for_day(arg_dow INTEGER)
+    RETURNS SETOF public.SwitchFlip
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT f.hour, f.minute,
+    CASE WHEN f.direction = 'Off' THEN
+        s.off_code
+    ELSE
+        s.on_code
+    END AS code
+    FROM public.flip as f
+        LEFT JOIN public.group_member as m
+        ON f.group_id = m.group_id
+        JOIN public.switch as s
+        ON s.id = COALESCE(f.switch_id, m.switch_id)
+    WHERE f.dow & arg_dow > 0;
+$BODY$;
+
+CREATE OR REPLACE FUNCTION get_flips_for_minute(arg_hour INTEGER, arg_minute INTEGER, arg_dow INTEGER)
+RETURNS SETOF public.SwitchFlip
+LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT f.hour, f.minute,
+    CASE WHEN f.direction = 'Off' THEN
+        s.off_code
+    ELSE
+        s.on_code
+    END AS code
+    FROM public.flip as f
+        LEFT JOIN public.group_member as m
+        ON f.group_id = m.group_id
+        JOIN public.switch as s
+        ON s.id = COALESCE(f.switch_id, m.switch_id)
+    WHERE f.dow & arg_dow > 0
+      AND f.hour = arg_hour
+      AND f.minute = arg_minute;
+$BODY$;
+
+ALTER FUNCTION public.get_all_groups()
+    OWNER TO robot;
+
+ALTER FUNCTION public.get_group(INTEGER)
+    OWNER TO robot;
+
+ALTER FUNCTION public.get_group_members(INTEGER)
+    OWNER TO robot;
+
+ALTER FUNCTION public.get_group_flips(INTEGER)
+    OWNER TO robot;
+
+/************************
+* UPDATE
+*************************/
+CREATE OR REPLACE FUNCTION public.update_group(
+    arg_id INTEGER,
+    arg_name TEXT
+) RETURNS switch_group
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret switch_group;
+BEGIN
+    UPDATE switch_group
+    SET name = arg_name
+    WHERE id = arg_id
+    RETURNING * into ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_group(INTEGER, TEXT)
+    OWNER TO robot;
+
+/************************
+* DELETE
+*************************/
+CREATE OR REPLACE FUNCTION public.remove_group(
+    arg_id INTEGER
+) RETURNS INTEGER
+    LANGUAGE plpgsql
+    AS $BODY$
+DECLARE ret INTEGER;
+BEGIN
+    DELETE FROM switch_group WHERE id = arg_id;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.remove_group_member(
+    arg_group INTEGER,
+    arg_switch INTEGER
+) RETURNS INTEGER
+    LANGUAGE plpgsql
+    AS $BODY$
+DECLARE ret INTEGER;
+BEGIN
+    DELETE FROM group_member
+    WHERE group_id = arg_group
+    AND switch_id = arg_switch;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.remove_group(INTEGER)
+    OWNER TO robot;
+
+ALTER FUNCTION public.remove_group_member(INTEGER, INTEGER)
+    OWNER TO robot;
@@ -0,0 +1,31 @@
+/************************
+* FUNCTIONS
+*************************/
+DROP FUNCTION IF EXISTS public.new_scene(TEXT);
+DROP FUNCTION IF EXISTS public.set_scene_member(INTEGER, INTEGER, public.FlipDirection);
+DROP FUNCTION IF EXISTS public.new_scene_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind);
+DROP FUNCTION IF EXISTS public.get_all_scenes();
+DROP FUNCTION IF EXISTS public.get_scene(INTEGER);
+DROP FUNCTION IF EXISTS public.get_scene_members(INTEGER);
+DROP FUNCTION IF EXISTS public.get_scene_flips(INTEGER);
+DROP FUNCTION IF EXISTS public.update_scene(INTEGER, TEXT);
+DROP FUNCTION IF EXISTS public.remove_scene(INTEGER);
+DROP FUNCTION IF EXISTS public.remove_scene_member(INTEGER, INTEGER);
+/************************
+-- TABLES
+*************************/
+DELETE FROM public.flip WHERE scene_id IS NOT NULL;
+ALTER TABLE public.flip
+    DROP CONSTRAINT IF EXISTS flip_target,
+    DROP COLUMN IF EXISTS scene_id,
+    ADD CONSTRAINT flip_target CHECK ((switch_id IS NULL) <> (group_id IS NULL));
+DROP TABLE IF EXISTS public.scene_member;
+DROP TABLE IF EXISTS public.scene;
+/************************
+--TYPES
+*************************/
+DROP TYPE IF EXISTS public.SceneSwitch;
+/************************
+--SEQUENCES
+*************************/
+DROP SEQUENCE IF EXISTS public.scene_id_seq;
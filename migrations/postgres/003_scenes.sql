@@ -0,0 +1,325 @@
+/************************
+-- SEQUENCES
+*************************/
+CREATE SEQUENCE public.scene_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.scene_id_seq
+    OWNER to robot;
+
+/************************
+-- TABLES
+*************************/
+CREATE TABLE public.scene
+(
+    id INTEGER NOT NULL DEFAULT nextval('scene_id_seq'::regclass),
+    name character varying(255) COLLATE pg_catalog."default" NOT NULL,
+    CONSTRAINT scene_pkey PRIMARY KEY (id)
+)
+WITH (
+    OIDS = FALSE
+)
+TABLESPACE pg_default;
+
+CREATE TABLE public.scene_member
+(
+    scene_id INTEGER NOT NULL,
+    switch_id INTEGER NOT NULL,
+    direction public.FlipDirection NOT NULL,
+    CONSTRAINT scene_member_pkey PRIMARY KEY (scene_id, switch_id),
+    CONSTRAINT scene_member_scene FOREIGN KEY (scene_id)
+        REFERENCES public.scene (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE,
+    CONSTRAINT scene_member_switch FOREIGN KEY (switch_id)
+        REFERENCES public.switch (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE
+)
+WITH (
+    OIDS = FALSE
+)
+TABLESPACE pg_default;
+
+-- a flip can also trigger a scene
+ALTER TABLE public.flip
+    ADD COLUMN scene_id INTEGER,
+    ADD CONSTRAINT scene_flip FOREIGN KEY (scene_id)
+        REFERENCES public.scene (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE,
+    DROP CONSTRAINT flip_target,
+    ADD CONSTRAINT flip_target CHECK (num_nonnulls(switch_id, group_id, scene_id) = 1);
+
+ALTER TABLE public.scene
+    OWNER TO robot;
+
+ALTER TABLE public.scene_member
+    OWNER TO robot;
+
+CREATE TYPE public.SceneSwitch AS (
+    id INTEGER,
+    name TEXT,
+    on_code INTEGER,
+    off_code INTEGER,
+    direction public.FlipDirection
+);
+
+ALTER TYPE public.SceneSwitch
+    OWNER TO robot;
+
+/************************
+* CREATE
+*************************/
+CREATE OR REPLACE FUNCTION public.new_scene(
+    arg_name TEXT
+) RETURNS scene
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret scene;
+BEGIN
+    INSERT INTO scene (name)
+    VALUES (arg_name)
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.set_scene_member(
+    arg_scene INTEGER,
+    arg_switch INTEGER,
+    arg_direction public.FlipDirection
+) RETURNS VOID
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+BEGIN
+    INSERT INTO scene_member (scene_id, switch_id, direction)
+    VALUES (arg_scene, arg_switch, arg_direction)
+    ON CONFLICT (scene_id, switch_id) DO UPDATE
+    SET direction = arg_direction;
+END;
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.new_scene_flip(
+    arg_scene INTEGER,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_dow INTEGER,
+    arg_direction public.FlipDirection,
+    arg_kind public.FlipKind)
+RETURNS flip
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    INSERT INTO public.flip (
+        scene_id, hour, minute, dow, direction, kind)
+    VALUES (arg_scene, arg_hour, arg_minute, arg_dow, arg_direction, arg_kind)
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.new_scene(TEXT)
+    OWNER TO robot;
+
+ALTER FUNCTION public.set_scene_member(INTEGER, INTEGER, public.FlipDirection)
+    OWNER TO robot;
+
+ALTER FUNCTION public.new_scene_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind)
+    OWNER TO robot;
+
+/************************
+* READ
+*************************/
+CREATE OR REPLACE FUNCTION public.get_all_scenes()
+    RETURNS SETOF scene
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, name
+    FROM public.scene
+    ORDER BY name
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_scene(
+    arg_scene INTEGER
+)
+    RETURNS SETOF scene
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1
+AS $BODY$
+    SELECT id, name
+    FROM public.scene
+    WHERE id = arg_scene
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_scene_members(
+    arg_scene INTEGER
+)
+    RETURNS SETOF SceneSwitch
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT s.id, s.name::TEXT, s.on_code, s.off_code, m.direction
+    FROM public.switch AS s
+        JOIN public.scene_member AS m
+        ON s.id = m.switch_id
+    WHERE m.scene_id = arg_scene
+    ORDER BY s.id
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_scene_flips(
+    arg_scene INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind
+    FROM public.flip
+    WHERE scene_id = arg_scene
+    ORDER BY hour, minute
+$BODY$;
+
+-- a scene flip that is 'On' moves each member
+-- in its own direction, 'Off' turns them all off
+CREATE OR REPLACE FUNCTION public.get_flips_for_day(arg_dow INTEGER)
+    RETURNS SETOF public.SwitchFlip
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT f.hour, f.minute,
+    CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+        s.off_code
+    ELSE
+        s.on_code
+    END AS code
+    FROM public.flip as f
+        LEFT JOIN public.group_member as m
+        ON f.group_id = m.group_id
+        LEFT JOIN public.scene_member as sm
+        ON f.scene_id = sm.scene_id
+        JOIN public.switch as s
+        ON s.id = COALESCE(f.switch_id, m.switch_id, sm.switch_id)
+    WHERE f.dow & arg_dow > 0;
+$BODY$;
+
+CREATE OR REPLACE FUNCTION get_flips_for_minute(arg_hour INTEGER, arg_minute INTEGER, arg_dow INTEGER)
+RETURNS SETOF public.SwitchFlip
+LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT f.hour, f.minute,
+    CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+        s.off_code
+    ELSE
+        s.on_code
+    END AS code
+    FROM public.flip as f
+        LEFT JOIN public.group_member as m
+        ON f.group_id = m.group_id
+        LEFT JOIN public.scene_member as sm
+        ON f.scene_id = sm.scene_id
+        JOIN public.switch as s
+        ON s.id = COALESCE(f.switch_id, m.switch_id, sm.switch_id)
+    WHERE f.dow & arg_dow > 0
+      AND f.hour = arg_hour
+      AND f.minute = arg_minute;
+$BODY$;
+
+ALTER FUNCTION public.get_all_scenes()
+    OWNER TO robot;
+
+ALTER FUNCTION public.get_scene(INTEGER)
+    OWNER TO robot;
+
+ALTER FUNCTION public.get_scene_members(INTEGER)
+    OWNER TO robot;
+
+ALTER FUNCTION public.get_scene_flips(INTEGER)
+    OWNER TO robot;
+
+/************************
+* UPDATE
+*************************/
+CREATE OR REPLACE FUNCTION public.update_scene(
+    arg_id INTEGER,
+    arg_name TEXT
+) RETURNS scene
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret scene;
+BEGIN
+    UPDATE scene
+    SET name = arg_name
+    WHERE id = arg_id
+    RETURNING * into ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_scene(INTEGER, TEXT)
+    OWNER TO robot;
+
+/************************
+* DELETE
+*************************/
+CREATE OR REPLACE FUNCTION public.remove_scene(
+    arg_id INTEGER
+) RETURNS INTEGER
+    LANGUAGE plpgsql
+    AS $BODY$
+DECLARE ret INTEGER;
+BEGIN
+    DELETE FROM scene WHERE id = arg_id;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.remove_scene_member(
+    arg_scene INTEGER,
+    arg_switch INTEGER
+) RETURNS INTEGER
+    LANGUAGE plpgsql
+    AS $BODY$
+DECLARE ret INTEGER;
+BEGIN
+    DELETE FROM scene_member
+    WHERE scene_id = arg_scene
+    AND switch_id = arg_switch;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.remove_scene(INTEGER)
+    OWNER TO robot;
+
+ALTER FUNCTION public.remove_scene_member(INTEGER, INTEGER)
+    OWNER TO robot;
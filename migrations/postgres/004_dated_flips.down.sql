@@ -0,0 +1,117 @@
+/************************
+* FUNCTIONS
+*************************/
+DROP FUNCTION IF EXISTS public.new_dated_flip(INTEGER, DATE, INTEGER, INTEGER, public.FlipDirection, public.FlipKind);
+DROP FUNCTION IF EXISTS public.get_flips_for_minute(INTEGER, INTEGER, DATE);
+DROP FUNCTION IF EXISTS public.get_flips_for_day(DATE);
+DROP FUNCTION IF EXISTS public.remove_expired_flips(DATE);
+DROP FUNCTION IF EXISTS public.get_switch_flips(INTEGER);
+DROP FUNCTION IF EXISTS public.get_group_flips(INTEGER);
+DROP FUNCTION IF EXISTS public.get_scene_flips(INTEGER);
+/************************
+-- TABLES
+*************************/
+DELETE FROM public.flip WHERE date IS NOT NULL;
+ALTER TABLE public.flip
+    DROP COLUMN IF EXISTS date;
+/************************
+--TYPES
+*************************/
+ALTER TYPE public.FlipInfo
+    DROP ATTRIBUTE IF EXISTS date;
+/************************
+* RESTORE
+*************************/
+CREATE OR REPLACE FUNCTION public.get_switch_flips(
+	arg_switch INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+	SELECT id, hour, minute, dow, direction, kind
+	FROM public.flip
+	WHERE switch_id = arg_switch
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_group_flips(
+    arg_group INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind
+    FROM public.flip
+    WHERE group_id = arg_group
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_scene_flips(
+    arg_scene INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind
+    FROM public.flip
+    WHERE scene_id = arg_scene
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_flips_for_day(arg_dow INTEGER)
+    RETURNS SETOF public.SwitchFlip
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT f.hour, f.minute,
+    CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+        s.off_code
+    ELSE
+        s.on_code
+    END AS code
+    FROM public.flip as f
+        LEFT JOIN public.group_member as m
+        ON f.group_id = m.group_id
+        LEFT JOIN public.scene_member as sm
+        ON f.scene_id = sm.scene_id
+        JOIN public.switch as s
+        ON s.id = COALESCE(f.switch_id, m.switch_id, sm.switch_id)
+    WHERE f.dow & arg_dow > 0;
+$BODY$;
+
+CREATE OR REPLACE FUNCTION get_flips_for_minute(arg_hour INTEGER, arg_minute INTEGER, arg_dow INTEGER)
+RETURNS SETOF public.SwitchFlip
+LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT f.hour, f.minute,
+    CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+        s.off_code
+    ELSE
+        s.on_code
+    END AS code
+    FROM public.flip as f
+        LEFT JOIN public.group_member as m
+        ON f.group_id = m.group_id
+        LEFT JOIN public.scene_member as sm
+        ON f.scene_id = sm.scene_id
+        JOIN public.switch as s
+        ON s.id = COALESCE(f.switch_id, m.switch_id, sm.switch_id)
+    WHERE f.dow & arg_dow > 0
+      AND f.hour = arg_hour
+      AND f.minute = arg_minute;
+$BODY$;
@@ -0,0 +1,154 @@
+-- one-off flips fire only on their date
+-- and ignore the dow mask
+ALTER TABLE public.flip
+    ADD COLUMN date DATE;
+
+ALTER TYPE public.FlipInfo
+    ADD ATTRIBUTE date DATE;
+
+/************************
+* CREATE
+*************************/
+CREATE OR REPLACE FUNCTION public.new_dated_flip(
+    arg_switch INTEGER,
+    arg_date DATE,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_direction public.FlipDirection,
+    arg_kind public.FlipKind)
+RETURNS flip
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    INSERT INTO public.flip (
+        switch_id, date, hour, minute, dow, direction, kind)
+    VALUES (arg_switch, arg_date, arg_hour, arg_minute, 0, arg_direction, arg_kind)
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.new_dated_flip(INTEGER, DATE, INTEGER, INTEGER, public.FlipDirection, public.FlipKind)
+    OWNER TO robot;
+
+/************************
+* READ
+*************************/
+CREATE OR REPLACE FUNCTION public.get_switch_flips(
+	arg_switch INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+	SELECT id, hour, minute, dow, direction, kind, date
+	FROM public.flip
+	WHERE switch_id = arg_switch
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_group_flips(
+    arg_group INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date
+    FROM public.flip
+    WHERE group_id = arg_group
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_scene_flips(
+    arg_scene INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date
+    FROM public.flip
+    WHERE scene_id = arg_scene
+    ORDER BY hour, minute
+$BODY$;
+
+-- the day is now selected by date, the dow
+-- bit is worked out from the ISO day of the week
+DROP FUNCTION public.get_flips_for_day(INTEGER);
+DROP FUNCTION public.get_flips_for_minute(INTEGER, INTEGER, INTEGER);
+
+CREATE OR REPLACE FUNCTION public.get_flips_for_day(arg_date DATE)
+    RETURNS SETOF public.SwitchFlip
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT f.hour, f.minute,
+    CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+        s.off_code
+    ELSE
+        s.on_code
+    END AS code
+    FROM public.flip as f
+        LEFT JOIN public.group_member as m
+        ON f.group_id = m.group_id
+        LEFT JOIN public.scene_member as sm
+        ON f.scene_id = sm.scene_id
+        JOIN public.switch as s
+        ON s.id = COALESCE(f.switch_id, m.switch_id, sm.switch_id)
+    WHERE CASE WHEN f.date IS NULL THEN
+        f.dow & (1 << (EXTRACT(ISODOW FROM arg_date)::INTEGER - 1)) > 0
+    ELSE
+        f.date = arg_date
+    END;
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_flips_for_minute(arg_hour INTEGER, arg_minute INTEGER, arg_date DATE)
+RETURNS SETOF public.SwitchFlip
+LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT hour, minute, code
+    FROM public.get_flips_for_day(arg_date)
+    WHERE hour = arg_hour
+      AND minute = arg_minute;
+$BODY$;
+
+ALTER FUNCTION public.get_flips_for_day(DATE)
+    OWNER TO robot;
+
+ALTER FUNCTION public.get_flips_for_minute(INTEGER, INTEGER, DATE)
+    OWNER TO robot;
+
+/************************
+* DELETE
+*************************/
+CREATE OR REPLACE FUNCTION public.remove_expired_flips(
+    arg_date DATE
+) RETURNS INTEGER
+    LANGUAGE plpgsql
+    AS $BODY$
+DECLARE ret INTEGER;
+BEGIN
+    DELETE FROM flip WHERE date < arg_date;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.remove_expired_flips(DATE)
+    OWNER TO robot;
@@ -0,0 +1,154 @@
+/************************
+* FUNCTIONS
+*************************/
+DROP FUNCTION IF EXISTS public.new_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER);
+DROP FUNCTION IF EXISTS public.update_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER);
+DROP FUNCTION IF EXISTS public.get_switch_flips(INTEGER);
+DROP FUNCTION IF EXISTS public.get_flip(INTEGER);
+DROP FUNCTION IF EXISTS public.get_group_flips(INTEGER);
+DROP FUNCTION IF EXISTS public.get_scene_flips(INTEGER);
+/************************
+-- TABLES
+*************************/
+ALTER TABLE public.flip
+    DROP CONSTRAINT IF EXISTS flip_season_check,
+    DROP COLUMN IF EXISTS season_start,
+    DROP COLUMN IF EXISTS season_end;
+/************************
+--TYPES
+*************************/
+ALTER TYPE public.FlipInfo
+    DROP ATTRIBUTE IF EXISTS season_start,
+    DROP ATTRIBUTE IF EXISTS season_end;
+/************************
+* RESTORE
+*************************/
+CREATE OR REPLACE FUNCTION public.new_flip(
+    arg_switch INTEGER,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_dow INTEGER,
+    arg_direction public.FlipDirection,
+    arg_kind public.FlipKind)
+RETURNS flip
+LANGUAGE 'plpgsql'
+
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    INSERT INTO public.flip (
+        switch_id, hour, minute, dow, direction, kind)
+    VALUES (arg_switch, arg_hour, arg_minute, arg_dow, arg_direction, arg_kind)
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.new_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind)
+    OWNER TO robot;
+
+CREATE OR REPLACE FUNCTION public.update_flip(
+    arg_id INTEGER,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_dow INTEGER,
+    arg_dir public.FlipDirection,
+    arg_kind public.FlipKind
+) RETURNS flip
+LANGUAGE plpgsql
+COST 100
+VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    UPDATE flip
+    SET hour = arg_hour,
+    minute = arg_minute,
+    dow = arg_dow,
+    direction = arg_dir,
+    kind = arg_kind
+    WHERE id = arg_id
+    RETURNING * into ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind)
+    OWNER TO robot;
+
+CREATE OR REPLACE FUNCTION public.get_switch_flips(
+	arg_switch INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+	SELECT id, hour, minute, dow, direction, kind, date
+	FROM public.flip
+	WHERE switch_id = arg_switch
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_group_flips(
+    arg_group INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date
+    FROM public.flip
+    WHERE group_id = arg_group
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_scene_flips(
+    arg_scene INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date
+    FROM public.flip
+    WHERE scene_id = arg_scene
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_flips_for_day(arg_date DATE)
+    RETURNS SETOF public.SwitchFlip
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT f.hour, f.minute,
+    CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+        s.off_code
+    ELSE
+        s.on_code
+    END AS code
+    FROM public.flip as f
+        LEFT JOIN public.group_member as m
+        ON f.group_id = m.group_id
+        LEFT JOIN public.scene_member as sm
+        ON f.scene_id = sm.scene_id
+        JOIN public.switch as s
+        ON s.id = COALESCE(f.switch_id, m.switch_id, sm.switch_id)
+    WHERE CASE WHEN f.date IS NULL THEN
+        f.dow & (1 << (EXTRACT(ISODOW FROM arg_date)::INTEGER - 1)) > 0
+    ELSE
+        f.date = arg_date
+    END;
+$BODY$;
+
+DROP FUNCTION IF EXISTS public.in_season(DATE, INTEGER, INTEGER);
@@ -0,0 +1,194 @@
+-- a season limits a recurring flip to part of
+-- the year, both ends are stored as month * 100 + day
+-- and the range wraps the new year when start > end
+ALTER TABLE public.flip
+    ADD COLUMN season_start INTEGER,
+    ADD COLUMN season_end INTEGER,
+    ADD CONSTRAINT flip_season_check CHECK ((season_start IS NULL) = (season_end IS NULL));
+
+ALTER TYPE public.FlipInfo
+    ADD ATTRIBUTE season_start INTEGER,
+    ADD ATTRIBUTE season_end INTEGER;
+
+CREATE OR REPLACE FUNCTION public.in_season(
+    arg_date DATE,
+    arg_start INTEGER,
+    arg_end INTEGER
+) RETURNS BOOLEAN
+    LANGUAGE 'sql'
+    IMMUTABLE
+AS $BODY$
+    SELECT CASE
+        WHEN arg_start IS NULL THEN
+            TRUE
+        WHEN arg_start <= arg_end THEN
+            EXTRACT(MONTH FROM arg_date) * 100 + EXTRACT(DAY FROM arg_date) BETWEEN arg_start AND arg_end
+        ELSE
+            EXTRACT(MONTH FROM arg_date) * 100 + EXTRACT(DAY FROM arg_date) >= arg_start
+            OR EXTRACT(MONTH FROM arg_date) * 100 + EXTRACT(DAY FROM arg_date) <= arg_end
+    END;
+$BODY$;
+
+ALTER FUNCTION public.in_season(DATE, INTEGER, INTEGER)
+    OWNER TO robot;
+
+/************************
+* CREATE
+*************************/
+DROP FUNCTION public.new_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind);
+
+CREATE OR REPLACE FUNCTION public.new_flip(
+    arg_switch INTEGER,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_dow INTEGER,
+    arg_direction public.FlipDirection,
+    arg_kind public.FlipKind,
+    arg_season_start INTEGER,
+    arg_season_end INTEGER)
+RETURNS flip
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    INSERT INTO public.flip (
+        switch_id, hour, minute, dow, direction, kind, season_start, season_end)
+    VALUES (arg_switch, arg_hour, arg_minute, arg_dow, arg_direction, arg_kind, arg_season_start, arg_season_end)
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.new_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER)
+    OWNER TO robot;
+
+/************************
+* READ
+*************************/
+CREATE OR REPLACE FUNCTION public.get_switch_flips(
+	arg_switch INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+	SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end
+	FROM public.flip
+	WHERE switch_id = arg_switch
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_flip(
+    arg_id INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end
+    FROM public.flip
+    WHERE id = arg_id
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_group_flips(
+    arg_group INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end
+    FROM public.flip
+    WHERE group_id = arg_group
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_scene_flips(
+    arg_scene INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end
+    FROM public.flip
+    WHERE scene_id = arg_scene
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_flips_for_day(arg_date DATE)
+    RETURNS SETOF public.SwitchFlip
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT f.hour, f.minute,
+    CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+        s.off_code
+    ELSE
+        s.on_code
+    END AS code
+    FROM public.flip as f
+        LEFT JOIN public.group_member as m
+        ON f.group_id = m.group_id
+        LEFT JOIN public.scene_member as sm
+        ON f.scene_id = sm.scene_id
+        JOIN public.switch as s
+        ON s.id = COALESCE(f.switch_id, m.switch_id, sm.switch_id)
+    WHERE CASE WHEN f.date IS NULL THEN
+        f.dow & (1 << (EXTRACT(ISODOW FROM arg_date)::INTEGER - 1)) > 0
+    ELSE
+        f.date = arg_date
+    END
+    AND public.in_season(arg_date, f.season_start, f.season_end);
+$BODY$;
+
+/************************
+* UPDATE
+*************************/
+DROP FUNCTION public.update_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind);
+
+CREATE OR REPLACE FUNCTION public.update_flip(
+    arg_id INTEGER,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_dow INTEGER,
+    arg_dir public.FlipDirection,
+    arg_kind public.FlipKind,
+    arg_season_start INTEGER,
+    arg_season_end INTEGER
+) RETURNS flip
+LANGUAGE plpgsql
+COST 100
+VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    UPDATE flip
+    SET hour = arg_hour,
+    minute = arg_minute,
+    dow = arg_dow,
+    direction = arg_dir,
+    kind = arg_kind,
+    season_start = arg_season_start,
+    season_end = arg_season_end
+    WHERE id = arg_id
+    RETURNING * into ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER)
+    OWNER TO robot;
@@ -0,0 +1,210 @@
+-- solar flips fire this many minutes before (negative)
+-- or after their special time
+ALTER TABLE public.flip
+    ADD COLUMN offset_minutes INTEGER NOT NULL DEFAULT 0;
+
+ALTER TYPE public.FlipInfo
+    ADD ATTRIBUTE offset_minutes INTEGER;
+
+/************************
+* CREATE
+*************************/
+DROP FUNCTION public.new_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER);
+
+CREATE OR REPLACE FUNCTION public.new_flip(
+    arg_switch INTEGER,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_dow INTEGER,
+    arg_direction public.FlipDirection,
+    arg_kind public.FlipKind,
+    arg_offset INTEGER,
+    arg_season_start INTEGER,
+    arg_season_end INTEGER)
+RETURNS flip
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    INSERT INTO public.flip (
+        switch_id, hour, minute, dow, direction, kind, offset_minutes, season_start, season_end)
+    VALUES (arg_switch, arg_hour, arg_minute, arg_dow, arg_direction, arg_kind, arg_offset, arg_season_start, arg_season_end)
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.new_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER, INTEGER)
+    OWNER TO robot;
+
+/************************
+* READ
+*************************/
+CREATE OR REPLACE FUNCTION public.get_switch_flips(
+	arg_switch INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+	SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes
+	FROM public.flip
+	WHERE switch_id = arg_switch
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_flip(
+    arg_id INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes
+    FROM public.flip
+    WHERE id = arg_id
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_group_flips(
+    arg_group INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes
+    FROM public.flip
+    WHERE group_id = arg_group
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_scene_flips(
+    arg_scene INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes
+    FROM public.flip
+    WHERE scene_id = arg_scene
+    ORDER BY hour, minute
+$BODY$;
+
+/************************
+* UPDATE
+*************************/
+DROP FUNCTION public.update_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER);
+
+CREATE OR REPLACE FUNCTION public.update_flip(
+    arg_id INTEGER,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_dow INTEGER,
+    arg_dir public.FlipDirection,
+    arg_kind public.FlipKind,
+    arg_offset INTEGER,
+    arg_season_start INTEGER,
+    arg_season_end INTEGER
+) RETURNS flip
+LANGUAGE plpgsql
+COST 100
+VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    UPDATE flip
+    SET hour = arg_hour,
+    minute = arg_minute,
+    dow = arg_dow,
+    direction = arg_dir,
+    kind = arg_kind,
+    offset_minutes = arg_offset,
+    season_start = arg_season_start,
+    season_end = arg_season_end
+    WHERE id = arg_id
+    RETURNING * into ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER, INTEGER)
+    OWNER TO robot;
+
+-- minutes past midnight after applying the
+-- offset, wrapped into a single day
+CREATE OR REPLACE FUNCTION public.offset_minutes(
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_offset INTEGER
+) RETURNS INTEGER
+    LANGUAGE 'sql'
+    IMMUTABLE
+AS $BODY$
+    SELECT ((arg_hour * 60 + arg_minute + arg_offset) % 1440 + 1440) % 1440;
+$BODY$;
+
+ALTER FUNCTION public.offset_minutes(INTEGER, INTEGER, INTEGER)
+    OWNER TO robot;
+
+CREATE OR REPLACE FUNCTION public.update_special_times(
+    predawn_hour INT,
+    predawn_min INT,
+    sunrise_hour INT,
+    sunrise_min INT,
+    dusk_hour INT,
+    dusk_min INT,
+    sunset_hour INT,
+    sunset_min INT
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE pd_ct INT := 0;
+DECLARE d_ct INT := 0;
+DECLARE ps_ct INT := 0;
+DECLARE s_ct INT := 0;
+BEGIN
+    INSERT INTO special_time (kind, hour, minute)
+        VALUES ('PreDawn', predawn_hour, predawn_min),
+               ('Sunrise', sunrise_hour, sunrise_min),
+               ('Dusk', dusk_hour, dusk_min),
+               ('Sunset', sunset_hour, sunset_min);
+    UPDATE flip
+    SET hour = public.offset_minutes(predawn_hour, predawn_min, offset_minutes) / 60,
+    minute = public.offset_minutes(predawn_hour, predawn_min, offset_minutes) % 60
+    WHERE kind = 'PreDawn';
+    GET DIAGNOSTICS pd_ct = ROW_COUNT;
+
+    UPDATE flip
+    SET hour = public.offset_minutes(sunrise_hour, sunrise_min, offset_minutes) / 60,
+    minute = public.offset_minutes(sunrise_hour, sunrise_min, offset_minutes) % 60
+    WHERE kind = 'Sunrise';
+    GET DIAGNOSTICS d_ct = ROW_COUNT;
+
+    UPDATE flip
+    SET hour = public.offset_minutes(dusk_hour, dusk_min, offset_minutes) / 60,
+    minute = public.offset_minutes(dusk_hour, dusk_min, offset_minutes) % 60
+    WHERE kind = 'Dusk';
+    GET DIAGNOSTICS ps_ct = ROW_COUNT;
+
+    UPDATE flip
+    SET hour = public.offset_minutes(sunset_hour, sunset_min, offset_minutes) / 60,
+    minute = public.offset_minutes(sunset_hour, sunset_min, offset_minutes) % 60
+    WHERE kind = 'Sunset';
+    GET DIAGNOSTICS s_ct = ROW_COUNT;
+    RETURN pd_ct + d_ct + ps_ct + s_ct;
+END;
+$BODY$;
@@ -0,0 +1,94 @@
+/************************
+* FUNCTIONS
+*************************/
+DROP FUNCTION IF EXISTS public.update_special_times(DATE, INT, INT, INT, INT, INT, INT, INT, INT);
+/************************
+* RESTORE
+*************************/
+CREATE OR REPLACE FUNCTION public.get_flips_for_day(arg_date DATE)
+    RETURNS SETOF public.SwitchFlip
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT f.hour, f.minute,
+    CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+        s.off_code
+    ELSE
+        s.on_code
+    END AS code
+    FROM public.flip as f
+        LEFT JOIN public.group_member as m
+        ON f.group_id = m.group_id
+        LEFT JOIN public.scene_member as sm
+        ON f.scene_id = sm.scene_id
+        JOIN public.switch as s
+        ON s.id = COALESCE(f.switch_id, m.switch_id, sm.switch_id)
+    WHERE CASE WHEN f.date IS NULL THEN
+        f.dow & (1 << (EXTRACT(ISODOW FROM arg_date)::INTEGER - 1)) > 0
+    ELSE
+        f.date = arg_date
+    END
+    AND public.in_season(arg_date, f.season_start, f.season_end);
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.update_special_times(
+    predawn_hour INT,
+    predawn_min INT,
+    sunrise_hour INT,
+    sunrise_min INT,
+    dusk_hour INT,
+    dusk_min INT,
+    sunset_hour INT,
+    sunset_min INT
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE pd_ct INT := 0;
+DECLARE d_ct INT := 0;
+DECLARE ps_ct INT := 0;
+DECLARE s_ct INT := 0;
+BEGIN
+    INSERT INTO special_time (kind, hour, minute)
+        VALUES ('PreDawn', predawn_hour, predawn_min),
+               ('Sunrise', sunrise_hour, sunrise_min),
+               ('Dusk', dusk_hour, dusk_min),
+               ('Sunset', sunset_hour, sunset_min);
+    UPDATE flip
+    SET hour = public.offset_minutes(predawn_hour, predawn_min, offset_minutes) / 60,
+    minute = public.offset_minutes(predawn_hour, predawn_min, offset_minutes) % 60
+    WHERE kind = 'PreDawn';
+    GET DIAGNOSTICS pd_ct = ROW_COUNT;
+
+    UPDATE flip
+    SET hour = public.offset_minutes(sunrise_hour, sunrise_min, offset_minutes) / 60,
+    minute = public.offset_minutes(sunrise_hour, sunrise_min, offset_minutes) % 60
+    WHERE kind = 'Sunrise';
+    GET DIAGNOSTICS d_ct = ROW_COUNT;
+
+    UPDATE flip
+    SET hour = public.offset_minutes(dusk_hour, dusk_min, offset_minutes) / 60,
+    minute = public.offset_minutes(dusk_hour, dusk_min, offset_minutes) % 60
+    WHERE kind = 'Dusk';
+    GET DIAGNOSTICS ps_ct = ROW_COUNT;
+
+    UPDATE flip
+    SET hour = public.offset_minutes(sunset_hour, sunset_min, offset_minutes) / 60,
+    minute = public.offset_minutes(sunset_hour, sunset_min, offset_minutes) % 60
+    WHERE kind = 'Sunset';
+    GET DIAGNOSTICS s_ct = ROW_COUNT;
+    RETURN pd_ct + d_ct + ps_ct + s_ct;
+END;
+$BODY$;
+
+DROP FUNCTION IF EXISTS public.get_special_times(DATE);
+/************************
+-- TABLES
+*************************/
+ALTER TABLE public.special_time
+    DROP CONSTRAINT IF EXISTS special_time_date_kind_key,
+    ALTER COLUMN date TYPE TIMESTAMP WITH TIME ZONE USING date::TIMESTAMP WITH TIME ZONE,
+    ALTER COLUMN date SET DEFAULT CURRENT_DATE;
@@ -0,0 +1,109 @@
+-- solar flips are resolved against the special
+-- time for each date instead of being rewritten,
+-- so keep one row per date and kind
+DELETE FROM public.special_time AS a
+    USING public.special_time AS b
+    WHERE a.date::DATE = b.date::DATE
+      AND a.kind = b.kind
+      AND a.id < b.id;
+
+ALTER TABLE public.special_time
+    ALTER COLUMN date TYPE DATE USING date::DATE,
+    ALTER COLUMN date SET DEFAULT CURRENT_DATE,
+    ADD CONSTRAINT special_time_date_kind_key UNIQUE (date, kind);
+
+/************************
+* READ
+*************************/
+CREATE OR REPLACE FUNCTION public.get_special_times(arg_date DATE)
+    RETURNS SETOF public.special_time
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 10
+AS $BODY$
+    SELECT DISTINCT ON (kind) *
+    FROM public.special_time
+    WHERE hour IS NOT NULL
+      AND minute IS NOT NULL
+    ORDER BY kind, abs(date - arg_date), date DESC;
+$BODY$;
+
+ALTER FUNCTION public.get_special_times(DATE)
+    OWNER TO robot;
+
+CREATE OR REPLACE FUNCTION public.get_flips_for_day(arg_date DATE)
+    RETURNS SETOF public.SwitchFlip
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT t.minutes / 60 AS hour, t.minutes % 60 AS minute, t.code
+    FROM (
+        SELECT CASE WHEN st.kind IS NULL THEN
+            f.hour * 60 + f.minute
+        ELSE
+            public.offset_minutes(st.hour, st.minute, f.offset_minutes)
+        END AS minutes,
+        CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+            s.off_code
+        ELSE
+            s.on_code
+        END AS code
+        FROM public.flip as f
+            LEFT JOIN public.get_special_times(arg_date) as st
+            ON st.kind = f.kind
+            AND f.kind <> 'Custom'
+            LEFT JOIN public.group_member as m
+            ON f.group_id = m.group_id
+            LEFT JOIN public.scene_member as sm
+            ON f.scene_id = sm.scene_id
+            JOIN public.switch as s
+            ON s.id = COALESCE(f.switch_id, m.switch_id, sm.switch_id)
+        WHERE CASE WHEN f.date IS NULL THEN
+            f.dow & (1 << (EXTRACT(ISODOW FROM arg_date)::INTEGER - 1)) > 0
+        ELSE
+            f.date = arg_date
+        END
+        AND public.in_season(arg_date, f.season_start, f.season_end)
+    ) AS t;
+$BODY$;
+
+/************************
+* UPDATE
+*************************/
+DROP FUNCTION public.update_special_times(INT, INT, INT, INT, INT, INT, INT, INT);
+
+CREATE OR REPLACE FUNCTION public.update_special_times(
+    arg_date DATE,
+    predawn_hour INT,
+    predawn_min INT,
+    sunrise_hour INT,
+    sunrise_min INT,
+    dusk_hour INT,
+    dusk_min INT,
+    sunset_hour INT,
+    sunset_min INT
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret INT := 0;
+BEGIN
+    INSERT INTO special_time (date, kind, hour, minute)
+        VALUES (arg_date, 'PreDawn', predawn_hour, predawn_min),
+               (arg_date, 'Sunrise', sunrise_hour, sunrise_min),
+               (arg_date, 'Dusk', dusk_hour, dusk_min),
+               (arg_date, 'Sunset', sunset_hour, sunset_min)
+    ON CONFLICT (date, kind) DO UPDATE
+        SET hour = EXCLUDED.hour,
+        minute = EXCLUDED.minute;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_special_times(DATE, INT, INT, INT, INT, INT, INT, INT, INT)
+    OWNER TO robot;
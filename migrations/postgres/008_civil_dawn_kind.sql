@@ -0,0 +1,5 @@
+-- ALTER TYPE ... ADD VALUE can't be run inside a
+-- transaction block before PostgreSQL 12, so each
+-- new kind is a migration of its own that runs
+-- outside of one
+ALTER TYPE public.FlipKind ADD VALUE IF NOT EXISTS 'CivilDawn';
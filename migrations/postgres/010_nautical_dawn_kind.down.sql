@@ -0,0 +1,8 @@
+-- postgres can't drop a value from an enum, NauticalDawn
+-- stays on the type but nothing is left using it
+UPDATE public.flip
+    SET kind = 'Custom'
+    WHERE kind::TEXT = 'NauticalDawn';
+
+DELETE FROM public.special_time
+    WHERE kind::TEXT = 'NauticalDawn';
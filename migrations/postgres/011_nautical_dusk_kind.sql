@@ -0,0 +1,2 @@
+-- runs outside a transaction, see 008_civil_dawn_kind
+ALTER TYPE public.FlipKind ADD VALUE IF NOT EXISTS 'NauticalDusk';
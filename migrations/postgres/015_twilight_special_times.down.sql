@@ -0,0 +1,34 @@
+DROP FUNCTION public.update_special_time(DATE, public.FlipKind, INT, INT);
+
+CREATE OR REPLACE FUNCTION public.update_special_times(
+    arg_date DATE,
+    predawn_hour INT,
+    predawn_min INT,
+    sunrise_hour INT,
+    sunrise_min INT,
+    dusk_hour INT,
+    dusk_min INT,
+    sunset_hour INT,
+    sunset_min INT
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret INT := 0;
+BEGIN
+    INSERT INTO special_time (date, kind, hour, minute)
+        VALUES (arg_date, 'PreDawn', predawn_hour, predawn_min),
+               (arg_date, 'Sunrise', sunrise_hour, sunrise_min),
+               (arg_date, 'Dusk', dusk_hour, dusk_min),
+               (arg_date, 'Sunset', sunset_hour, sunset_min)
+    ON CONFLICT (date, kind) DO UPDATE
+        SET hour = EXCLUDED.hour,
+        minute = EXCLUDED.minute;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_special_times(DATE, INT, INT, INT, INT, INT, INT, INT, INT)
+    OWNER TO robot;
@@ -0,0 +1,32 @@
+/************************
+* UPDATE
+*************************/
+-- refresher records whichever kinds it could work
+-- out for a date, so special times are recorded
+-- one kind at a time
+DROP FUNCTION public.update_special_times(DATE, INT, INT, INT, INT, INT, INT, INT, INT);
+
+CREATE OR REPLACE FUNCTION public.update_special_time(
+    arg_date DATE,
+    arg_kind public.FlipKind,
+    arg_hour INT,
+    arg_minute INT
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret INT := 0;
+BEGIN
+    INSERT INTO special_time (date, kind, hour, minute)
+        VALUES (arg_date, arg_kind, arg_hour, arg_minute)
+    ON CONFLICT (date, kind) DO UPDATE
+        SET hour = EXCLUDED.hour,
+        minute = EXCLUDED.minute;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_special_time(DATE, public.FlipKind, INT, INT)
+    OWNER TO robot;
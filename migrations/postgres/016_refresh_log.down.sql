@@ -0,0 +1,11 @@
+DROP FUNCTION IF EXISTS public.new_refresh(DATE, TEXT, BOOLEAN, INTEGER, TEXT);
+DROP FUNCTION IF EXISTS public.get_last_refresh();
+DROP FUNCTION IF EXISTS public.get_latest_special_date();
+/************************
+-- TABLES
+*************************/
+DROP TABLE IF EXISTS public.refresh_log;
+/************************
+--SEQUENCES
+*************************/
+DROP SEQUENCE IF EXISTS public.refresh_log_id_seq;
@@ -0,0 +1,97 @@
+/************************
+-- SEQUENCES
+*************************/
+CREATE SEQUENCE public.refresh_log_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.refresh_log_id_seq
+    OWNER to robot;
+
+/************************
+-- TABLES
+*************************/
+-- one row for each refresher run, source is
+-- the provider the times came from or `stored`
+-- when none could be reached
+CREATE TABLE public.refresh_log
+(
+    id INTEGER NOT NULL DEFAULT nextval('refresh_log_id_seq'::regclass),
+    refreshed TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    date DATE NOT NULL,
+    source TEXT NOT NULL,
+    succeeded BOOLEAN NOT NULL,
+    attempts INTEGER NOT NULL,
+    message TEXT,
+    CONSTRAINT refresh_log_pkey PRIMARY KEY (id)
+)
+WITH (
+    OIDS = FALSE
+)
+TABLESPACE pg_default;
+
+ALTER TABLE public.refresh_log
+    OWNER TO robot;
+
+/************************
+* CREATE
+*************************/
+CREATE OR REPLACE FUNCTION public.new_refresh(
+    arg_date DATE,
+    arg_source TEXT,
+    arg_succeeded BOOLEAN,
+    arg_attempts INTEGER,
+    arg_message TEXT
+) RETURNS refresh_log
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret refresh_log;
+BEGIN
+    INSERT INTO refresh_log (date, source, succeeded, attempts, message)
+    VALUES (arg_date, arg_source, arg_succeeded, arg_attempts, arg_message)
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.new_refresh(DATE, TEXT, BOOLEAN, INTEGER, TEXT)
+    OWNER TO robot;
+
+/************************
+* READ
+*************************/
+CREATE OR REPLACE FUNCTION public.get_last_refresh()
+    RETURNS SETOF public.refresh_log
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1
+AS $BODY$
+    SELECT *
+    FROM public.refresh_log
+    ORDER BY refreshed DESC, id DESC
+    LIMIT 1;
+$BODY$;
+
+ALTER FUNCTION public.get_last_refresh()
+    OWNER TO robot;
+
+CREATE OR REPLACE FUNCTION public.get_latest_special_date()
+    RETURNS DATE
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+AS $BODY$
+    SELECT MAX(date)
+    FROM public.special_time
+    WHERE hour IS NOT NULL
+      AND minute IS NOT NULL;
+$BODY$;
+
+ALTER FUNCTION public.get_latest_special_date()
+    OWNER TO robot;
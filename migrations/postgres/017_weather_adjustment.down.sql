@@ -0,0 +1,31 @@
+DROP FUNCTION public.update_special_time(DATE, public.FlipKind, INT, INT, INT);
+
+CREATE OR REPLACE FUNCTION public.update_special_time(
+    arg_date DATE,
+    arg_kind public.FlipKind,
+    arg_hour INT,
+    arg_minute INT
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret INT := 0;
+BEGIN
+    INSERT INTO special_time (date, kind, hour, minute)
+        VALUES (arg_date, arg_kind, arg_hour, arg_minute)
+    ON CONFLICT (date, kind) DO UPDATE
+        SET hour = EXCLUDED.hour,
+        minute = EXCLUDED.minute;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_special_time(DATE, public.FlipKind, INT, INT)
+    OWNER TO robot;
+/************************
+-- TABLES
+*************************/
+ALTER TABLE public.special_time
+    DROP COLUMN IF EXISTS adjustment;
@@ -0,0 +1,40 @@
+/************************
+-- TABLES
+*************************/
+-- the minutes refresher moved a special time by
+-- for the weather, hour and minute already have
+-- it applied
+ALTER TABLE public.special_time
+    ADD COLUMN IF NOT EXISTS adjustment INTEGER NOT NULL DEFAULT 0;
+
+/************************
+* UPDATE
+*************************/
+DROP FUNCTION public.update_special_time(DATE, public.FlipKind, INT, INT);
+
+CREATE OR REPLACE FUNCTION public.update_special_time(
+    arg_date DATE,
+    arg_kind public.FlipKind,
+    arg_hour INT,
+    arg_minute INT,
+    arg_adjustment INT
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret INT := 0;
+BEGIN
+    INSERT INTO special_time (date, kind, hour, minute, adjustment)
+        VALUES (arg_date, arg_kind, arg_hour, arg_minute, arg_adjustment)
+    ON CONFLICT (date, kind) DO UPDATE
+        SET hour = EXCLUDED.hour,
+        minute = EXCLUDED.minute,
+        adjustment = EXCLUDED.adjustment;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_special_time(DATE, public.FlipKind, INT, INT, INT)
+    OWNER TO robot;
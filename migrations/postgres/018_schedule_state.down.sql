@@ -0,0 +1,6 @@
+DROP FUNCTION IF EXISTS public.update_last_processed(TIMESTAMP WITH TIME ZONE);
+DROP FUNCTION IF EXISTS public.get_last_processed();
+/************************
+-- TABLES
+*************************/
+DROP TABLE IF EXISTS public.schedule_state;
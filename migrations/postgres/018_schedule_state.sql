@@ -0,0 +1,61 @@
+/************************
+-- TABLES
+*************************/
+-- a single row, the instant the scheduler last
+-- sent the flips that were due so a restart
+-- doesn't send them again
+CREATE TABLE public.schedule_state
+(
+    id INTEGER NOT NULL DEFAULT 1,
+    last_processed TIMESTAMP WITH TIME ZONE NOT NULL,
+    CONSTRAINT schedule_state_pkey PRIMARY KEY (id),
+    CONSTRAINT schedule_state_single CHECK (id = 1)
+)
+WITH (
+    OIDS = FALSE
+)
+TABLESPACE pg_default;
+
+ALTER TABLE public.schedule_state
+    OWNER TO robot;
+
+/************************
+* READ
+*************************/
+CREATE OR REPLACE FUNCTION public.get_last_processed()
+    RETURNS TIMESTAMP WITH TIME ZONE
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+AS $BODY$
+    SELECT last_processed
+    FROM public.schedule_state
+    WHERE id = 1;
+$BODY$;
+
+ALTER FUNCTION public.get_last_processed()
+    OWNER TO robot;
+
+/************************
+* UPDATE
+*************************/
+CREATE OR REPLACE FUNCTION public.update_last_processed(
+    arg_last_processed TIMESTAMP WITH TIME ZONE
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret INT := 0;
+BEGIN
+    INSERT INTO schedule_state (id, last_processed)
+        VALUES (1, arg_last_processed)
+    ON CONFLICT (id) DO UPDATE
+        SET last_processed = EXCLUDED.last_processed;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_last_processed(TIMESTAMP WITH TIME ZONE)
+    OWNER TO robot;
@@ -0,0 +1,6 @@
+DROP FUNCTION IF EXISTS public.update_vacation(BOOLEAN);
+DROP FUNCTION IF EXISTS public.get_vacation();
+/************************
+-- TABLES
+*************************/
+DROP TABLE IF EXISTS public.vacation;
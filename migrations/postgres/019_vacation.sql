@@ -0,0 +1,60 @@
+/************************
+-- TABLES
+*************************/
+-- a single row, whether the scheduler should
+-- make the house look lived-in while we are away
+CREATE TABLE public.vacation
+(
+    id INTEGER NOT NULL DEFAULT 1,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    CONSTRAINT vacation_pkey PRIMARY KEY (id),
+    CONSTRAINT vacation_single CHECK (id = 1)
+)
+WITH (
+    OIDS = FALSE
+)
+TABLESPACE pg_default;
+
+ALTER TABLE public.vacation
+    OWNER TO robot;
+
+/************************
+* READ
+*************************/
+CREATE OR REPLACE FUNCTION public.get_vacation()
+    RETURNS BOOLEAN
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+AS $BODY$
+    SELECT COALESCE((SELECT enabled
+                     FROM public.vacation
+                     WHERE id = 1), FALSE);
+$BODY$;
+
+ALTER FUNCTION public.get_vacation()
+    OWNER TO robot;
+
+/************************
+* UPDATE
+*************************/
+CREATE OR REPLACE FUNCTION public.update_vacation(
+    arg_enabled BOOLEAN
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret INT := 0;
+BEGIN
+    INSERT INTO vacation (id, enabled)
+        VALUES (1, arg_enabled)
+    ON CONFLICT (id) DO UPDATE
+        SET enabled = EXCLUDED.enabled;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_vacation(BOOLEAN)
+    OWNER TO robot;
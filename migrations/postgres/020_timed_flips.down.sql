@@ -0,0 +1,213 @@
+/************************
+* FUNCTIONS
+*************************/
+DROP FUNCTION IF EXISTS public.new_pending_off(INTEGER, TIMESTAMP WITH TIME ZONE);
+DROP FUNCTION IF EXISTS public.new_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER, INTEGER, INTEGER);
+DROP FUNCTION IF EXISTS public.get_pending_offs();
+DROP FUNCTION IF EXISTS public.remove_pending_off(INTEGER);
+DROP FUNCTION IF EXISTS public.update_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER, INTEGER, INTEGER);
+DROP FUNCTION IF EXISTS public.get_switch_flips(INTEGER);
+DROP FUNCTION IF EXISTS public.get_flip(INTEGER);
+DROP FUNCTION IF EXISTS public.get_group_flips(INTEGER);
+DROP FUNCTION IF EXISTS public.get_scene_flips(INTEGER);
+DROP FUNCTION IF EXISTS public.get_flips_for_minute(INTEGER, INTEGER, DATE);
+DROP FUNCTION IF EXISTS public.get_flips_for_day(DATE);
+/************************
+-- TABLES
+*************************/
+DROP TABLE IF EXISTS public.pending_off;
+ALTER TABLE public.flip
+    DROP CONSTRAINT IF EXISTS flip_duration_positive,
+    DROP COLUMN IF EXISTS duration;
+/************************
+--SEQUENCES
+*************************/
+DROP SEQUENCE IF EXISTS public.pending_off_id_seq;
+/************************
+--TYPES
+*************************/
+ALTER TYPE public.FlipInfo
+    DROP ATTRIBUTE IF EXISTS duration;
+ALTER TYPE public.SwitchFlip
+    DROP ATTRIBUTE IF EXISTS duration;
+/************************
+* RESTORE
+*************************/
+CREATE OR REPLACE FUNCTION public.new_flip(
+    arg_switch INTEGER,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_dow INTEGER,
+    arg_direction public.FlipDirection,
+    arg_kind public.FlipKind,
+    arg_offset INTEGER,
+    arg_season_start INTEGER,
+    arg_season_end INTEGER)
+RETURNS flip
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    INSERT INTO public.flip (
+        switch_id, hour, minute, dow, direction, kind, offset_minutes, season_start, season_end)
+    VALUES (arg_switch, arg_hour, arg_minute, arg_dow, arg_direction, arg_kind, arg_offset, arg_season_start, arg_season_end)
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.new_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER, INTEGER)
+    OWNER TO robot;
+
+CREATE OR REPLACE FUNCTION public.update_flip(
+    arg_id INTEGER,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_dow INTEGER,
+    arg_dir public.FlipDirection,
+    arg_kind public.FlipKind,
+    arg_offset INTEGER,
+    arg_season_start INTEGER,
+    arg_season_end INTEGER
+) RETURNS flip
+LANGUAGE plpgsql
+COST 100
+VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    UPDATE flip
+    SET hour = arg_hour,
+    minute = arg_minute,
+    dow = arg_dow,
+    direction = arg_dir,
+    kind = arg_kind,
+    offset_minutes = arg_offset,
+    season_start = arg_season_start,
+    season_end = arg_season_end
+    WHERE id = arg_id
+    RETURNING * into ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER, INTEGER)
+    OWNER TO robot;
+
+CREATE OR REPLACE FUNCTION public.get_switch_flips(
+    arg_switch INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes
+    FROM public.flip
+    WHERE switch_id = arg_switch
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_flip(
+    arg_id INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes
+    FROM public.flip
+    WHERE id = arg_id
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_group_flips(
+    arg_group INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes
+    FROM public.flip
+    WHERE group_id = arg_group
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_scene_flips(
+    arg_scene INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes
+    FROM public.flip
+    WHERE scene_id = arg_scene
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_flips_for_day(arg_date DATE)
+    RETURNS SETOF public.SwitchFlip
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT t.minutes / 60 AS hour, t.minutes % 60 AS minute, t.code
+    FROM (
+        SELECT CASE WHEN st.kind IS NULL THEN
+            f.hour * 60 + f.minute
+        ELSE
+            public.offset_minutes(st.hour, st.minute, f.offset_minutes)
+        END AS minutes,
+        CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+            s.off_code
+        ELSE
+            s.on_code
+        END AS code
+        FROM public.flip as f
+            LEFT JOIN public.get_special_times(arg_date) as st
+            ON st.kind = f.kind
+            AND f.kind <> 'Custom'
+            LEFT JOIN public.group_member as m
+            ON f.group_id = m.group_id
+            LEFT JOIN public.scene_member as sm
+            ON f.scene_id = sm.scene_id
+            JOIN public.switch as s
+            ON s.id = COALESCE(f.switch_id, m.switch_id, sm.switch_id)
+        WHERE CASE WHEN f.date IS NULL THEN
+            f.dow & (1 << (EXTRACT(ISODOW FROM arg_date)::INTEGER - 1)) > 0
+        ELSE
+            f.date = arg_date
+        END
+        AND public.in_season(arg_date, f.season_start, f.season_end)
+    ) AS t;
+$BODY$;
+
+ALTER FUNCTION public.get_flips_for_day(DATE)
+    OWNER TO robot;
+
+CREATE OR REPLACE FUNCTION public.get_flips_for_minute(arg_hour INTEGER, arg_minute INTEGER, arg_date DATE)
+RETURNS SETOF public.SwitchFlip
+LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT hour, minute, code
+    FROM public.get_flips_for_day(arg_date)
+    WHERE hour = arg_hour
+      AND minute = arg_minute;
+$BODY$;
+
+ALTER FUNCTION public.get_flips_for_minute(INTEGER, INTEGER, DATE)
+    OWNER TO robot;
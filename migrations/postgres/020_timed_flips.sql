@@ -0,0 +1,300 @@
+/************************
+-- SEQUENCES
+*************************/
+CREATE SEQUENCE public.pending_off_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.pending_off_id_seq
+    OWNER to robot;
+
+/************************
+-- TABLES
+*************************/
+-- minutes an On flip leaves its switches on
+-- before they are turned back off
+ALTER TABLE public.flip
+    ADD COLUMN duration INTEGER,
+    ADD CONSTRAINT flip_duration_positive CHECK (duration > 0);
+
+-- the off codes waiting to be sent after a
+-- timed flip, until the scheduler sends them
+CREATE TABLE public.pending_off
+(
+    id INTEGER NOT NULL DEFAULT nextval('pending_off_id_seq'::regclass),
+    code INTEGER NOT NULL,
+    due TIMESTAMP WITH TIME ZONE NOT NULL,
+    CONSTRAINT pending_off_pkey PRIMARY KEY (id)
+)
+WITH (
+    OIDS = FALSE
+)
+TABLESPACE pg_default;
+
+ALTER TABLE public.pending_off
+    OWNER TO robot;
+
+/************************
+--TYPES
+*************************/
+ALTER TYPE public.FlipInfo
+    ADD ATTRIBUTE duration INTEGER;
+
+-- only set for the codes that turn a switch on
+ALTER TYPE public.SwitchFlip
+    ADD ATTRIBUTE duration INTEGER;
+
+/************************
+* CREATE
+*************************/
+CREATE OR REPLACE FUNCTION public.new_pending_off(
+    arg_code INTEGER,
+    arg_due TIMESTAMP WITH TIME ZONE
+) RETURNS pending_off
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret pending_off;
+BEGIN
+    INSERT INTO pending_off (code, due)
+    VALUES (arg_code, arg_due)
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.new_pending_off(INTEGER, TIMESTAMP WITH TIME ZONE)
+    OWNER TO robot;
+
+DROP FUNCTION public.new_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER, INTEGER);
+
+CREATE OR REPLACE FUNCTION public.new_flip(
+    arg_switch INTEGER,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_dow INTEGER,
+    arg_direction public.FlipDirection,
+    arg_kind public.FlipKind,
+    arg_offset INTEGER,
+    arg_duration INTEGER,
+    arg_season_start INTEGER,
+    arg_season_end INTEGER)
+RETURNS flip
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    INSERT INTO public.flip (
+        switch_id, hour, minute, dow, direction, kind, offset_minutes, duration, season_start, season_end)
+    VALUES (arg_switch, arg_hour, arg_minute, arg_dow, arg_direction, arg_kind, arg_offset, arg_duration, arg_season_start, arg_season_end)
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.new_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER, INTEGER, INTEGER)
+    OWNER TO robot;
+
+/************************
+* READ
+*************************/
+CREATE OR REPLACE FUNCTION public.get_pending_offs()
+    RETURNS SETOF public.pending_off
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 100
+AS $BODY$
+    SELECT *
+    FROM public.pending_off
+    ORDER BY due, id;
+$BODY$;
+
+ALTER FUNCTION public.get_pending_offs()
+    OWNER TO robot;
+
+CREATE OR REPLACE FUNCTION public.get_switch_flips(
+    arg_switch INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+    FROM public.flip
+    WHERE switch_id = arg_switch
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_flip(
+    arg_id INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+    FROM public.flip
+    WHERE id = arg_id
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_group_flips(
+    arg_group INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+    FROM public.flip
+    WHERE group_id = arg_group
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_scene_flips(
+    arg_scene INTEGER
+)
+    RETURNS SETOF FlipInfo
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT id, hour, minute, dow, direction, kind, date, season_start, season_end, offset_minutes, duration
+    FROM public.flip
+    WHERE scene_id = arg_scene
+    ORDER BY hour, minute
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_flips_for_day(arg_date DATE)
+    RETURNS SETOF public.SwitchFlip
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT t.minutes / 60 AS hour, t.minutes % 60 AS minute, t.code, t.duration
+    FROM (
+        SELECT CASE WHEN st.kind IS NULL THEN
+            f.hour * 60 + f.minute
+        ELSE
+            public.offset_minutes(st.hour, st.minute, f.offset_minutes)
+        END AS minutes,
+        CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+            s.off_code
+        ELSE
+            s.on_code
+        END AS code,
+        CASE WHEN f.direction = 'Off' OR sm.direction = 'Off' THEN
+            NULL
+        ELSE
+            f.duration
+        END AS duration
+        FROM public.flip as f
+            LEFT JOIN public.get_special_times(arg_date) as st
+            ON st.kind = f.kind
+            AND f.kind <> 'Custom'
+            LEFT JOIN public.group_member as m
+            ON f.group_id = m.group_id
+            LEFT JOIN public.scene_member as sm
+            ON f.scene_id = sm.scene_id
+            JOIN public.switch as s
+            ON s.id = COALESCE(f.switch_id, m.switch_id, sm.switch_id)
+        WHERE CASE WHEN f.date IS NULL THEN
+            f.dow & (1 << (EXTRACT(ISODOW FROM arg_date)::INTEGER - 1)) > 0
+        ELSE
+            f.date = arg_date
+        END
+        AND public.in_season(arg_date, f.season_start, f.season_end)
+    ) AS t;
+$BODY$;
+
+CREATE OR REPLACE FUNCTION public.get_flips_for_minute(arg_hour INTEGER, arg_minute INTEGER, arg_date DATE)
+RETURNS SETOF public.SwitchFlip
+LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1000
+AS $BODY$
+    SELECT hour, minute, code, duration
+    FROM public.get_flips_for_day(arg_date)
+    WHERE hour = arg_hour
+      AND minute = arg_minute;
+$BODY$;
+
+/************************
+* UPDATE
+*************************/
+DROP FUNCTION public.update_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER, INTEGER);
+
+CREATE OR REPLACE FUNCTION public.update_flip(
+    arg_id INTEGER,
+    arg_hour INTEGER,
+    arg_minute INTEGER,
+    arg_dow INTEGER,
+    arg_dir public.FlipDirection,
+    arg_kind public.FlipKind,
+    arg_offset INTEGER,
+    arg_duration INTEGER,
+    arg_season_start INTEGER,
+    arg_season_end INTEGER
+) RETURNS flip
+LANGUAGE plpgsql
+COST 100
+VOLATILE
+AS $BODY$
+DECLARE ret flip;
+BEGIN
+    UPDATE flip
+    SET hour = arg_hour,
+    minute = arg_minute,
+    dow = arg_dow,
+    direction = arg_dir,
+    kind = arg_kind,
+    offset_minutes = arg_offset,
+    duration = arg_duration,
+    season_start = arg_season_start,
+    season_end = arg_season_end
+    WHERE id = arg_id
+    RETURNING * into ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_flip(INTEGER, INTEGER, INTEGER, INTEGER, public.FlipDirection, public.FlipKind, INTEGER, INTEGER, INTEGER, INTEGER)
+    OWNER TO robot;
+
+/************************
+* DELETE
+*************************/
+-- clear out an off once it has been sent
+CREATE OR REPLACE FUNCTION public.remove_pending_off(
+    arg_id INTEGER
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret INT := 0;
+BEGIN
+    DELETE FROM pending_off
+    WHERE id = arg_id;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.remove_pending_off(INTEGER)
+    OWNER TO robot;
@@ -0,0 +1,11 @@
+DROP FUNCTION IF EXISTS public.remove_expired_holds(TIMESTAMP WITH TIME ZONE);
+DROP FUNCTION IF EXISTS public.remove_hold(INTEGER);
+DROP FUNCTION IF EXISTS public.update_pause(BOOLEAN, TIMESTAMP WITH TIME ZONE);
+DROP FUNCTION IF EXISTS public.get_holds();
+DROP FUNCTION IF EXISTS public.get_pause();
+DROP FUNCTION IF EXISTS public.new_hold(INTEGER, TIMESTAMP WITH TIME ZONE);
+/************************
+-- TABLES
+*************************/
+DROP TABLE IF EXISTS public.switch_hold;
+DROP TABLE IF EXISTS public.schedule_pause;
@@ -0,0 +1,166 @@
+/************************
+-- TABLES
+*************************/
+-- a single row, whether the scheduler is holding
+-- back every flip and when it should start again
+CREATE TABLE public.schedule_pause
+(
+    id INTEGER NOT NULL DEFAULT 1,
+    paused BOOLEAN NOT NULL DEFAULT FALSE,
+    resume_at TIMESTAMP WITH TIME ZONE,
+    CONSTRAINT schedule_pause_pkey PRIMARY KEY (id),
+    CONSTRAINT schedule_pause_single CHECK (id = 1)
+)
+WITH (
+    OIDS = FALSE
+)
+TABLESPACE pg_default;
+
+ALTER TABLE public.schedule_pause
+    OWNER TO robot;
+
+-- switches the scheduler leaves alone, until
+-- the hold is removed when `until` is NULL
+CREATE TABLE public.switch_hold
+(
+    switch_id INTEGER NOT NULL,
+    until TIMESTAMP WITH TIME ZONE,
+    CONSTRAINT switch_hold_pkey PRIMARY KEY (switch_id),
+    CONSTRAINT switch_hold_switch FOREIGN KEY (switch_id)
+        REFERENCES public.switch (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE
+)
+WITH (
+    OIDS = FALSE
+)
+TABLESPACE pg_default;
+
+ALTER TABLE public.switch_hold
+    OWNER TO robot;
+
+/************************
+* CREATE
+*************************/
+CREATE OR REPLACE FUNCTION public.new_hold(
+    arg_switch INTEGER,
+    arg_until TIMESTAMP WITH TIME ZONE
+) RETURNS switch_hold
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret switch_hold;
+BEGIN
+    INSERT INTO switch_hold (switch_id, until)
+        VALUES (arg_switch, arg_until)
+    ON CONFLICT (switch_id) DO UPDATE
+        SET until = EXCLUDED.until
+    RETURNING * INTO ret;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.new_hold(INTEGER, TIMESTAMP WITH TIME ZONE)
+    OWNER TO robot;
+
+/************************
+* READ
+*************************/
+CREATE OR REPLACE FUNCTION public.get_pause()
+    RETURNS SETOF public.schedule_pause
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 1
+AS $BODY$
+    SELECT *
+    FROM public.schedule_pause
+    WHERE id = 1;
+$BODY$;
+
+ALTER FUNCTION public.get_pause()
+    OWNER TO robot;
+
+CREATE OR REPLACE FUNCTION public.get_holds()
+    RETURNS SETOF public.switch_hold
+    LANGUAGE 'sql'
+    COST 100
+    VOLATILE
+    ROWS 100
+AS $BODY$
+    SELECT *
+    FROM public.switch_hold
+    ORDER BY switch_id;
+$BODY$;
+
+ALTER FUNCTION public.get_holds()
+    OWNER TO robot;
+
+/************************
+* UPDATE
+*************************/
+CREATE OR REPLACE FUNCTION public.update_pause(
+    arg_paused BOOLEAN,
+    arg_resume_at TIMESTAMP WITH TIME ZONE
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret INT := 0;
+BEGIN
+    INSERT INTO schedule_pause (id, paused, resume_at)
+        VALUES (1, arg_paused, arg_resume_at)
+    ON CONFLICT (id) DO UPDATE
+        SET paused = EXCLUDED.paused,
+        resume_at = EXCLUDED.resume_at;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.update_pause(BOOLEAN, TIMESTAMP WITH TIME ZONE)
+    OWNER TO robot;
+
+/************************
+* DELETE
+*************************/
+CREATE OR REPLACE FUNCTION public.remove_hold(
+    arg_switch INTEGER
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret INT := 0;
+BEGIN
+    DELETE FROM switch_hold
+    WHERE switch_id = arg_switch;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.remove_hold(INTEGER)
+    OWNER TO robot;
+
+-- clear out the holds that have run out
+CREATE OR REPLACE FUNCTION public.remove_expired_holds(
+    arg_through TIMESTAMP WITH TIME ZONE
+) RETURNS INT
+LANGUAGE plpgsql
+    COST 100
+    VOLATILE
+AS $BODY$
+DECLARE ret INT := 0;
+BEGIN
+    DELETE FROM switch_hold
+    WHERE until <= arg_through;
+    GET DIAGNOSTICS ret = ROW_COUNT;
+    RETURN ret;
+END;
+$BODY$;
+
+ALTER FUNCTION public.remove_expired_holds(TIMESTAMP WITH TIME ZONE)
+    OWNER TO robot;
@@ -0,0 +1,51 @@
+/************************
+-- TABLES
+*************************/
+CREATE TABLE IF NOT EXISTS switch
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    on_code INTEGER NOT NULL,
+    off_code INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS flip
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    switch_id INTEGER NOT NULL
+        REFERENCES switch (id)
+        ON DELETE CASCADE,
+    hour INTEGER NOT NULL,
+    minute INTEGER NOT NULL,
+    direction TEXT NOT NULL
+        CHECK (direction IN ('Off', 'On')),
+    dow INTEGER NOT NULL,
+    kind TEXT NOT NULL DEFAULT 'Custom'
+        CHECK (kind IN ('Custom', 'PreDawn', 'Sunrise', 'Dusk', 'Sunset'))
+);
+
+CREATE TABLE IF NOT EXISTS authorize
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    token TEXT NOT NULL UNIQUE,
+    issued TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS token
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    auth_id INTEGER NOT NULL
+        REFERENCES authorize (id)
+        ON DELETE CASCADE,
+    shared BLOB NOT NULL,
+    private BLOB NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS special_time
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    date TEXT NOT NULL DEFAULT CURRENT_DATE,
+    kind TEXT NOT NULL,
+    hour INTEGER,
+    minute INTEGER
+);
@@ -0,0 +1,45 @@
+CREATE TABLE IF NOT EXISTS switch_group
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS group_member
+(
+    group_id INTEGER NOT NULL
+        REFERENCES switch_group (id)
+        ON DELETE CASCADE,
+    switch_id INTEGER NOT NULL
+        REFERENCES switch (id)
+        ON DELETE CASCADE,
+    PRIMARY KEY (group_id, switch_id)
+);
+
+-- SQLite can't drop the NOT NULL on flip.switch_id
+-- so the table is rebuilt with a group_id column
+CREATE TABLE flip_target
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    switch_id INTEGER
+        REFERENCES switch (id)
+        ON DELETE CASCADE,
+    group_id INTEGER
+        REFERENCES switch_group (id)
+        ON DELETE CASCADE,
+    hour INTEGER NOT NULL,
+    minute INTEGER NOT NULL,
+    direction TEXT NOT NULL
+        CHECK (direction IN ('Off', 'On')),
+    dow INTEGER NOT NULL,
+    kind TEXT NOT NULL DEFAULT 'Custom'
+        CHECK (kind IN ('Custom', 'PreDawn', 'Sunrise', 'Dusk', 'Sunset')),
+    CHECK ((switch_id IS NULL) <> (group_id IS NULL))
+);
+
+INSERT INTO flip_target (id, switch_id, hour, minute, direction, dow, kind)
+SELECT id, switch_id, hour, minute, direction, dow, kind
+FROM flip;
+
+DROP TABLE flip;
+
+ALTER TABLE flip_target RENAME TO flip;
@@ -0,0 +1,50 @@
+CREATE TABLE IF NOT EXISTS scene
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS scene_member
+(
+    scene_id INTEGER NOT NULL
+        REFERENCES scene (id)
+        ON DELETE CASCADE,
+    switch_id INTEGER NOT NULL
+        REFERENCES switch (id)
+        ON DELETE CASCADE,
+    direction TEXT NOT NULL
+        CHECK (direction IN ('Off', 'On')),
+    PRIMARY KEY (scene_id, switch_id)
+);
+
+-- rebuilt to add scene_id to the
+-- flip_target check
+CREATE TABLE flip_target
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    switch_id INTEGER
+        REFERENCES switch (id)
+        ON DELETE CASCADE,
+    group_id INTEGER
+        REFERENCES switch_group (id)
+        ON DELETE CASCADE,
+    scene_id INTEGER
+        REFERENCES scene (id)
+        ON DELETE CASCADE,
+    hour INTEGER NOT NULL,
+    minute INTEGER NOT NULL,
+    direction TEXT NOT NULL
+        CHECK (direction IN ('Off', 'On')),
+    dow INTEGER NOT NULL,
+    kind TEXT NOT NULL DEFAULT 'Custom'
+        CHECK (kind IN ('Custom', 'PreDawn', 'Sunrise', 'Dusk', 'Sunset')),
+    CHECK ((switch_id IS NOT NULL) + (group_id IS NOT NULL) + (scene_id IS NOT NULL) = 1)
+);
+
+INSERT INTO flip_target (id, switch_id, group_id, hour, minute, direction, dow, kind)
+SELECT id, switch_id, group_id, hour, minute, direction, dow, kind
+FROM flip;
+
+DROP TABLE flip;
+
+ALTER TABLE flip_target RENAME TO flip;
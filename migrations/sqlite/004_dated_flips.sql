@@ -0,0 +1,3 @@
+-- one-off flips fire only on their date
+-- and ignore the dow mask
+ALTER TABLE flip ADD COLUMN date TEXT;
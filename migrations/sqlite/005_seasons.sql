@@ -0,0 +1,5 @@
+-- a season limits a recurring flip to part of
+-- the year, both ends are stored as month * 100 + day
+-- and the range wraps the new year when start > end
+ALTER TABLE flip ADD COLUMN season_start INTEGER;
+ALTER TABLE flip ADD COLUMN season_end INTEGER;
@@ -0,0 +1,3 @@
+-- solar flips fire this many minutes before (negative)
+-- or after their special time
+ALTER TABLE flip ADD COLUMN offset_minutes INTEGER NOT NULL DEFAULT 0;
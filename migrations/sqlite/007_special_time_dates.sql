@@ -0,0 +1,12 @@
+-- solar flips are resolved against the special
+-- time for each date, so keep one row per date
+-- and kind, the last one recorded wins
+DELETE FROM special_time
+WHERE id NOT IN (
+    SELECT MAX(id)
+    FROM special_time
+    GROUP BY date, kind
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS special_time_date_kind
+    ON special_time (date, kind);
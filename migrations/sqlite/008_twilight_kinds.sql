@@ -0,0 +1,40 @@
+-- rebuilt to add the twilight kinds to the
+-- kind check, postgres adds them one at a
+-- time in 8 to 14
+CREATE TABLE flip_kinds
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    switch_id INTEGER
+        REFERENCES switch (id)
+        ON DELETE CASCADE,
+    group_id INTEGER
+        REFERENCES switch_group (id)
+        ON DELETE CASCADE,
+    scene_id INTEGER
+        REFERENCES scene (id)
+        ON DELETE CASCADE,
+    hour INTEGER NOT NULL,
+    minute INTEGER NOT NULL,
+    direction TEXT NOT NULL
+        CHECK (direction IN ('Off', 'On')),
+    dow INTEGER NOT NULL,
+    kind TEXT NOT NULL DEFAULT 'Custom'
+        CHECK (kind IN ('Custom', 'PreDawn', 'Sunrise', 'Dusk', 'Sunset',
+                        'CivilDawn', 'CivilDusk', 'NauticalDawn', 'NauticalDusk',
+                        'AstronomicalDawn', 'AstronomicalDusk', 'SolarNoon')),
+    date TEXT,
+    season_start INTEGER,
+    season_end INTEGER,
+    offset_minutes INTEGER NOT NULL DEFAULT 0,
+    CHECK ((switch_id IS NOT NULL) + (group_id IS NOT NULL) + (scene_id IS NOT NULL) = 1)
+);
+
+INSERT INTO flip_kinds (id, switch_id, group_id, scene_id, hour, minute, direction, dow, kind,
+                        date, season_start, season_end, offset_minutes)
+SELECT id, switch_id, group_id, scene_id, hour, minute, direction, dow, kind,
+       date, season_start, season_end, offset_minutes
+FROM flip;
+
+DROP TABLE flip;
+
+ALTER TABLE flip_kinds RENAME TO flip;
@@ -0,0 +1,13 @@
+-- one row for each refresher run, source is
+-- the provider the times came from or `stored`
+-- when none could be reached
+CREATE TABLE IF NOT EXISTS refresh_log
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    refreshed TEXT NOT NULL,
+    date TEXT NOT NULL,
+    source TEXT NOT NULL,
+    succeeded INTEGER NOT NULL,
+    attempts INTEGER NOT NULL,
+    message TEXT
+);
@@ -0,0 +1,5 @@
+-- the minutes refresher moved a special time by
+-- for the weather, hour and minute already have
+-- it applied
+ALTER TABLE special_time
+    ADD COLUMN adjustment INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,8 @@
+-- a single row, the instant the scheduler last
+-- sent the flips that were due so a restart
+-- doesn't send them again
+CREATE TABLE IF NOT EXISTS schedule_state
+(
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    last_processed TEXT NOT NULL
+);
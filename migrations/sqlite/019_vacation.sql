@@ -0,0 +1,7 @@
+-- a single row, whether the scheduler should
+-- make the house look lived-in while we are away
+CREATE TABLE IF NOT EXISTS vacation
+(
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    enabled INTEGER NOT NULL DEFAULT 0
+);
@@ -0,0 +1,12 @@
+-- minutes an On flip leaves its switches on
+-- before they are turned back off
+ALTER TABLE flip ADD COLUMN duration INTEGER CHECK (duration > 0);
+
+-- the off codes waiting to be sent after a
+-- timed flip, until the scheduler sends them
+CREATE TABLE IF NOT EXISTS pending_off
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    code INTEGER NOT NULL,
+    due TEXT NOT NULL
+);
@@ -0,0 +1,18 @@
+-- a single row, whether the scheduler is holding
+-- back every flip and when it should start again
+CREATE TABLE IF NOT EXISTS schedule_pause
+(
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    paused INTEGER NOT NULL DEFAULT 0,
+    resume_at TEXT
+);
+
+-- switches the scheduler leaves alone, until
+-- the hold is removed when `until` is NULL
+CREATE TABLE IF NOT EXISTS switch_hold
+(
+    switch_id INTEGER PRIMARY KEY
+        REFERENCES switch (id)
+        ON DELETE CASCADE,
+    until TEXT
+);